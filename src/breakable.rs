@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+
+use crate::carry::Thrown;
+use crate::health::DamageEvent;
+use crate::player::GroundPoundEvent;
+
+pub struct BreakablePlugin;
+
+impl Plugin for BreakablePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Breakable>()
+            .init_resource::<BreakableConfig>()
+            .add_event::<BlockBrokenEvent>()
+            .add_systems(Startup, load_debris_assets)
+            .add_systems(
+                Update,
+                (ground_pound_damage, damage_breakable, fade_debris).chain(),
+            )
+            // Collision events come from the physics step in `PostUpdate`
+            .add_systems(PostUpdate, thrown_damage.after(PhysicsSet::Sync));
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct BreakableConfig {
+    pub ground_pound_damage: f32,
+    /// Damage of a thrown object's first hit
+    pub thrown_damage: f32,
+    /// Seconds the debris of a broken block lies around
+    pub debris_lifetime: f32,
+}
+
+impl Default for BreakableConfig {
+    fn default() -> Self {
+        Self {
+            ground_pound_damage: 50.,
+            thrown_damage: 25.,
+            debris_lifetime: 3.,
+        }
+    }
+}
+
+/// Level geometry that takes `DamageEvent`s like an actor, and goes away once
+/// its `health` runs out, leaving `debris` pieces behind.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Breakable {
+    pub health: f32,
+    pub debris: u32,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct BlockBrokenEvent {
+    pub entity: Entity,
+    pub position: Vec3,
+}
+
+/// Piece of a broken block, despawned once `remaining` runs out.
+#[derive(Component, Debug)]
+struct Debris {
+    remaining: f32,
+}
+
+#[derive(Resource)]
+struct DebrisAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+const DEBRIS_SIZE: f32 = 0.15;
+const DEBRIS_SPEED: f32 = 3.;
+
+fn load_debris_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(DebrisAssets {
+        mesh: meshes.add(Mesh::from(shape::Cube { size: DEBRIS_SIZE })),
+        material: materials.add(Color::rgb(0.5, 0.4, 0.3).into()),
+    });
+}
+
+/// The breakable the collider belongs to, which may be a node above the mesh.
+fn find_breakable(
+    entity: Entity,
+    breakables: &Query<(), With<Breakable>>,
+    parents: &Query<&Parent>,
+) -> Option<Entity> {
+    std::iter::once(entity)
+        .chain(parents.iter_ancestors(entity))
+        .find(|&entity| breakables.contains(entity))
+}
+
+fn ground_pound_damage(
+    mut ground_pound: EventReader<GroundPoundEvent>,
+    mut damage: EventWriter<DamageEvent>,
+    breakables: Query<(), With<Breakable>>,
+    parents: Query<&Parent>,
+    config: Res<BreakableConfig>,
+) {
+    for ev in ground_pound.read() {
+        if let Some(target) = ev
+            .ground
+            .and_then(|ground| find_breakable(ground, &breakables, &parents))
+        {
+            damage.send(DamageEvent {
+                target,
+                amount: config.ground_pound_damage,
+            });
+        }
+    }
+}
+
+fn thrown_damage(
+    mut started: EventReader<CollisionStarted>,
+    mut damage: EventWriter<DamageEvent>,
+    thrown: Query<(), With<Thrown>>,
+    breakables: Query<(), With<Breakable>>,
+    parents: Query<&Parent>,
+    config: Res<BreakableConfig>,
+) {
+    for CollisionStarted(entity1, entity2) in started.read() {
+        for (projectile, other) in [(*entity1, *entity2), (*entity2, *entity1)] {
+            if !thrown.contains(projectile) {
+                continue;
+            }
+            if let Some(target) = find_breakable(other, &breakables, &parents) {
+                damage.send(DamageEvent {
+                    target,
+                    amount: config.thrown_damage,
+                });
+            }
+        }
+    }
+}
+
+// Blocks have no i-frames, every hit counts
+fn damage_breakable(
+    mut commands: Commands,
+    mut damage: EventReader<DamageEvent>,
+    mut breakables: Query<(&mut Breakable, &GlobalTransform)>,
+    mut broken: EventWriter<BlockBrokenEvent>,
+    assets: Res<DebrisAssets>,
+    config: Res<BreakableConfig>,
+) {
+    for ev in damage.read() {
+        let Ok((mut breakable, transform)) = breakables.get_mut(ev.target) else {
+            continue;
+        };
+        // Already broken this frame
+        if breakable.health <= 0. {
+            continue;
+        }
+
+        breakable.health -= ev.amount;
+        if breakable.health > 0. {
+            continue;
+        }
+
+        let position = transform.translation();
+        commands.entity(ev.target).despawn_recursive();
+        broken.send(BlockBrokenEvent {
+            entity: ev.target,
+            position,
+        });
+
+        for i in 0..breakable.debris {
+            // Spread evenly around, flying up and out
+            let angle = i as f32 / breakable.debris as f32 * std::f32::consts::TAU;
+            let outward = Vec3::new(angle.cos(), 0., angle.sin());
+            commands.spawn((
+                Name::new("Debris"),
+                Debris {
+                    remaining: config.debris_lifetime,
+                },
+                PbrBundle {
+                    mesh: assets.mesh.clone(),
+                    material: assets.material.clone(),
+                    transform: Transform::from_translation(position + outward * DEBRIS_SIZE),
+                    ..default()
+                },
+                RigidBody::Dynamic,
+                Collider::cuboid(DEBRIS_SIZE, DEBRIS_SIZE, DEBRIS_SIZE),
+                LinearVelocity((outward + Vec3::Y) * DEBRIS_SPEED),
+            ));
+        }
+    }
+}
+
+fn fade_debris(mut commands: Commands, mut debris: Query<(Entity, &mut Debris)>, time: Res<Time>) {
+    for (entity, mut debris) in debris.iter_mut() {
+        debris.remaining -= time.delta_seconds();
+        if debris.remaining <= 0. {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use crate::input::Action;
+use crate::interaction::{InteractEvent, Interactable};
+use crate::player::Player;
+
+pub struct CarryPlugin;
+
+impl Plugin for CarryPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Carryable>()
+            .add_systems(
+                Update,
+                (make_carryable, pick_up, hold_carried, throw_carried).chain(),
+            )
+            .add_systems(PostUpdate, land_thrown.after(PhysicsSet::Sync));
+    }
+}
+
+/// Dynamic prop the player can pick up with Interact and throw.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Carryable;
+
+/// On the player while holding `entity`.
+#[derive(Component, Debug)]
+pub struct Carrying {
+    pub entity: Entity,
+}
+
+/// On a `Carryable` while it's held.
+#[derive(Component, Debug)]
+pub struct Carried {
+    pub by: Entity,
+}
+
+/// On a `Carryable` from being thrown until it first hits something.
+#[derive(Component, Debug)]
+pub struct Thrown {
+    pub by: Entity,
+}
+
+// Hold point in front of the player
+const HOLD_DISTANCE: f32 = 0.8;
+const HOLD_HEIGHT: f32 = 0.3;
+
+const THROW_SPEED: f32 = 6.0;
+const THROW_LIFT: f32 = 2.0;
+
+fn hold_point(transform: &Transform) -> Vec3 {
+    transform.translation + transform.forward() * HOLD_DISTANCE + Vec3::Y * HOLD_HEIGHT
+}
+
+fn make_carryable(mut commands: Commands, carryables: Query<Entity, Added<Carryable>>) {
+    for entity in carryables.iter() {
+        commands.entity(entity).insert(Interactable);
+    }
+}
+
+fn pick_up(
+    mut commands: Commands,
+    mut interact: EventReader<InteractEvent>,
+    carryables: Query<(), (With<Carryable>, Without<Carried>)>,
+    player: Query<(), (With<Player>, Without<Carrying>)>,
+) {
+    for ev in interact.read() {
+        if !carryables.contains(ev.target) || !player.contains(ev.player) {
+            continue;
+        }
+
+        commands
+            .entity(ev.player)
+            .insert(Carrying { entity: ev.target });
+        // Kinematic so it follows the hold point, sensor so it doesn't shove the player
+        commands.entity(ev.target).insert((
+            Carried { by: ev.player },
+            RigidBody::Kinematic,
+            Sensor,
+            LinearVelocity::ZERO,
+            AngularVelocity::ZERO,
+        ));
+    }
+}
+
+fn hold_carried(
+    mut commands: Commands,
+    player: Query<(Entity, &Carrying, &Transform), With<Player>>,
+    mut carried: Query<(&mut Position, &mut Rotation), (With<Carried>, Without<Player>)>,
+) {
+    for (entity, carrying, transform) in player.iter() {
+        let Ok((mut position, mut rotation)) = carried.get_mut(carrying.entity) else {
+            // Despawned while held
+            commands.entity(entity).remove::<Carrying>();
+            continue;
+        };
+
+        position.0 = hold_point(transform);
+        rotation.0 = transform.rotation;
+    }
+}
+
+fn throw_carried(
+    mut commands: Commands,
+    player: Query<
+        (
+            Entity,
+            &Carrying,
+            &Transform,
+            &ActionState<Action>,
+            &LinearVelocity,
+        ),
+        With<Player>,
+    >,
+    mut carried: Query<
+        (&Collider, &mut Position, &Rotation, &mut LinearVelocity),
+        (With<Carried>, Without<Player>),
+    >,
+    spatial_query: SpatialQuery,
+) {
+    for (entity, carrying, transform, input, player_velocity) in player.iter() {
+        if !(input.just_pressed(Action::Interact) || input.just_pressed(Action::Throw)) {
+            continue;
+        }
+        let Ok((collider, mut position, rotation, mut velocity)) = carried.get_mut(carrying.entity)
+        else {
+            continue;
+        };
+
+        // The held object passes through walls, so pull it back out of any
+        // terrain between the player and the hold point before it turns solid
+        let origin = transform.translation + Vec3::Y * HOLD_HEIGHT;
+        let offset = position.0 - origin;
+        if let Some(direction) = offset.try_normalize() {
+            if let Some(hit) = spatial_query.cast_shape(
+                collider,
+                origin,
+                rotation.0,
+                direction,
+                offset.length(),
+                true,
+                SpatialQueryFilter::new().without_entities([entity, carrying.entity]),
+            ) {
+                position.0 = origin + direction * hit.time_of_impact;
+            }
+        }
+
+        velocity.0 = player_velocity.0 + transform.forward() * THROW_SPEED + Vec3::Y * THROW_LIFT;
+
+        commands
+            .entity(carrying.entity)
+            .remove::<(Carried, Sensor)>()
+            .insert((RigidBody::Dynamic, Thrown { by: entity }));
+        commands.entity(entity).remove::<Carrying>();
+    }
+}
+
+fn land_thrown(
+    mut commands: Commands,
+    mut started: EventReader<CollisionStarted>,
+    thrown: Query<(), With<Thrown>>,
+) {
+    for CollisionStarted(entity1, entity2) in started.read() {
+        for &entity in [entity1, entity2] {
+            if thrown.contains(entity) {
+                commands.entity(entity).remove::<Thrown>();
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::player::Player;
+use crate::save::CollectedItems;
+use crate::terrain::LevelUnloadedEvent;
+use crate::zone::{make_zone_sensor, SensorOverlap};
+
+pub struct CollectiblePlugin;
+
+impl Plugin for CollectiblePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Collectible>()
+            .register_type::<CollectibleKind>()
+            .init_resource::<Score>()
+            .init_resource::<LevelCollectibles>()
+            .add_event::<CollectedEvent>()
+            .add_systems(
+                Update,
+                (
+                    reset_level_collectibles,
+                    (make_zone_sensor::<Collectible>, init_collectible),
+                    collect,
+                    animate_collectible,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash, Debug, Reflect)]
+pub enum CollectibleKind {
+    #[default]
+    Coin,
+    Gem,
+    Star,
+}
+
+/// Picked up by walking into it. Named nodes stay collected across saves.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Collectible {
+    pub kind: CollectibleKind,
+    pub value: u32,
+}
+
+/// Sum of the values picked up, overall and per kind.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct Score {
+    pub total: u32,
+    pub by_kind: HashMap<CollectibleKind, u32>,
+}
+
+impl Score {
+    fn add(&mut self, collectible: &Collectible) {
+        self.total += collectible.value;
+        *self.by_kind.entry(collectible.kind).or_default() += collectible.value;
+    }
+}
+
+/// How many collectibles the current level has, and how many of them are gone.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct LevelCollectibles {
+    pub total: u32,
+    pub collected: u32,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct CollectedEvent {
+    pub player: Entity,
+    pub kind: CollectibleKind,
+    pub value: u32,
+}
+
+// Spin and bob of the items still lying around
+const SPIN_SPEED: f32 = 2.;
+const BOB_HEIGHT: f32 = 0.1;
+const BOB_SPEED: f32 = 3.;
+
+#[derive(Component)]
+struct CollectibleAnimation {
+    origin: Transform,
+}
+
+fn reset_level_collectibles(
+    mut unloaded: EventReader<LevelUnloadedEvent>,
+    mut level: ResMut<LevelCollectibles>,
+) {
+    if unloaded.read().count() > 0 {
+        *level = LevelCollectibles::default();
+    }
+}
+
+fn init_collectible(
+    mut commands: Commands,
+    collectibles: Query<(Entity, &Collectible, &Transform, Option<&Name>), Added<Collectible>>,
+    collected_items: Res<CollectedItems>,
+    mut level: ResMut<LevelCollectibles>,
+    mut score: ResMut<Score>,
+) {
+    for (entity, collectible, transform, name) in collectibles.iter() {
+        level.total += 1;
+
+        // Picked up in an earlier session
+        if name.is_some_and(|name| collected_items.0.contains(name.as_str())) {
+            level.collected += 1;
+            score.add(collectible);
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        commands
+            .entity(entity)
+            .insert(CollectibleAnimation { origin: *transform });
+    }
+}
+
+#[derive(SystemParam)]
+struct Collection<'w, 's> {
+    commands: Commands<'w, 's>,
+    score: ResMut<'w, Score>,
+    level: ResMut<'w, LevelCollectibles>,
+    collected_items: ResMut<'w, CollectedItems>,
+    collected: EventWriter<'w, CollectedEvent>,
+}
+
+impl Collection<'_, '_> {
+    fn collect(
+        &mut self,
+        player: Entity,
+        entity: Entity,
+        collectible: &Collectible,
+        name: Option<&Name>,
+    ) {
+        debug_print!(
+            "collected {:?} worth {}",
+            collectible.kind,
+            collectible.value
+        );
+
+        self.score.add(collectible);
+        self.level.collected += 1;
+        if let Some(name) = name {
+            self.collected_items.0.insert(name.to_string());
+        }
+        self.collected.send(CollectedEvent {
+            player,
+            kind: collectible.kind,
+            value: collectible.value,
+        });
+        self.commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn collect(
+    mut overlaps: EventReader<SensorOverlap>,
+    collectibles: Query<(&Collectible, Option<&Name>)>,
+    player: Query<(), With<Player>>,
+    mut collection: Collection,
+) {
+    for overlap in overlaps.read().filter(|overlap| overlap.entered()) {
+        let Ok((collectible, name)) = collectibles.get(overlap.zone) else {
+            continue;
+        };
+        if !player.contains(overlap.other) {
+            continue;
+        }
+
+        collection.collect(overlap.other, overlap.zone, collectible, name);
+    }
+}
+
+fn animate_collectible(
+    mut collectibles: Query<(&CollectibleAnimation, &mut Transform)>,
+    time: Res<Time>,
+) {
+    let t = time.elapsed_seconds();
+    for (animation, mut transform) in collectibles.iter_mut() {
+        transform.rotation = Quat::from_rotation_y(t * SPIN_SPEED) * animation.origin.rotation;
+        transform.translation =
+            animation.origin.translation + Vec3::Y * (t * BOB_SPEED).sin() * BOB_HEIGHT;
+    }
+}
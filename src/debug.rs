@@ -0,0 +1,202 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use bevy_debug_text_overlay::OverlayPlugin;
+use bevy_xpbd_3d::prelude::*;
+
+use crate::interaction::InteractionRayCaster;
+use crate::player::{Player, PlayerGrounded, PlayerJumping, PlayerMovingOnLadder, PlayerSlot};
+use crate::terrain::Ladder;
+
+/// Developer overlays, toggled at runtime. Only built with the `debug` feature.
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            PhysicsDebugPlugin::default(),
+            OverlayPlugin::default(),
+            FrameTimeDiagnosticsPlugin,
+        ))
+        .init_resource::<DebugOverlay>()
+        .add_systems(Startup, (apply_gizmo_toggle, spawn_diagnostics_text))
+        .add_systems(
+            Update,
+            (
+                (toggle_gizmos, toggle_text, toggle_diagnostics),
+                apply_gizmo_toggle.run_if(resource_changed::<DebugOverlay>()),
+                (draw_ladders, draw_interaction_rays).run_if(gizmos_enabled),
+                update_diagnostics_text,
+            )
+                .chain(),
+        );
+    }
+}
+
+const TOGGLE_GIZMOS_KEY: KeyCode = KeyCode::F3;
+const TOGGLE_TEXT_KEY: KeyCode = KeyCode::F4;
+const TOGGLE_DIAGNOSTICS_KEY: KeyCode = KeyCode::F5;
+
+/// Whether `debug_print!` goes to the screen. Static so the macro works
+/// anywhere, without a system parameter.
+static SHOW_TEXT: AtomicBool = AtomicBool::new(true);
+
+pub fn text_enabled() -> bool {
+    SHOW_TEXT.load(Ordering::Relaxed)
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct DebugOverlay {
+    /// Collider wireframes, ladders and interaction rays
+    pub gizmos: bool,
+    /// Frame rate, physics load and player state in the corner
+    pub diagnostics: bool,
+}
+
+fn gizmos_enabled(overlay: Res<DebugOverlay>) -> bool {
+    overlay.gizmos
+}
+
+fn toggle_gizmos(keys: Res<Input<KeyCode>>, mut overlay: ResMut<DebugOverlay>) {
+    if keys.just_pressed(TOGGLE_GIZMOS_KEY) {
+        overlay.gizmos = !overlay.gizmos;
+    }
+}
+
+fn toggle_diagnostics(keys: Res<Input<KeyCode>>, mut overlay: ResMut<DebugOverlay>) {
+    if keys.just_pressed(TOGGLE_DIAGNOSTICS_KEY) {
+        overlay.diagnostics = !overlay.diagnostics;
+    }
+}
+
+// Lines already on screen fade out on their own
+fn toggle_text(keys: Res<Input<KeyCode>>) {
+    if keys.just_pressed(TOGGLE_TEXT_KEY) {
+        SHOW_TEXT.fetch_xor(true, Ordering::Relaxed);
+    }
+}
+
+// The physics debug renderer draws through gizmos as well, so this hides it too
+fn apply_gizmo_toggle(overlay: Res<DebugOverlay>, mut config: ResMut<GizmoConfig>) {
+    config.enabled = overlay.gizmos;
+}
+
+fn draw_ladders(mut gizmos: Gizmos, ladders: Query<(&Ladder, &Position)>) {
+    for (ladder, position) in ladders.iter() {
+        let top = position.0 + ladder.up * ladder.half_height;
+        let bottom = position.0 - ladder.up * ladder.half_height;
+        gizmos.line(bottom, top, Color::YELLOW);
+        gizmos.sphere(top, Quat::IDENTITY, 0.1, Color::GREEN);
+        gizmos.sphere(bottom, Quat::IDENTITY, 0.1, Color::RED);
+        gizmos.ray(position.0, ladder.face_normal, Color::BLUE);
+    }
+}
+
+fn draw_interaction_rays(
+    mut gizmos: Gizmos,
+    rays: Query<(&RayCaster, &RayHits), With<InteractionRayCaster>>,
+) {
+    for (ray, hits) in rays.iter() {
+        let origin = ray.global_origin();
+        let direction = ray.global_direction();
+        gizmos.ray(origin, direction * ray.max_time_of_impact, Color::CYAN);
+        for hit in hits.iter() {
+            let point = origin + direction * hit.time_of_impact;
+            gizmos.sphere(point, Quat::IDENTITY, 0.05, Color::ORANGE_RED);
+            gizmos.ray(point, hit.normal * 0.3, Color::ORANGE_RED);
+        }
+    }
+}
+
+// Diagnostics
+
+#[derive(Component)]
+struct DiagnosticsText;
+
+fn spawn_diagnostics_text(mut commands: Commands) {
+    commands.spawn((
+        Name::new("DiagnosticsText"),
+        DiagnosticsText,
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 16.,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.),
+                right: Val::Px(8.),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.5).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+}
+
+fn update_diagnostics_text(
+    mut text: Query<(&mut Text, &mut Visibility), With<DiagnosticsText>>,
+    overlay: Res<DebugOverlay>,
+    diagnostics: Res<DiagnosticsStore>,
+    // Sleeping bodies and the static level cost the solver next to nothing
+    bodies: Query<&RigidBody, Without<Sleeping>>,
+    player: Query<
+        (
+            Option<&PlayerSlot>,
+            Has<PlayerGrounded>,
+            Has<PlayerJumping>,
+            Has<PlayerMovingOnLadder>,
+        ),
+        With<Player>,
+    >,
+) {
+    let Ok((mut text, mut visibility)) = text.get_single_mut() else {
+        return;
+    };
+    visibility.set_if_neq(if overlay.diagnostics {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    });
+    if !overlay.diagnostics {
+        return;
+    }
+
+    let smoothed = |path| {
+        diagnostics
+            .get(path)
+            .and_then(|diagnostic| diagnostic.smoothed())
+            .unwrap_or_default()
+    };
+    let mut lines = vec![
+        format!("FPS: {:.0}", smoothed(FrameTimeDiagnosticsPlugin::FPS)),
+        format!(
+            "Frame time: {:.1} ms",
+            smoothed(FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        ),
+        format!(
+            "Active bodies: {}",
+            bodies.iter().filter(|body| !body.is_static()).count()
+        ),
+    ];
+    for (slot, grounded, jumping, on_ladder) in player.iter() {
+        // Other states such as swimming or hanging show up as neither
+        let state = if on_ladder {
+            "MovingOnLadder"
+        } else if jumping {
+            "Jumping"
+        } else if grounded {
+            "Grounded"
+        } else {
+            "-"
+        };
+        lines.push(format!("Player {}: {state}", slot.map_or(0, |slot| slot.0)));
+    }
+    text.sections[0].value = lines.join("\n");
+}
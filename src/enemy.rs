@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_tnua::controller::TnuaController;
+use bevy_xpbd_3d::prelude::*;
+use seldom_state::prelude::*;
+
+use crate::game::GameState;
+use crate::health::DamageEvent;
+use crate::platform::{LoopMode, PlatformRoute};
+use crate::player::{Player, PlayerGroundPounding, PlayerJumping};
+use crate::terrain::MakeCollider;
+use crate::zone::apply_impulse;
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Enemy>()
+            .init_resource::<EnemyConfig>()
+            .add_event::<EnemyDefeatedEvent>()
+            .add_systems(Update, (init_enemy, start_patrol).chain())
+            .add_systems(
+                Update,
+                (patrol, contact_damage).run_if(in_state(GameState::Playing)),
+            )
+            // Before the solver, while the player still has the velocity they came in with
+            .add_systems(PostProcessCollisions, stomp_enemy);
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct EnemyConfig {
+    /// Damage of touching an enemy, dealt again once the i-frames end
+    pub contact_damage: f32,
+    /// Steepest angle from straight up of the contact normal that still counts
+    /// as landing on top
+    pub stomp_max_angle: f32,
+    /// Upward speed the player bounces off a stomped enemy with
+    pub stomp_bounce: f32,
+}
+
+impl Default for EnemyConfig {
+    fn default() -> Self {
+        Self {
+            contact_damage: 20.,
+            stomp_max_angle: 45f32.to_radians(),
+            stomp_bounce: 8.,
+        }
+    }
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct EnemyDefeatedEvent {
+    pub enemy: Entity,
+    pub by: Entity,
+}
+
+/// Walks through `patrol`, offsets from where it's placed in the level, and
+/// turns back at either end. Hurts the player on contact.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Enemy {
+    pub patrol: Vec<Vec3>,
+    pub speed: f32,
+}
+
+fn init_enemy(
+    mut commands: Commands,
+    enemies: Query<(Entity, Has<MakeCollider>, Has<Collider>), Added<Enemy>>,
+) {
+    for (entity, make_collider, has_collider) in enemies.iter() {
+        let mut entity = commands.entity(entity);
+        // The collider comes from the mesh like any other, unless the node asks for one itself
+        if !make_collider && !has_collider {
+            entity.insert(MakeCollider(true));
+        }
+        entity.insert((RigidBody::Kinematic, CollidingEntities::default()));
+    }
+}
+
+fn start_patrol(
+    mut commands: Commands,
+    enemies: Query<(Entity, &Position), (With<Enemy>, Without<PlatformRoute>)>,
+) {
+    // Waits for the collider to have a `Position`
+    for (entity, position) in enemies.iter() {
+        commands
+            .entity(entity)
+            .insert(PlatformRoute::new(position.0));
+    }
+}
+
+fn patrol(mut enemies: Query<(&Enemy, &mut PlatformRoute, &mut Position)>, time: Res<Time>) {
+    for (enemy, mut route, mut position) in enemies.iter_mut() {
+        route.step(
+            &mut position.0,
+            &enemy.patrol,
+            LoopMode::PingPong,
+            enemy.speed * time.delta_seconds(),
+        );
+    }
+}
+
+// Sent every frame of contact, the player's i-frames keep it to one hit at a time
+fn contact_damage(
+    enemies: Query<&CollidingEntities, With<Enemy>>,
+    player: Query<(), With<Player>>,
+    mut damage: EventWriter<DamageEvent>,
+    config: Res<EnemyConfig>,
+) {
+    for colliding in enemies.iter() {
+        for &entity in colliding.iter() {
+            if player.contains(entity) {
+                damage.send(DamageEvent {
+                    target: entity,
+                    amount: config.contact_damage,
+                });
+            }
+        }
+    }
+}
+
+/// Landing on an enemy from above during a jump or a ground pound defeats it
+/// and bounces the player off. Anything else is left to `contact_damage`.
+fn stomp_enemy(
+    mut commands: Commands,
+    enemies: Query<(&Rotation, Option<&LinearVelocity>), With<Enemy>>,
+    mut player: Query<
+        (&mut LinearVelocity, Has<PlayerGroundPounding>),
+        (
+            With<Player>,
+            Or<(With<PlayerJumping>, With<PlayerGroundPounding>)>,
+            With<TnuaController>,
+            Without<Enemy>,
+        ),
+    >,
+    mut collisions: ResMut<Collisions>,
+    mut defeated: EventWriter<EnemyDefeatedEvent>,
+    config: Res<EnemyConfig>,
+    // Stomped in an earlier substep, awaiting the despawn
+    mut stomped: Local<HashSet<Entity>>,
+) {
+    stomped.retain(|&enemy| enemies.contains(enemy));
+
+    collisions.retain(|contacts| {
+        // Normals point out of their own entity, so take the enemy's side
+        let (enemy, player_entity, enemy_first) =
+            if enemies.contains(contacts.entity1) && player.contains(contacts.entity2) {
+                (contacts.entity1, contacts.entity2, true)
+            } else if enemies.contains(contacts.entity2) && player.contains(contacts.entity1) {
+                (contacts.entity2, contacts.entity1, false)
+            } else {
+                return true;
+            };
+        if stomped.contains(&enemy) {
+            return false;
+        }
+        let Ok((rotation, enemy_velocity)) = enemies.get(enemy) else {
+            return true;
+        };
+        let Ok((mut velocity, pounding)) = player.get_mut(player_entity) else {
+            return true;
+        };
+
+        let on_top = contacts.manifolds.iter().any(|manifold| {
+            let normal = if enemy_first {
+                manifold.normal1
+            } else {
+                manifold.normal2
+            };
+            let normal = rotation.0 * normal;
+            normal.angle_between(Vec3::Y) <= config.stomp_max_angle
+        });
+        // Coming down onto it, not standing on it or brushing past the top edge
+        let relative = velocity.0 - enemy_velocity.map_or(Vec3::ZERO, |v| v.0);
+        if !on_top || relative.y >= 0. {
+            return true;
+        }
+
+        stomped.insert(enemy);
+        commands.entity(enemy).despawn_recursive();
+        defeated.send(EnemyDefeatedEvent {
+            enemy,
+            by: player_entity,
+        });
+        // Every stomp bounces as high, however fast the player came down
+        velocity.0.y = 0.;
+        apply_impulse(&mut velocity, Vec3::Y * config.stomp_bounce);
+        // Or the slam would take the bounce straight back
+        if pounding {
+            commands.entity(player_entity).insert(Done::Success);
+        }
+        // No pushing back or stopping the player on a body that's going away
+        false
+    });
+}
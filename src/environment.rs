@@ -0,0 +1,312 @@
+use bevy::core_pipeline::Skybox;
+use bevy::pbr::FogFalloff;
+use bevy::prelude::*;
+use bevy::render::render_resource::{TextureViewDescriptor, TextureViewDimension};
+
+use crate::player::PlayerFollowingCamera;
+use crate::terrain::LevelUnloadedEvent;
+
+pub struct EnvironmentPlugin;
+
+impl Plugin for EnvironmentPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LevelEnvironment>()
+            .register_type::<LevelFog>()
+            .register_type::<LevelFogFalloff>()
+            .init_resource::<ActiveEnvironment>()
+            .init_resource::<FogTransition>()
+            .add_systems(Startup, reset_environment)
+            .add_systems(
+                Update,
+                (
+                    reset_environment.run_if(on_event::<LevelUnloadedEvent>()),
+                    apply_level_environment,
+                    update_camera_environment,
+                    reinterpret_cubemap,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    clear_fog.run_if(on_event::<LevelUnloadedEvent>()),
+                    apply_level_fog,
+                    update_camera_fog,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Sky and ambient lighting of the level, tagged on any one node of it.
+/// Levels without one get `LevelEnvironment::default()`.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct LevelEnvironment {
+    /// Cubemap of the sky, a cube .ktx2 or an image of the six faces stacked
+    /// vertically. Empty for none.
+    pub skybox: String,
+    pub ambient_color: Color,
+    pub ambient_brightness: f32,
+    /// Prefiltered maps for lighting from the surroundings, both or neither
+    pub diffuse_map: String,
+    pub specular_map: String,
+}
+
+impl Default for LevelEnvironment {
+    fn default() -> Self {
+        Self {
+            skybox: String::new(),
+            ambient_color: Color::WHITE,
+            ambient_brightness: 0.2,
+            diffuse_map: String::new(),
+            specular_map: String::new(),
+        }
+    }
+}
+
+/// What the cameras get from the current `LevelEnvironment`.
+#[derive(Resource, Default)]
+struct ActiveEnvironment {
+    skybox: Option<Handle<Image>>,
+    environment_map: Option<EnvironmentMapLight>,
+}
+
+fn reset_environment(mut commands: Commands, asset_server: Res<AssetServer>) {
+    apply_environment(&mut commands, &asset_server, &LevelEnvironment::default());
+}
+
+fn apply_environment(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    environment: &LevelEnvironment,
+) {
+    commands.insert_resource(AmbientLight {
+        color: environment.ambient_color,
+        brightness: environment.ambient_brightness,
+    });
+
+    let load = |path: &str| (!path.is_empty()).then(|| asset_server.load(path.to_string()));
+    let environment_map = match (
+        load(&environment.diffuse_map),
+        load(&environment.specular_map),
+    ) {
+        (Some(diffuse_map), Some(specular_map)) => Some(EnvironmentMapLight {
+            diffuse_map,
+            specular_map,
+        }),
+        (None, None) => None,
+        _ => {
+            warn!("Environment map needs both a diffuse and a specular map");
+            None
+        }
+    };
+    commands.insert_resource(ActiveEnvironment {
+        skybox: load(&environment.skybox),
+        environment_map,
+    });
+}
+
+fn apply_level_environment(
+    mut commands: Commands,
+    environments: Query<&LevelEnvironment, Added<LevelEnvironment>>,
+    asset_server: Res<AssetServer>,
+) {
+    for environment in environments.iter() {
+        apply_environment(&mut commands, &asset_server, environment);
+    }
+}
+
+// Split-screen cameras come and go, so they're caught up as they're added
+fn update_camera_environment(
+    mut commands: Commands,
+    cameras: Query<Entity, With<PlayerFollowingCamera>>,
+    added: Query<(), Added<PlayerFollowingCamera>>,
+    active: Res<ActiveEnvironment>,
+) {
+    if !active.is_changed() && added.is_empty() {
+        return;
+    }
+
+    for entity in cameras.iter() {
+        let mut camera = commands.entity(entity);
+        match &active.skybox {
+            Some(skybox) => camera.insert(Skybox(skybox.clone())),
+            None => camera.remove::<Skybox>(),
+        };
+        match &active.environment_map {
+            Some(environment_map) => camera.insert(environment_map.clone()),
+            None => camera.remove::<EnvironmentMapLight>(),
+        };
+    }
+}
+
+// Only a .ktx2 or .dds is a cube by itself, other formats come in as a strip
+fn reinterpret_cubemap(
+    mut asset_ev: EventReader<AssetEvent<Image>>,
+    mut images: ResMut<Assets<Image>>,
+    active: Res<ActiveEnvironment>,
+) {
+    let Some(skybox) = &active.skybox else {
+        asset_ev.clear();
+        return;
+    };
+
+    for ev in asset_ev.read() {
+        if !ev.is_loaded_with_dependencies(skybox) {
+            continue;
+        }
+        let Some(image) = images.get_mut(skybox) else {
+            continue;
+        };
+        if image.texture_descriptor.array_layer_count() == 1 {
+            image.reinterpret_stacked_2d_as_array(image.height() / image.width());
+            image.texture_view_descriptor = Some(TextureViewDescriptor {
+                dimension: Some(TextureViewDimension::Cube),
+                ..default()
+            });
+        }
+    }
+}
+
+// Fog
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Reflect)]
+pub enum LevelFogFalloff {
+    /// From none at the first of `distances` to full at the second
+    #[default]
+    Linear,
+    /// Thinning out gradually, leaving 5% visibility at the second of `distances`
+    Exponential,
+    /// Like `Exponential`, but clearer up close and thicker towards the end
+    ExponentialSquared,
+}
+
+/// Fog over the level, tagged on any one node of it. The cameras ease into it
+/// on loading the level, and back out on leaving for one without.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct LevelFog {
+    /// Alpha is how opaque the fog gets
+    pub color: Color,
+    pub falloff: LevelFogFalloff,
+    pub distances: Vec2,
+}
+
+impl Default for LevelFog {
+    fn default() -> Self {
+        Self {
+            color: Color::rgba(0.6, 0.65, 0.7, 1.),
+            falloff: LevelFogFalloff::Linear,
+            distances: Vec2::new(10., 50.),
+        }
+    }
+}
+
+impl LevelFog {
+    /// The same fog faded out, for levels without one.
+    fn cleared(self) -> Self {
+        Self {
+            color: self.color.with_a(0.),
+            ..self
+        }
+    }
+
+    /// The falloff only changes at the end, it doesn't blend.
+    fn lerp(self, to: Self, t: f32) -> Self {
+        let from = Vec4::from(self.color.as_rgba_f32());
+        let color = from.lerp(Vec4::from(to.color.as_rgba_f32()), t);
+        Self {
+            color: Color::rgba(color.x, color.y, color.z, color.w),
+            falloff: if t < 1. { self.falloff } else { to.falloff },
+            distances: self.distances.lerp(to.distances, t),
+        }
+    }
+
+    fn settings(self) -> FogSettings {
+        // The cameras are HDR with bloom, so a fog brighter than white would
+        // glow. Kept at most white, like the lit scene around it.
+        let [r, g, b, a] = self.color.as_rgba_f32();
+        let falloff = match self.falloff {
+            LevelFogFalloff::Linear => FogFalloff::Linear {
+                start: self.distances.x,
+                end: self.distances.y,
+            },
+            LevelFogFalloff::Exponential => FogFalloff::from_visibility(self.distances.y),
+            LevelFogFalloff::ExponentialSquared => {
+                FogFalloff::from_visibility_squared(self.distances.y)
+            }
+        };
+        FogSettings {
+            color: Color::rgba(r.min(1.), g.min(1.), b.min(1.), a),
+            falloff,
+            ..default()
+        }
+    }
+}
+
+/// Seconds to blend from one level's fog to the next.
+const FOG_TRANSITION_TIME: f32 = 1.5;
+
+/// Blend of the camera fog towards the current level's.
+#[derive(Resource)]
+struct FogTransition {
+    from: LevelFog,
+    to: LevelFog,
+    elapsed: f32,
+}
+
+impl Default for FogTransition {
+    fn default() -> Self {
+        let clear = LevelFog::default().cleared();
+        Self {
+            from: clear,
+            to: clear,
+            elapsed: FOG_TRANSITION_TIME,
+        }
+    }
+}
+
+impl FogTransition {
+    fn current(&self) -> LevelFog {
+        self.from
+            .lerp(self.to, (self.elapsed / FOG_TRANSITION_TIME).min(1.))
+    }
+
+    fn start(&mut self, to: LevelFog) {
+        self.from = self.current();
+        self.to = to;
+        self.elapsed = 0.;
+    }
+}
+
+fn clear_fog(mut transition: ResMut<FogTransition>) {
+    let cleared = transition.to.cleared();
+    transition.start(cleared);
+}
+
+fn apply_level_fog(fogs: Query<&LevelFog, Added<LevelFog>>, mut transition: ResMut<FogTransition>) {
+    for fog in fogs.iter() {
+        transition.start(*fog);
+    }
+}
+
+fn update_camera_fog(
+    mut commands: Commands,
+    cameras: Query<(Entity, Has<FogSettings>), With<PlayerFollowingCamera>>,
+    mut transition: ResMut<FogTransition>,
+    time: Res<Time>,
+) {
+    let blending = transition.elapsed < FOG_TRANSITION_TIME;
+    if blending {
+        transition.elapsed += time.delta_seconds();
+    }
+
+    let settings = transition.current().settings();
+    for (entity, has_fog) in cameras.iter() {
+        // New split-screen cameras get the fog as it is
+        if blending || !has_fog {
+            commands.entity(entity).insert(settings.clone());
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use crate::input::Action;
+use crate::player::LocalPlayers;
+use crate::speedrun::{format_time, SpeedrunTimer};
+use crate::terrain::{CurrentLevel, LoadLevelEvent};
+use crate::zone::LevelCompleteEvent;
+
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state::<GameState>()
+            .add_systems(
+                OnEnter(GameState::MainMenu),
+                (spawn_main_menu, pause_gameplay),
+            )
+            .add_systems(OnEnter(GameState::Paused), spawn_pause_menu)
+            .add_systems(OnEnter(GameState::Results), spawn_results_menu)
+            .add_systems(OnExit(GameState::MainMenu), despawn_menu)
+            .add_systems(OnExit(GameState::Paused), despawn_menu)
+            .add_systems(OnExit(GameState::Results), despawn_menu)
+            // Rendering goes on, but nothing moves outside of gameplay
+            .add_systems(OnEnter(GameState::Playing), resume_gameplay)
+            .add_systems(OnExit(GameState::Playing), pause_gameplay)
+            .add_systems(Update, (toggle_pause, menu_buttons, complete_level));
+    }
+}
+
+#[derive(States, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GameState {
+    #[default]
+    MainMenu,
+    Playing,
+    Paused,
+    /// The level's been completed with nowhere to go on to
+    Results,
+}
+
+// Player input is released along with physics, so movement stops as well
+fn pause_gameplay(mut time: ResMut<Time<Physics>>, mut actions: ResMut<ToggleActions<Action>>) {
+    time.pause();
+    actions.enabled = false;
+}
+
+fn resume_gameplay(mut time: ResMut<Time<Physics>>, mut actions: ResMut<ToggleActions<Action>>) {
+    time.unpause();
+    actions.enabled = true;
+}
+
+fn toggle_pause(
+    keys: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keys.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    match state.get() {
+        GameState::Playing => next_state.set(GameState::Paused),
+        GameState::Paused => next_state.set(GameState::Playing),
+        GameState::MainMenu | GameState::Results => {}
+    }
+}
+
+fn complete_level(
+    mut complete: EventReader<LevelCompleteEvent>,
+    mut load: EventWriter<LoadLevelEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(ev) = complete.read().last() else {
+        return;
+    };
+    match &ev.next_level {
+        Some(path) => load.send(LoadLevelEvent {
+            path: path.clone(),
+            spawn: None,
+        }),
+        None => next_state.set(GameState::Results),
+    }
+}
+
+// Menus
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+
+/// Root of the menu on screen, despawned on leaving its state.
+#[derive(Component)]
+struct MenuRoot;
+
+#[derive(Component, Clone, Copy, Debug)]
+enum MenuButton {
+    Play,
+    /// Starts split-screen with a second player on the gamepad
+    CoOp,
+    /// Plays the current level again from the start
+    Retry,
+    Quit,
+}
+
+fn spawn_main_menu(commands: Commands) {
+    spawn_menu(
+        commands,
+        "Regino",
+        &[
+            (MenuButton::Play, "Start"),
+            (MenuButton::CoOp, "2 Players"),
+            (MenuButton::Quit, "Quit"),
+        ],
+    );
+}
+
+fn spawn_pause_menu(commands: Commands) {
+    spawn_menu(
+        commands,
+        "Paused",
+        &[(MenuButton::Play, "Resume"), (MenuButton::Quit, "Quit")],
+    );
+}
+
+fn spawn_results_menu(commands: Commands, timer: Res<SpeedrunTimer>) {
+    let title = if timer.new_record {
+        format!("New record {}", format_time(timer.elapsed))
+    } else {
+        format!("Clear {}", format_time(timer.elapsed))
+    };
+    spawn_menu(
+        commands,
+        &title,
+        &[(MenuButton::Retry, "Retry"), (MenuButton::Quit, "Quit")],
+    );
+}
+
+fn spawn_menu(mut commands: Commands, title: &str, buttons: &[(MenuButton, &str)]) {
+    commands
+        .spawn((
+            Name::new("Menu"),
+            MenuRoot,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(16.),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.5).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                title,
+                TextStyle {
+                    font_size: 64.,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+            for &(button, label) in buttons {
+                parent
+                    .spawn((
+                        button,
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(200.),
+                                height: Val::Px(50.),
+                                align_items: AlignItems::Center,
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            background_color: BUTTON_COLOR.into(),
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font_size: 32.,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn despawn_menu(mut commands: Commands, menus: Query<Entity, With<MenuRoot>>) {
+    for entity in menus.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn menu_buttons(
+    mut buttons: Query<(&MenuButton, &Interaction, &mut BackgroundColor), Changed<Interaction>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut local_players: ResMut<LocalPlayers>,
+    mut exit: EventWriter<AppExit>,
+    mut load: EventWriter<LoadLevelEvent>,
+    level: Res<CurrentLevel>,
+) {
+    for (button, interaction, mut color) in buttons.iter_mut() {
+        match interaction {
+            Interaction::Pressed => match button {
+                MenuButton::Play => next_state.set(GameState::Playing),
+                // Takes effect as the level spawns its players
+                MenuButton::CoOp => {
+                    local_players.0 = 2;
+                    next_state.set(GameState::Playing);
+                }
+                MenuButton::Retry => {
+                    load.send(LoadLevelEvent {
+                        path: level.0.clone(),
+                        spawn: None,
+                    });
+                    next_state.set(GameState::Playing);
+                }
+                MenuButton::Quit => exit.send(AppExit),
+            },
+            Interaction::Hovered => *color = BUTTON_HOVERED_COLOR.into(),
+            Interaction::None => *color = BUTTON_COLOR.into(),
+        }
+    }
+}
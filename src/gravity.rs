@@ -0,0 +1,173 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy_tnua::TnuaProximitySensor;
+use bevy_xpbd_3d::prelude::*;
+
+use crate::player::{crouch_enforcer, Player, PlayerDimensions};
+use crate::zone::{make_zone_sensor, ZoneSensor};
+
+pub struct GravityPlugin;
+
+impl Plugin for GravityPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<GravityZone>()
+            .register_type::<GravityFlip>()
+            .init_resource::<GravitySettings>()
+            .add_systems(
+                Update,
+                (
+                    make_zone_sensor::<GravityZone>,
+                    make_zone_sensor::<GravityFlip>,
+                    apply_gravity,
+                ),
+            )
+            .add_systems(
+                Update,
+                (init_player_up, flip_gravity, ease_player_up).chain(),
+            );
+    }
+}
+
+/// Gravity of the level outside of any `GravityZone`, copied into `Gravity`.
+#[derive(Resource, Debug, Clone)]
+pub struct GravitySettings {
+    pub gravity: Vec3,
+    /// Rate at which the camera and the sprite turn over on a gravity flip, per second
+    pub flip_speed: f32,
+}
+
+impl Default for GravitySettings {
+    fn default() -> Self {
+        Self {
+            gravity: Vec3::NEG_Y * 9.81,
+            flip_speed: 4.,
+        }
+    }
+}
+
+/// Gravity for everything while the player is inside, e.g. a low-gravity room.
+/// Tnua keeps the player upright along +Y, so it should still point downwards.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct GravityZone {
+    pub gravity: Vec3,
+}
+
+// Tnua reads `Gravity` as well, so the float spring and jump height follow along
+fn apply_gravity(
+    sensors: Query<(&ZoneSensor, &CollidingEntities)>,
+    zones: Query<&GravityZone>,
+    player: Query<Entity, With<Player>>,
+    settings: Res<GravitySettings>,
+    mut gravity: ResMut<Gravity>,
+) {
+    let zone = sensors
+        .iter()
+        .filter(|(_, colliding)| player.iter().any(|entity| colliding.contains(&entity)))
+        .find_map(|(sensor, _)| zones.get(sensor.zone).ok());
+
+    // Leaving the zone goes back to the level's gravity
+    let target = zone.map_or(settings.gravity, |zone| zone.gravity);
+    if gravity.0 != target {
+        gravity.0 = target;
+    }
+}
+
+// Gravity flip
+
+/// Turns "up" upside down for the player while inside, so they fall onto the
+/// ceiling and walk on it.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct GravityFlip(bool);
+
+/// Which way is up for the player.
+#[derive(Component, Default, Debug)]
+pub struct PlayerUp {
+    pub flipped: bool,
+    /// From world up to the player's, eased towards the flip for the camera
+    /// and the sprite
+    pub rotation: Quat,
+}
+
+impl PlayerUp {
+    /// Up for physics and Tnua, which flips at once.
+    pub fn up(&self) -> Vec3 {
+        if self.flipped {
+            Vec3::NEG_Y
+        } else {
+            Vec3::Y
+        }
+    }
+
+    // Rolled over around the forward axis, so the view turns like a barrel roll
+    fn target_rotation(&self) -> Quat {
+        if self.flipped {
+            Quat::from_rotation_z(PI)
+        } else {
+            Quat::IDENTITY
+        }
+    }
+}
+
+fn init_player_up(mut commands: Commands, player: Query<Entity, Added<Player>>) {
+    for entity in player.iter() {
+        commands
+            .entity(entity)
+            .insert((PlayerUp::default(), GravityScale(1.)));
+    }
+}
+
+// The body isn't turned over, its capsule is the same either way up
+fn flip_gravity(
+    mut commands: Commands,
+    sensors: Query<(&ZoneSensor, &CollidingEntities)>,
+    zones: Query<&GravityFlip>,
+    mut player: Query<
+        (
+            Entity,
+            &mut PlayerUp,
+            &mut GravityScale,
+            &mut TnuaProximitySensor,
+        ),
+        With<Player>,
+    >,
+    dimensions: Res<PlayerDimensions>,
+) {
+    for (entity, mut up, mut gravity_scale, mut sensor) in player.iter_mut() {
+        let flipped = sensors
+            .iter()
+            .filter(|(_, colliding)| colliding.contains(&entity))
+            .any(|(sensor, _)| zones.get(sensor.zone).is_ok_and(|zone| zone.0));
+        if up.flipped == flipped {
+            continue;
+        }
+
+        up.flipped = flipped;
+        gravity_scale.0 = if flipped { -1. } else { 1. };
+        // Tnua looks for the ground below the player's own feet
+        sensor.cast_direction = -up.up();
+        // ...and for headroom above its head
+        commands
+            .entity(entity)
+            .insert(crouch_enforcer(&dimensions, up.up()));
+    }
+}
+
+fn ease_player_up(
+    mut player: Query<&mut PlayerUp>,
+    settings: Res<GravitySettings>,
+    time: Res<Time>,
+) {
+    let rate = (settings.flip_speed * time.delta_seconds()).min(1.);
+    for mut up in player.iter_mut() {
+        let target = up.target_rotation();
+        if up.rotation != target {
+            up.rotation = up.rotation.slerp(target, rate);
+            if up.rotation.angle_between(target) < 1e-3 {
+                up.rotation = target;
+            }
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+
+use crate::player::{Player, PlayerDiedEvent};
+use crate::zone::{make_zone_sensor, SensorOverlap, ZoneSensor};
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Hazard>()
+            .init_resource::<HealthConfig>()
+            .add_event::<DamageEvent>()
+            .add_event::<HealEvent>()
+            .add_systems(
+                Update,
+                (
+                    init_health,
+                    tick_invulnerability,
+                    (hazard_damage, hazard_kill),
+                    apply_damage,
+                    apply_heal,
+                    restore_health,
+                )
+                    .chain(),
+            )
+            .add_systems(Update, make_zone_sensor::<Hazard>);
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct HealthConfig {
+    pub max_health: f32,
+    /// Seconds the player can't be damaged again after taking a hit
+    pub invulnerability_duration: f32,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            max_health: 100.,
+            invulnerability_duration: 1.,
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+/// I-frames left after the last hit.
+#[derive(Component, Default, Debug)]
+pub struct Invulnerability {
+    remaining: f32,
+}
+
+impl Invulnerability {
+    /// Makes sure no damage is taken for at least `duration` from now.
+    pub fn grant(&mut self, duration: f32) {
+        self.remaining = self.remaining.max(duration);
+    }
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct HealEvent {
+    pub target: Entity,
+    pub amount: f32,
+}
+
+fn init_health(
+    mut commands: Commands,
+    player: Query<Entity, Added<Player>>,
+    config: Res<HealthConfig>,
+) {
+    for entity in player.iter() {
+        commands.entity(entity).insert((
+            Health {
+                current: config.max_health,
+                max: config.max_health,
+            },
+            Invulnerability::default(),
+            HazardExposure::default(),
+        ));
+    }
+}
+
+fn tick_invulnerability(mut query: Query<&mut Invulnerability>, time: Res<Time>) {
+    for mut invulnerability in query.iter_mut() {
+        invulnerability.remaining = (invulnerability.remaining - time.delta_seconds()).max(0.);
+    }
+}
+
+fn apply_damage(
+    mut damage: EventReader<DamageEvent>,
+    mut died: EventWriter<PlayerDiedEvent>,
+    mut query: Query<(&mut Health, &mut Invulnerability, Has<Player>)>,
+    config: Res<HealthConfig>,
+) {
+    for ev in damage.read() {
+        let Ok((mut health, mut invulnerability, is_player)) = query.get_mut(ev.target) else {
+            continue;
+        };
+        // Already dead this frame, or still blinking from the last hit
+        if health.current <= 0. || invulnerability.remaining > 0. {
+            continue;
+        }
+
+        health.current = (health.current - ev.amount).max(0.);
+        invulnerability.remaining = config.invulnerability_duration;
+        debug_print!(sec: 1., "{:?} took {} damage", ev.target, ev.amount);
+
+        if health.current <= 0. && is_player {
+            died.send(PlayerDiedEvent { entity: ev.target });
+        }
+    }
+}
+
+fn apply_heal(mut heal: EventReader<HealEvent>, mut query: Query<&mut Health>) {
+    for ev in heal.read() {
+        if let Ok(mut health) = query.get_mut(ev.target) {
+            health.current = (health.current + ev.amount).min(health.max);
+        }
+    }
+}
+
+/// The player comes back from a respawn at full health, however they died.
+fn restore_health(
+    mut died: EventReader<PlayerDiedEvent>,
+    mut query: Query<(&mut Health, &mut Invulnerability)>,
+) {
+    for ev in died.read() {
+        if let Ok((mut health, mut invulnerability)) = query.get_mut(ev.entity) {
+            health.current = health.max;
+            invulnerability.remaining = 0.;
+        }
+    }
+}
+
+// Hazards
+
+/// Spikes, lava and the like. Hurts while the player stays inside, or kills
+/// outright if `instant_kill` is set.
+/// Lava can carry a `ForceZone` as well to push the player back out.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Hazard {
+    pub damage_per_second: f32,
+    pub instant_kill: bool,
+}
+
+/// Hazard damage built up while invulnerable, dealt once the i-frames end.
+#[derive(Component, Default, Debug)]
+struct HazardExposure {
+    pending: f32,
+}
+
+fn hazard_damage(
+    sensors: Query<(&ZoneSensor, &CollidingEntities)>,
+    hazards: Query<&Hazard>,
+    mut player: Query<(Entity, &Invulnerability, &mut HazardExposure), With<Player>>,
+    mut damage: EventWriter<DamageEvent>,
+    time: Res<Time>,
+) {
+    for (entity, invulnerability, mut exposure) in player.iter_mut() {
+        // Overlapping hazards don't stack, the worst one applies
+        let damage_per_second = sensors
+            .iter()
+            .filter(|(_, colliding)| colliding.contains(&entity))
+            .filter_map(|(sensor, _)| hazards.get(sensor.zone).ok())
+            .filter(|hazard| !hazard.instant_kill)
+            .map(|hazard| hazard.damage_per_second)
+            .fold(0., f32::max);
+
+        if damage_per_second <= 0. {
+            exposure.pending = 0.;
+            continue;
+        }
+
+        exposure.pending += damage_per_second * time.delta_seconds();
+        if invulnerability.remaining <= 0. {
+            damage.send(DamageEvent {
+                target: entity,
+                amount: exposure.pending,
+            });
+            exposure.pending = 0.;
+        }
+    }
+}
+
+fn hazard_kill(
+    mut overlaps: EventReader<SensorOverlap>,
+    hazards: Query<&Hazard>,
+    player: Query<(), With<Player>>,
+    mut died: EventWriter<PlayerDiedEvent>,
+) {
+    for overlap in overlaps.read().filter(|overlap| overlap.entered()) {
+        if hazards
+            .get(overlap.zone)
+            .is_ok_and(|hazard| hazard.instant_kill)
+            && player.contains(overlap.other)
+        {
+            died.send(PlayerDiedEvent {
+                entity: overlap.other,
+            });
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowResized};
+
+use crate::collectible::{CollectedEvent, LevelCollectibles, Score};
+use crate::health::{DamageEvent, HealEvent, Health};
+use crate::mechanism::DoorLockedEvent;
+use crate::player::{Player, PlayerDiedEvent, PlayerSlot};
+use crate::replay::{InputReplay, RecordingSelection, ReplayMode};
+use crate::speedrun::{format_time, SpeedrunState, SpeedrunTimer};
+use crate::zone::GoalLockedEvent;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_hud)
+            // After the events have been applied to the health and score
+            .add_systems(
+                PostUpdate,
+                (
+                    update_health_bar,
+                    update_collectible_counter,
+                    update_speedrun_timer,
+                    show_locked_message,
+                    update_replay_text,
+                    scale_hud_text,
+                ),
+            );
+    }
+}
+
+/// Counter text height relative to the window height.
+const HUD_FONT_SCALE: f32 = 0.04;
+
+#[derive(Component)]
+struct HealthBarFill;
+
+#[derive(Component)]
+struct CollectibleCounter;
+
+#[derive(Component)]
+struct SpeedrunText;
+
+const NEW_RECORD_COLOR: Color = Color::GOLD;
+
+#[derive(Component)]
+struct LockedMessage;
+
+#[derive(Component)]
+struct ReplayText;
+
+/// Seconds the message of a locked goal or door stays up.
+const LOCKED_MESSAGE_DURATION: f32 = 2.;
+
+fn spawn_hud(mut commands: Commands) {
+    // Health bar, top left
+    commands
+        .spawn((
+            Name::new("HealthBar"),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Vw(2.),
+                    top: Val::Vh(2.),
+                    width: Val::Vw(20.),
+                    height: Val::Vh(2.5),
+                    padding: UiRect::all(Val::Px(2.)),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.6).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                HealthBarFill,
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.8, 0.1, 0.1).into(),
+                    ..default()
+                },
+            ));
+        });
+
+    // Collectible counter, top right
+    commands.spawn((
+        Name::new("CollectibleCounter"),
+        CollectibleCounter,
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 24.,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Vw(2.),
+                top: Val::Vh(2.),
+                ..default()
+            },
+            ..default()
+        },
+    ));
+
+    // Speedrun timer, top centre, with the latest split below
+    commands
+        .spawn((
+            Name::new("SpeedrunTimer"),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    top: Val::Vh(2.),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                SpeedrunText,
+                TextBundle::from_sections([
+                    TextSection::new(
+                        "",
+                        TextStyle {
+                            font_size: 24.,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    TextSection::new(
+                        "",
+                        TextStyle {
+                            font_size: 24.,
+                            color: Color::GRAY,
+                            ..default()
+                        },
+                    ),
+                ])
+                .with_text_alignment(TextAlignment::Center),
+            ));
+        });
+
+    // Recording or replay going on, and the recording picked, bottom left
+    commands.spawn((
+        Name::new("ReplayText"),
+        ReplayText,
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 24.,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Vw(2.),
+                bottom: Val::Vh(2.),
+                ..default()
+            },
+            ..default()
+        },
+    ));
+
+    // Locked goal or door message, middle of the screen
+    commands
+        .spawn((
+            Name::new("LockedMessage"),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    top: Val::Vh(40.),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                LockedMessage,
+                TextBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 24.,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn update_health_bar(
+    mut damage: EventReader<DamageEvent>,
+    mut heal: EventReader<HealEvent>,
+    mut died: EventReader<PlayerDiedEvent>,
+    player: Query<(Ref<Health>, Option<&PlayerSlot>), With<Player>>,
+    mut fill: Query<&mut Style, With<HealthBarFill>>,
+) {
+    // Split-screen only shows the first player's health
+    let Some((health, _)) = player
+        .iter()
+        .find(|(_, slot)| slot.map_or(true, |slot| slot.0 == 0))
+    else {
+        return;
+    };
+    // Events are read out regardless, so they don't pile up for the next frame
+    let events = damage.read().count() + heal.read().count() + died.read().count();
+    if events == 0 && !health.is_added() {
+        return;
+    }
+
+    let fraction = (health.current / health.max).clamp(0., 1.);
+    for mut style in fill.iter_mut() {
+        style.width = Val::Percent(fraction * 100.);
+    }
+}
+
+fn update_collectible_counter(
+    mut collected: EventReader<CollectedEvent>,
+    score: Res<Score>,
+    level: Res<LevelCollectibles>,
+    mut counter: Query<&mut Text, With<CollectibleCounter>>,
+) {
+    // The level total grows while the level spawns, without any event
+    if collected.read().count() == 0 && !level.is_changed() {
+        return;
+    }
+
+    for mut text in counter.iter_mut() {
+        text.sections[0].value =
+            format!("{}/{}  Score {}", level.collected, level.total, score.total);
+    }
+}
+
+fn update_speedrun_timer(
+    timer: Res<SpeedrunTimer>,
+    mut text: Query<&mut Text, With<SpeedrunText>>,
+) {
+    if !timer.is_changed() {
+        return;
+    }
+
+    for mut text in text.iter_mut() {
+        text.sections[0].value = format_time(timer.elapsed);
+        text.sections[0].style.color = if timer.new_record {
+            NEW_RECORD_COLOR
+        } else {
+            Color::WHITE
+        };
+        text.sections[1].value = match (timer.state, timer.splits.last()) {
+            (SpeedrunState::Finished, _) if timer.new_record => "\nNew record!".to_string(),
+            (_, Some((order, split))) => format!("\nCheckpoint {order}  {}", format_time(*split)),
+            _ => String::new(),
+        };
+    }
+}
+
+fn show_locked_message(
+    mut goal_locked: EventReader<GoalLockedEvent>,
+    mut door_locked: EventReader<DoorLockedEvent>,
+    mut message: Query<(&mut Text, &mut Visibility), With<LockedMessage>>,
+    time: Res<Time>,
+    mut shown_for: Local<Option<f32>>,
+) {
+    let Ok((mut text, mut visibility)) = message.get_single_mut() else {
+        return;
+    };
+    let goal = goal_locked
+        .read()
+        .last()
+        .map(|ev| format!("Collect everything first, {} to go", ev.remaining));
+    let door = door_locked
+        .read()
+        .last()
+        .map(|ev| format!("Locked, needs key {}", ev.key.0));
+    if let Some(value) = door.or(goal) {
+        text.sections[0].value = value;
+        *visibility = Visibility::Inherited;
+        *shown_for = Some(0.);
+        return;
+    }
+
+    let Some(elapsed) = shown_for.as_mut() else {
+        return;
+    };
+    *elapsed += time.delta_seconds();
+    if *elapsed > LOCKED_MESSAGE_DURATION {
+        *visibility = Visibility::Hidden;
+        *shown_for = None;
+    }
+}
+
+fn update_replay_text(
+    replay: Res<InputReplay>,
+    selection: Res<RecordingSelection>,
+    mut text: Query<&mut Text, With<ReplayText>>,
+) {
+    if !replay.is_changed() && !selection.is_changed() {
+        return;
+    }
+
+    let mode = match (replay.mode, replay.desynced_at) {
+        (ReplayMode::Off, _) => String::new(),
+        (ReplayMode::Recording, _) => "Recording".to_string(),
+        (ReplayMode::Replaying, None) => "Replaying".to_string(),
+        (ReplayMode::Replaying, Some(tick)) => format!("Replaying, out of sync from tick {tick}"),
+    };
+    let picked = match (selection.selected, selection.path()) {
+        (Some(i), Some(path)) => {
+            format!("Recording {}/{}  {path}", i + 1, selection.recordings.len())
+        }
+        _ => String::new(),
+    };
+    let value = [mode, picked]
+        .into_iter()
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    for mut text in text.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+fn scale_hud_text(
+    mut resized: EventReader<WindowResized>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut texts: Query<
+        &mut Text,
+        Or<(
+            With<CollectibleCounter>,
+            With<SpeedrunText>,
+            With<LockedMessage>,
+            With<ReplayText>,
+        )>,
+    >,
+) {
+    if resized.read().count() == 0 {
+        return;
+    }
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+
+    for mut text in texts.iter_mut() {
+        for section in &mut text.sections {
+            section.style.font_size = window.height() * HUD_FONT_SCALE;
+        }
+    }
+}
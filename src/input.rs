@@ -0,0 +1,350 @@
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+use leafwing_input_manager::axislike::{AxisType, DeadZoneShape};
+use leafwing_input_manager::prelude::*;
+use leafwing_input_manager::user_input::InputKind;
+use serde::{Deserialize, Serialize};
+
+use crate::player::Player;
+
+pub struct PlayerInputPlugin;
+
+impl Plugin for PlayerInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(InputManagerPlugin::<Action>::default())
+            .add_event::<RebindRequest>()
+            .add_systems(Update, rebind_action);
+    }
+}
+
+#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect, Serialize, Deserialize)]
+pub enum Action {
+    /// Analog walk direction, combining the left stick and the directional keys
+    Move,
+    Up,
+    Down,
+    Left,
+    Right,
+    Jump,
+    Interact,
+    Crouch,
+    Sprint,
+    Throw,
+    /// Camera zoom in steps, from the mouse wheel
+    Zoom,
+    /// Continuous camera zoom, from the gamepad d-pad
+    ZoomAxis,
+    /// Camera orbit, from mouse motion
+    Look,
+    /// Camera orbit, from the right stick
+    LookAxis,
+    /// Switches between the third-person and the first-person camera
+    CameraMode,
+    Dash,
+}
+
+/// Keys for these also make up the keyboard dpads of `Action::Move`.
+const DIRECTION_ACTIONS: [Action; 4] = [Action::Up, Action::Down, Action::Left, Action::Right];
+
+/// Actions that must keep at least one binding, or the game can't be played.
+const ESSENTIAL_ACTIONS: [Action; 2] = [Action::Jump, Action::Interact];
+
+/// Stick deflection below this is treated as drift and ignored.
+const GAMEPAD_DEAD_ZONE: f32 = 0.15;
+
+/// Where rebound controls are persisted between sessions.
+const BINDINGS_PATH: &str = "bindings.ron";
+
+/// Devices a player reads input from. Split-screen players each get one,
+/// a single player gets both.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputDevices {
+    All,
+    KeyboardMouse,
+    Gamepad(Gamepad),
+}
+
+pub fn add_action_state(mut entity: EntityCommands, devices: InputDevices) {
+    let input_map = load_input_map().unwrap_or_else(default_input_map);
+    let input_map = match devices {
+        InputDevices::All => input_map,
+        InputDevices::KeyboardMouse => device_input_map(&input_map, false),
+        InputDevices::Gamepad(gamepad) => {
+            let mut input_map = device_input_map(&input_map, true);
+            input_map.set_gamepad(gamepad);
+            input_map
+        }
+    };
+    entity.insert((
+        InputManagerBundle::<Action> {
+            action_state: default(),
+            input_map,
+        },
+        devices,
+    ));
+}
+
+/// Only the bindings of either the gamepad or the keyboard and mouse.
+fn device_input_map(input_map: &InputMap<Action>, gamepad: bool) -> InputMap<Action> {
+    let mut bindings = vec![];
+    for (action, inputs) in input_map.iter() {
+        for input in inputs {
+            if is_gamepad_input(input) == gamepad {
+                bindings.push((input.clone(), *action));
+            }
+        }
+    }
+    InputMap::new(bindings)
+}
+
+fn default_input_map() -> InputMap<Action> {
+    let mut input_map = InputMap::new([
+        // WASD
+        (KeyCode::W, Action::Up),
+        (KeyCode::S, Action::Down),
+        (KeyCode::A, Action::Left),
+        (KeyCode::D, Action::Right),
+        // Cursor keys
+        (KeyCode::Up, Action::Up),
+        (KeyCode::Down, Action::Down),
+        (KeyCode::Left, Action::Left),
+        (KeyCode::Right, Action::Right),
+        // Space
+        (KeyCode::Space, Action::Jump),
+        // E
+        (KeyCode::E, Action::Interact),
+        // Left Ctrl
+        (KeyCode::ControlLeft, Action::Crouch),
+        // Left Shift
+        (KeyCode::ShiftLeft, Action::Sprint),
+        // Q
+        (KeyCode::Q, Action::Throw),
+        // V
+        (KeyCode::V, Action::CameraMode),
+        // F
+        (KeyCode::F, Action::Dash),
+    ]);
+    input_map
+        .insert(
+            DualAxis::symmetric(
+                GamepadAxisType::LeftStickX,
+                GamepadAxisType::LeftStickY,
+                DeadZoneShape::Ellipse {
+                    radius_x: GAMEPAD_DEAD_ZONE,
+                    radius_y: GAMEPAD_DEAD_ZONE,
+                },
+            ),
+            Action::Move,
+        )
+        .insert(SingleAxis::mouse_wheel_y(), Action::Zoom)
+        .insert(
+            VirtualAxis {
+                negative: InputKind::GamepadButton(GamepadButtonType::DPadDown),
+                positive: InputKind::GamepadButton(GamepadButtonType::DPadUp),
+            },
+            Action::ZoomAxis,
+        )
+        .insert(DualAxis::mouse_motion(), Action::Look)
+        .insert(
+            DualAxis::symmetric(
+                GamepadAxisType::RightStickX,
+                GamepadAxisType::RightStickY,
+                DeadZoneShape::Ellipse {
+                    radius_x: GAMEPAD_DEAD_ZONE,
+                    radius_y: GAMEPAD_DEAD_ZONE,
+                },
+            ),
+            Action::LookAxis,
+        )
+        .insert(VirtualDPad::wasd(), Action::Move)
+        .insert(VirtualDPad::arrow_keys(), Action::Move)
+        // Left stick, each half of an axis drives one directional action
+        .insert_multiple([
+            (
+                SingleAxis::positive_only(GamepadAxisType::LeftStickY, GAMEPAD_DEAD_ZONE),
+                Action::Up,
+            ),
+            (
+                SingleAxis::negative_only(GamepadAxisType::LeftStickY, -GAMEPAD_DEAD_ZONE),
+                Action::Down,
+            ),
+            (
+                SingleAxis::negative_only(GamepadAxisType::LeftStickX, -GAMEPAD_DEAD_ZONE),
+                Action::Left,
+            ),
+            (
+                SingleAxis::positive_only(GamepadAxisType::LeftStickX, GAMEPAD_DEAD_ZONE),
+                Action::Right,
+            ),
+        ])
+        .insert_multiple([
+            (GamepadButtonType::South, Action::Jump),
+            (GamepadButtonType::West, Action::Interact),
+            (GamepadButtonType::North, Action::Interact),
+            (GamepadButtonType::East, Action::Crouch),
+            (GamepadButtonType::LeftTrigger, Action::Sprint),
+            (GamepadButtonType::RightTrigger, Action::Throw),
+            (GamepadButtonType::RightThumb, Action::CameraMode),
+            (GamepadButtonType::LeftTrigger2, Action::Dash),
+        ]);
+    input_map
+}
+
+// Rebinding
+
+/// Replaces the bindings of `action` on the device `new_input` belongs to,
+/// keeping the bindings of the other device.
+#[derive(Event, Clone, Debug)]
+pub struct RebindRequest {
+    pub action: Action,
+    pub new_input: UserInput,
+}
+
+fn is_gamepad_kind(kind: &InputKind) -> bool {
+    match kind {
+        InputKind::GamepadButton(_) => true,
+        // Mouse motion and the wheel are axes too
+        InputKind::SingleAxis(axis) => matches!(axis.axis_type, AxisType::Gamepad(_)),
+        InputKind::DualAxis(axis) => matches!(axis.x.axis_type, AxisType::Gamepad(_)),
+        _ => false,
+    }
+}
+
+fn is_gamepad_input(input: &UserInput) -> bool {
+    match input {
+        UserInput::Single(kind) => is_gamepad_kind(kind),
+        UserInput::Chord(kinds) => kinds.iter().any(is_gamepad_kind),
+        UserInput::VirtualDPad(dpad) => is_gamepad_kind(&dpad.up),
+        UserInput::VirtualAxis(axis) => is_gamepad_kind(&axis.positive),
+    }
+}
+
+fn rebound_input_map(
+    input_map: &InputMap<Action>,
+    request: &RebindRequest,
+) -> Result<InputMap<Action>, String> {
+    let gamepad = is_gamepad_input(&request.new_input);
+
+    let mut bindings = vec![];
+    for (action, inputs) in input_map.iter() {
+        for input in inputs {
+            if *action == request.action && is_gamepad_input(input) == gamepad {
+                continue;
+            }
+            // An input does a single thing, so it's taken away from the other action
+            if *input == request.new_input {
+                warn!(
+                    "{:?} conflicts with the binding of {action:?}, unbinding it",
+                    request.new_input
+                );
+                continue;
+            }
+            bindings.push((input.clone(), *action));
+        }
+    }
+    bindings.push((request.new_input.clone(), request.action));
+
+    // Walking reads `Move` alone, so its dpads follow the rebound keys
+    if !gamepad && DIRECTION_ACTIONS.contains(&request.action) {
+        bindings.retain(|(input, action)| {
+            *action != Action::Move || !matches!(input, UserInput::VirtualDPad(_))
+        });
+        for dpad in keyboard_move_dpads(&bindings) {
+            bindings.push((UserInput::VirtualDPad(dpad), Action::Move));
+        }
+    }
+
+    for essential in ESSENTIAL_ACTIONS {
+        if !bindings.iter().any(|(_, action)| *action == essential) {
+            return Err(format!(
+                "rebinding {:?} would leave {essential:?} unbound",
+                request.action
+            ));
+        }
+    }
+
+    let mut rebound = InputMap::new(bindings);
+    if let Some(gamepad) = input_map.gamepad() {
+        rebound.set_gamepad(gamepad);
+    }
+    Ok(rebound)
+}
+
+/// One dpad per set of direction keys, in the order they were bound: WASD and
+/// the cursor keys by default. A direction with fewer keys reuses its last.
+fn keyboard_move_dpads(bindings: &[(UserInput, Action)]) -> Vec<VirtualDPad> {
+    let [up, down, left, right] = DIRECTION_ACTIONS.map(|direction| {
+        bindings
+            .iter()
+            .filter(|(_, action)| *action == direction)
+            .filter_map(|(input, _)| match input {
+                UserInput::Single(kind) if !is_gamepad_kind(kind) => Some(kind.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    });
+    if [&up, &down, &left, &right]
+        .iter()
+        .any(|keys| keys.is_empty())
+    {
+        return vec![];
+    }
+
+    let count = up.len().max(down.len()).max(left.len()).max(right.len());
+    let nth = |keys: &[InputKind], i: usize| keys[i.min(keys.len() - 1)].clone();
+    (0..count)
+        .map(|i| VirtualDPad {
+            up: nth(&up, i),
+            down: nth(&down, i),
+            left: nth(&left, i),
+            right: nth(&right, i),
+        })
+        .collect()
+}
+
+// Split-screen players keep the bindings they were given
+fn rebind_action(
+    mut requests: EventReader<RebindRequest>,
+    mut input_maps: Query<(&mut InputMap<Action>, &InputDevices), With<Player>>,
+) {
+    for request in requests.read() {
+        for (mut input_map, devices) in input_maps.iter_mut() {
+            if *devices != InputDevices::All {
+                continue;
+            }
+            match rebound_input_map(&input_map, request) {
+                Ok(rebound) => {
+                    *input_map = rebound;
+                    save_input_map(&input_map);
+                }
+                Err(e) => warn!("Rejected rebind: {e}"),
+            }
+        }
+    }
+}
+
+// Persistence
+
+pub fn input_map_to_ron(input_map: &InputMap<Action>) -> Result<String, ron::Error> {
+    ron::ser::to_string_pretty(input_map, ron::ser::PrettyConfig::default())
+}
+
+pub fn input_map_from_ron(ron: &str) -> Result<InputMap<Action>, ron::error::SpannedError> {
+    ron::from_str(ron)
+}
+
+fn load_input_map() -> Option<InputMap<Action>> {
+    let ron = std::fs::read_to_string(BINDINGS_PATH).ok()?;
+    input_map_from_ron(&ron)
+        .map_err(|e| error!("Failed to parse {BINDINGS_PATH}: {e}"))
+        .ok()
+}
+
+fn save_input_map(input_map: &InputMap<Action>) {
+    let result = input_map_to_ron(input_map)
+        .map_err(|e| e.to_string())
+        .and_then(|ron| std::fs::write(BINDINGS_PATH, ron).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        error!("Failed to save {BINDINGS_PATH}: {e}");
+    }
+}
@@ -0,0 +1,283 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use crate::input::Action;
+use crate::player::{CameraTarget, PlayerFollowingCamera};
+
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<InteractionLabel>()
+            .init_resource::<InteractionConfig>()
+            .add_event::<InteractEvent>()
+            .add_systems(Startup, spawn_prompt)
+            .add_systems(
+                Update,
+                (
+                    (aim_interaction_ray, apply_interaction_range),
+                    dispatch_interaction,
+                    update_prompt,
+                ),
+            );
+    }
+}
+
+/// Ray cast from the player, child of the player entity, looking for `Interactable`s.
+///
+/// Left at the default `max_hits` so nothing in front is dropped. The hits
+/// come in no particular order, readers pick the nearest themselves.
+#[derive(Component)]
+pub struct InteractionRayCaster;
+
+#[derive(Resource, Debug, Clone)]
+pub struct InteractionConfig {
+    /// How far from the player's center the aimed interaction ray reaches
+    pub range: f32,
+}
+
+impl Default for InteractionConfig {
+    fn default() -> Self {
+        Self { range: 0.8 }
+    }
+}
+
+/// Which way an `InteractionRayCaster` points. Casters without one keep the
+/// direction they were spawned with.
+#[derive(Component, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RaycastSource {
+    /// Straight out of the front of the player
+    #[default]
+    PlayerForward,
+    /// From the player along the view of their camera, for what they're
+    /// looking at rather than what's in front of the capsule
+    CameraLook,
+}
+
+// The ray is in the space of the player, whose rotation the camera doesn't share
+fn aim_interaction_ray(
+    mut rays: Query<(&mut RayCaster, &RaycastSource, &Parent), With<InteractionRayCaster>>,
+    player: Query<&GlobalTransform>,
+    cameras: Query<(&CameraTarget, &GlobalTransform)>,
+) {
+    for (mut ray, source, parent) in rays.iter_mut() {
+        let direction = match source {
+            RaycastSource::PlayerForward => Vec3::NEG_Z,
+            RaycastSource::CameraLook => {
+                let Ok(player_transform) = player.get(parent.get()) else {
+                    continue;
+                };
+                let Some((_, camera_transform)) =
+                    cameras.iter().find(|(target, _)| target.0 == parent.get())
+                else {
+                    continue;
+                };
+                let (_, rotation, _) = player_transform.to_scale_rotation_translation();
+                rotation.inverse() * camera_transform.forward()
+            }
+        };
+        if ray.direction != direction {
+            ray.direction = direction;
+        }
+    }
+}
+
+// The ray overhead for monkey bars isn't aimed, and keeps its own length
+fn apply_interaction_range(
+    mut rays: Query<&mut RayCaster, (With<InteractionRayCaster>, With<RaycastSource>)>,
+    config: Res<InteractionConfig>,
+) {
+    for mut ray in rays.iter_mut() {
+        if ray.max_time_of_impact != config.range {
+            ray.max_time_of_impact = config.range;
+        }
+    }
+}
+
+/// Something the player can interact with. Reacting to it is up to the
+/// systems reading `InteractEvent`.
+#[derive(Component, Default)]
+pub struct Interactable;
+
+#[derive(Event, Clone, Debug)]
+pub struct InteractEvent {
+    pub player: Entity,
+    pub target: Entity,
+    /// Where the interaction ray hit `target`
+    pub point: Vec3,
+}
+
+fn dispatch_interaction(
+    ray: Query<(&RayCaster, &RayHits, &Parent), With<InteractionRayCaster>>,
+    player: Query<&ActionState<Action>>,
+    interactables: Query<(), With<Interactable>>,
+    mut interact: EventWriter<InteractEvent>,
+) {
+    // Nearest hit over all of a player's rays, so one press interacts with one thing
+    let mut nearest: HashMap<Entity, (Entity, f32, Vec3)> = HashMap::new();
+
+    for (ray, hits, parent) in &ray {
+        debug_print!("hit: {:?}", hits.as_slice());
+
+        let player_entity = parent.get();
+
+        let Ok(action) = player.get(player_entity) else {
+            error!("Player missing");
+            continue;
+        };
+        if !action.just_pressed(Action::Interact) {
+            continue;
+        }
+
+        let Some(hit) = hits
+            .iter()
+            .filter(|hit| interactables.contains(hit.entity))
+            .min_by(|a, b| a.time_of_impact.total_cmp(&b.time_of_impact))
+        else {
+            continue;
+        };
+
+        let point = ray.global_origin() + ray.global_direction() * hit.time_of_impact;
+        let candidate = (hit.entity, hit.time_of_impact, point);
+        nearest
+            .entry(player_entity)
+            .and_modify(|current| {
+                if candidate.1 < current.1 {
+                    *current = candidate;
+                }
+            })
+            .or_insert(candidate);
+    }
+
+    for (player, (target, _, point)) in nearest {
+        interact.send(InteractEvent {
+            player,
+            target,
+            point,
+        });
+    }
+}
+
+// Prompt
+
+/// Text of the prompt shown while the target is under the interaction ray.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct InteractionLabel(pub String);
+
+const DEFAULT_PROMPT: &str = "Press E";
+
+#[derive(Component)]
+struct InteractionPrompt;
+
+fn spawn_prompt(mut commands: Commands) {
+    commands.spawn((
+        Name::new("InteractionPrompt"),
+        InteractionPrompt,
+        TextBundle {
+            text: Text::from_section(
+                DEFAULT_PROMPT,
+                TextStyle {
+                    font_size: 24.,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+}
+
+fn update_prompt(
+    ray: Query<(&RayCaster, &RayHits, &Parent), With<InteractionRayCaster>>,
+    interactables: Query<Option<&InteractionLabel>, With<Interactable>>,
+    camera: Query<(&Camera, &GlobalTransform, &CameraTarget), With<PlayerFollowingCamera>>,
+    mut prompt: Query<(&mut Text, &mut Style, &mut Visibility), With<InteractionPrompt>>,
+) {
+    let Ok((mut text, mut style, mut visibility)) = prompt.get_single_mut() else {
+        return;
+    };
+
+    // Only what's first under a ray counts, not something hidden behind it
+    let target = ray
+        .iter()
+        .filter_map(|(ray, hits, player)| {
+            let hit = hits
+                .iter()
+                .min_by(|a, b| a.time_of_impact.total_cmp(&b.time_of_impact))?;
+            let label = interactables.get(hit.entity).ok()?;
+            let point = ray.global_origin() + ray.global_direction() * hit.time_of_impact;
+            Some((hit.time_of_impact, point, label, player.get()))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+
+    // Shown in the view of the player looking at it
+    let screen_pos = target.and_then(|(_, point, _, player)| {
+        let (camera, camera_transform, _) =
+            camera.iter().find(|(_, _, target)| target.0 == player)?;
+        let viewport = camera.logical_viewport_rect()?;
+        Some(viewport.min + camera.world_to_viewport(camera_transform, point)?)
+    });
+
+    let (Some((_, _, label, _)), Some(screen_pos)) = (target, screen_pos) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    text.sections[0].value = label
+        .map_or(DEFAULT_PROMPT, |label| label.0.as_str())
+        .to_string();
+    style.left = Val::Px(screen_pos.x);
+    style.top = Val::Px(screen_pos.y);
+    *visibility = Visibility::Visible;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interacts_with_the_nearest_hit() {
+        let mut app = App::new();
+        app.add_event::<InteractEvent>()
+            .add_systems(Update, dispatch_interaction);
+
+        let mut action = ActionState::<Action>::default();
+        action.press(Action::Interact);
+        let player = app.world.spawn(action).id();
+        let near = app.world.spawn(Interactable).id();
+        let far = app.world.spawn(Interactable).id();
+
+        // Listed farthest first, as the hits come unordered
+        let mut hits = RayHits::default();
+        for (entity, time_of_impact) in [(far, 0.7), (near, 0.3)] {
+            hits.push(RayHitData {
+                entity,
+                time_of_impact,
+                normal: Vec3::Z,
+            });
+        }
+        app.world
+            .spawn((
+                InteractionRayCaster,
+                RayCaster::new(Vec3::ZERO, Vec3::NEG_Z),
+                hits,
+            ))
+            .set_parent(player);
+
+        app.update();
+
+        let events = app.world.resource::<Events<InteractEvent>>();
+        let sent: Vec<_> = events.get_reader().read(events).collect();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].player, player);
+        assert_eq!(sent[0].target, near);
+    }
+}
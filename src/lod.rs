@@ -0,0 +1,106 @@
+use bevy::prelude::*;
+
+use crate::player::PlayerFollowingCamera;
+
+pub struct LodPlugin;
+
+impl Plugin for LodPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<MakeLod>()
+            .add_systems(Update, (make_lod, update_lod).chain());
+    }
+}
+
+/// Detail levels of a prop, authored as the children of the tagged node from
+/// the most detailed one on. `distances` are where each child after the first
+/// takes over, in increasing order.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+struct MakeLod {
+    distances: Vec<f32>,
+}
+
+/// Meshes swapped in by distance to the nearest camera, each from its
+/// distance on.
+#[derive(Component, Debug)]
+pub struct Lod {
+    pub levels: Vec<(f32, Handle<Mesh>)>,
+    current: usize,
+}
+
+/// Fraction of a level's distance to go past before switching, either way,
+/// so a mesh right at the threshold doesn't flicker between levels.
+const LOD_HYSTERESIS: f32 = 0.1;
+
+// The first child's mesh is the one swapped, the other children stay hidden
+fn make_lod(
+    mut commands: Commands,
+    query: Query<(Entity, &MakeLod, &Children), Added<MakeLod>>,
+    children: Query<&Children>,
+    meshes: Query<&Handle<Mesh>>,
+) {
+    for (entity, make_lod, lod_children) in query.iter() {
+        // A child with several primitives only counts its first
+        let found = lod_children
+            .iter()
+            .filter_map(|&child| {
+                std::iter::once(child)
+                    .chain(children.iter_descendants(child))
+                    .find_map(|entity| meshes.get(entity).ok().map(|mesh| (entity, mesh.clone())))
+            })
+            .collect::<Vec<_>>();
+        let Some(&(mesh_entity, _)) = found.first() else {
+            warn!("No LOD meshes below {entity:?}");
+            continue;
+        };
+        if found.len() != make_lod.distances.len() + 1 {
+            warn!(
+                "{entity:?} has {} LOD meshes for {} distances",
+                found.len(),
+                make_lod.distances.len()
+            );
+        }
+
+        let levels = std::iter::once(0.)
+            .chain(make_lod.distances.iter().copied())
+            .zip(found.into_iter().map(|(_, mesh)| mesh))
+            .collect();
+        commands
+            .entity(mesh_entity)
+            .insert(Lod { levels, current: 0 });
+        for &child in lod_children.iter().skip(1) {
+            commands.entity(child).insert(Visibility::Hidden);
+        }
+    }
+}
+
+fn update_lod(
+    cameras: Query<&GlobalTransform, With<PlayerFollowingCamera>>,
+    mut lods: Query<(&mut Lod, &mut Handle<Mesh>, &GlobalTransform)>,
+) {
+    for (mut lod, mut mesh, transform) in lods.iter_mut() {
+        // Split-screen shows the detail the closest view needs
+        let Some(distance) = cameras
+            .iter()
+            .map(|camera| camera.translation().distance(transform.translation()))
+            .min_by(f32::total_cmp)
+        else {
+            return;
+        };
+
+        let mut current = lod.current;
+        while current + 1 < lod.levels.len()
+            && distance > lod.levels[current + 1].0 * (1. + LOD_HYSTERESIS)
+        {
+            current += 1;
+        }
+        while current > 0 && distance < lod.levels[current].0 * (1. - LOD_HYSTERESIS) {
+            current -= 1;
+        }
+
+        if current != lod.current {
+            lod.current = current;
+            *mesh = lod.levels[current].1.clone();
+        }
+    }
+}
@@ -1,13 +1,62 @@
+/// `screen_print!` on the debug overlay while its text is toggled on.
+/// Compiled out without the `debug` feature.
+macro_rules! debug_print {
+    (@discard sec: $sec:expr, $($rest:tt)*) => {
+        debug_print!(@discard $($rest)*)
+    };
+    (@discard col: $col:expr, $($rest:tt)*) => {
+        debug_print!(@discard $($rest)*)
+    };
+    // Keeps the arguments type-checked and used, without evaluating them
+    (@discard $($arg:tt)*) => {
+        if false {
+            let _ = format!($($arg)*);
+        }
+    };
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "debug")]
+        {
+            if crate::debug::text_enabled() {
+                bevy_debug_text_overlay::screen_print!($($arg)*);
+            }
+        }
+        #[cfg(not(feature = "debug"))]
+        {
+            debug_print!(@discard $($arg)*);
+        }
+    }};
+}
+
+pub mod breakable;
+pub mod carry;
+pub mod collectible;
+#[cfg(feature = "debug")]
+pub mod debug;
+pub mod enemy;
+pub mod environment;
+pub mod game;
+pub mod gravity;
+pub mod health;
+pub mod hud;
+pub mod input;
+pub mod interaction;
+pub mod lod;
+pub mod mechanism;
+pub mod platform;
 mod player;
 pub mod regino;
+pub mod replay;
+pub mod save;
+pub mod sound;
+pub mod speedrun;
 pub mod terrain;
+pub mod zone;
 
 use bevy::{
     core_pipeline::{bloom::BloomSettings, experimental::taa::TemporalAntiAliasBundle},
     prelude::*,
-    window::close_on_esc,
 };
-use bevy_debug_text_overlay::OverlayPlugin;
+#[cfg(feature = "inspector")]
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 // use bevy_editor_pls::EditorPlugin;
 // use bevy_framepace::FramepacePlugin;
@@ -15,17 +64,15 @@ use bevy_xpbd_3d::prelude::*;
 use player::PlayerFollowingCamera;
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_plugins(OverlayPlugin::default())
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins)
         // .add_plugins(FramepacePlugin)
-        .add_plugins(WorldInspectorPlugin::new())
         .add_plugins(PhysicsPlugins::default())
-        // .add_plugins(PhysicsDebugPlugin::default())
         .add_plugins(regino::ReginoPlugins)
-        .add_systems(Startup, setup)
-        .add_systems(Update, close_on_esc)
-        .run();
+        .add_systems(Startup, setup);
+    #[cfg(feature = "inspector")]
+    app.add_plugins(WorldInspectorPlugin::new());
+    app.run();
 }
 
 fn setup(mut commands: Commands) {
@@ -46,9 +93,4 @@ fn setup(mut commands: Commands) {
         .insert(TemporalAntiAliasBundle::default())
         .insert(Name::new("MainCamera"))
         .insert(PlayerFollowingCamera);
-
-    commands.insert_resource(AmbientLight {
-        color: Color::WHITE,
-        brightness: 0.2,
-    });
 }
@@ -0,0 +1,343 @@
+use bevy::prelude::*;
+use bevy::scene::SceneInstanceReady;
+use bevy::utils::HashSet;
+use bevy_xpbd_3d::prelude::*;
+
+use crate::carry::Carryable;
+use crate::interaction::{InteractEvent, Interactable};
+use crate::player::Player;
+use crate::zone::{make_zone_sensor, SensorOverlap, ZoneSensor};
+
+pub struct MechanismPlugin;
+
+impl Plugin for MechanismPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<TargetName>()
+            .register_type::<PressurePlate>()
+            .register_type::<PlateMode>()
+            .register_type::<Door>()
+            .register_type::<KeyId>()
+            .register_type::<Key>()
+            .register_type::<Lock>()
+            .init_resource::<PlayerInventory>()
+            .add_event::<DoorUnlockedEvent>()
+            .add_event::<DoorLockedEvent>()
+            .add_event::<ActivateEvent>()
+            .add_event::<DeactivateEvent>()
+            .add_systems(
+                Update,
+                (
+                    make_zone_sensor::<PressurePlate>,
+                    init_pressure_plate,
+                    resolve_plate_targets,
+                    press_plate,
+                ),
+            )
+            .add_systems(Update, (init_door, operate_door, move_door).chain())
+            .add_systems(Update, (make_zone_sensor::<Key>, collect_key));
+    }
+}
+
+/// Name other components refer to an entity by, since glTF can't hold entity references.
+#[derive(Default, Component, Reflect, Clone, PartialEq, Eq, Debug)]
+#[reflect(Component)]
+pub struct TargetName(pub String);
+
+pub fn find_target(names: &Query<(Entity, &TargetName)>, name: &TargetName) -> Option<Entity> {
+    names
+        .iter()
+        .find(|(_, target_name)| *target_name == name)
+        .map(|(entity, _)| entity)
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct ActivateEvent {
+    pub source: Entity,
+    pub target: Entity,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct DeactivateEvent {
+    pub source: Entity,
+    pub target: Entity,
+}
+
+// Pressure plate
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Reflect)]
+pub enum PlateMode {
+    /// Active while something stands on it, deactivated when it's left
+    #[default]
+    WhileWeighted,
+    /// Activated the first time it's stepped on and stays so
+    Once,
+}
+
+/// Sensor activating `targets` while the player or a carryable weighs it down.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct PressurePlate {
+    pub targets: Vec<Entity>,
+    /// Resolved into `targets` once the level is loaded
+    pub target_names: Vec<TargetName>,
+    pub mode: PlateMode,
+}
+
+#[derive(Component, Default)]
+struct PlateState {
+    pressed: bool,
+    latched: bool,
+}
+
+fn init_pressure_plate(mut commands: Commands, plates: Query<Entity, Added<PressurePlate>>) {
+    for entity in plates.iter() {
+        commands.entity(entity).insert(PlateState::default());
+    }
+}
+
+fn resolve_plate_targets(
+    mut ready: EventReader<SceneInstanceReady>,
+    mut plates: Query<(Entity, &mut PressurePlate)>,
+    names: Query<(Entity, &TargetName)>,
+) {
+    if ready.read().count() == 0 {
+        return;
+    }
+
+    for (entity, mut plate) in plates.iter_mut() {
+        let plate = &mut *plate;
+        for name in &plate.target_names {
+            let Some(target) = find_target(&names, name) else {
+                warn!("Pressure plate {entity:?} has no target named {:?}", name.0);
+                continue;
+            };
+            if !plate.targets.contains(&target) {
+                plate.targets.push(target);
+            }
+        }
+    }
+}
+
+fn press_plate(
+    sensors: Query<(&ZoneSensor, &CollidingEntities)>,
+    mut plates: Query<(&PressurePlate, &mut PlateState)>,
+    weights: Query<(), Or<(With<Player>, With<Carryable>)>>,
+    mut activate: EventWriter<ActivateEvent>,
+    mut deactivate: EventWriter<DeactivateEvent>,
+) {
+    for (sensor, colliding) in sensors.iter() {
+        let Ok((plate, mut state)) = plates.get_mut(sensor.zone) else {
+            continue;
+        };
+
+        let weighted = colliding.iter().any(|&entity| weights.contains(entity));
+        if weighted == state.pressed {
+            continue;
+        }
+        state.pressed = weighted;
+
+        let source = sensor.zone;
+        match plate.mode {
+            PlateMode::WhileWeighted if weighted => {
+                activate.send_batch(
+                    plate
+                        .targets
+                        .iter()
+                        .map(|&target| ActivateEvent { source, target }),
+                );
+            }
+            PlateMode::WhileWeighted => {
+                deactivate.send_batch(
+                    plate
+                        .targets
+                        .iter()
+                        .map(|&target| DeactivateEvent { source, target }),
+                );
+            }
+            PlateMode::Once if weighted && !state.latched => {
+                state.latched = true;
+                activate.send_batch(
+                    plate
+                        .targets
+                        .iter()
+                        .map(|&target| ActivateEvent { source, target }),
+                );
+            }
+            PlateMode::Once => {}
+        }
+    }
+}
+
+// Door
+
+/// Kinematic collider sliding by `open_offset` (world space) over `duration` seconds.
+/// Toggled by interacting with it, or opened and closed by `ActivateEvent`s.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Door {
+    pub open_offset: Vec3,
+    pub duration: f32,
+}
+
+#[derive(Component)]
+struct DoorState {
+    open: bool,
+    closed_position: Vec3,
+    /// 0 when closed, 1 when fully open
+    progress: f32,
+}
+
+fn init_door(
+    mut commands: Commands,
+    doors: Query<(Entity, &Position), (With<Door>, Without<DoorState>)>,
+) {
+    // Waits for the collider to have a `Position`
+    for (entity, position) in doors.iter() {
+        commands.entity(entity).insert((
+            DoorState {
+                open: false,
+                closed_position: position.0,
+                progress: 0.,
+            },
+            RigidBody::Kinematic,
+            Interactable,
+            CollidingEntities::default(),
+        ));
+    }
+}
+
+fn operate_door(
+    mut commands: Commands,
+    mut interact: EventReader<InteractEvent>,
+    mut activate: EventReader<ActivateEvent>,
+    mut deactivate: EventReader<DeactivateEvent>,
+    mut doors: Query<(&mut DoorState, Option<&Lock>)>,
+    mut inventory: ResMut<PlayerInventory>,
+    (mut unlocked, mut locked): (EventWriter<DoorUnlockedEvent>, EventWriter<DoorLockedEvent>),
+) {
+    for ev in interact.read() {
+        let Ok((mut door, lock)) = doors.get_mut(ev.target) else {
+            continue;
+        };
+
+        if let Some(lock) = lock {
+            if !inventory.keys.contains(&lock.required) {
+                locked.send(DoorLockedEvent {
+                    player: ev.player,
+                    door: ev.target,
+                    key: lock.required.clone(),
+                });
+                continue;
+            }
+            if lock.consume {
+                inventory.keys.remove(&lock.required);
+            }
+            commands.entity(ev.target).remove::<Lock>();
+            unlocked.send(DoorUnlockedEvent {
+                door: ev.target,
+                key: lock.required.clone(),
+            });
+        }
+
+        door.open = !door.open;
+    }
+    // Switches can't open a door that's still locked
+    for ev in activate.read() {
+        if let Ok((mut door, None)) = doors.get_mut(ev.target) {
+            door.open = true;
+        }
+    }
+    for ev in deactivate.read() {
+        if let Ok((mut door, _)) = doors.get_mut(ev.target) {
+            door.open = false;
+        }
+    }
+}
+
+fn move_door(
+    mut doors: Query<(&Door, &mut DoorState, &mut Position, &CollidingEntities)>,
+    player: Query<(), With<Player>>,
+    time: Res<Time>,
+) {
+    for (door, mut state, mut position, colliding) in doors.iter_mut() {
+        let target = if state.open { 1. } else { 0. };
+        if state.progress == target {
+            continue;
+        }
+
+        // Closing onto the player waits for them to get out of the way instead of crushing them
+        if !state.open && colliding.iter().any(|&entity| player.contains(entity)) {
+            continue;
+        }
+
+        let step = time.delta_seconds() / door.duration.max(f32::EPSILON);
+        state.progress = if state.open {
+            (state.progress + step).min(1.)
+        } else {
+            (state.progress - step).max(0.)
+        };
+        position.0 = state.closed_position + door.open_offset * state.progress;
+    }
+}
+
+// Keys
+
+#[derive(Default, Clone, PartialEq, Eq, Hash, Debug, Reflect)]
+pub struct KeyId(pub String);
+
+/// Key pickup, collected into `PlayerInventory` by touching it.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Key {
+    pub id: KeyId,
+}
+
+/// Keeps a `Door` shut until the player interacts with the `required` key in hand.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Lock {
+    pub required: KeyId,
+    /// Uses up the key on unlocking
+    pub consume: bool,
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct PlayerInventory {
+    pub keys: HashSet<KeyId>,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct DoorUnlockedEvent {
+    pub door: Entity,
+    pub key: KeyId,
+}
+
+/// The player tried a locked door without its key.
+#[derive(Event, Clone, Debug)]
+pub struct DoorLockedEvent {
+    pub player: Entity,
+    pub door: Entity,
+    pub key: KeyId,
+}
+
+fn collect_key(
+    mut commands: Commands,
+    mut overlaps: EventReader<SensorOverlap>,
+    keys: Query<&Key>,
+    player: Query<(), With<Player>>,
+    mut inventory: ResMut<PlayerInventory>,
+) {
+    for overlap in overlaps.read().filter(|overlap| overlap.entered()) {
+        let Ok(key) = keys.get(overlap.zone) else {
+            continue;
+        };
+        if !player.contains(overlap.other) {
+            continue;
+        }
+
+        debug_print!("picked up key {:?}", key.id.0);
+
+        inventory.keys.insert(key.id.clone());
+        commands.entity(overlap.zone).despawn_recursive();
+    }
+}
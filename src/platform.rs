@@ -0,0 +1,668 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_tnua::TnuaProximitySensor;
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use crate::game::GameState;
+use crate::input::Action;
+use crate::interaction::{InteractEvent, Interactable};
+use crate::mechanism::{find_target, ActivateEvent, TargetName};
+use crate::player::Player;
+
+pub struct PlatformPlugin;
+
+impl Plugin for PlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<MovingPlatform>()
+            .register_type::<LoopMode>()
+            .register_type::<RotatingPlatform>()
+            .register_type::<OneWayPlatform>()
+            .register_type::<Elevator>()
+            .register_type::<ElevatorButton>()
+            .register_type::<TimedPlatform>()
+            .init_resource::<PlatformClock>()
+            .add_systems(
+                Update,
+                (
+                    (
+                        init_moving_platform,
+                        init_rotating_platform,
+                        init_elevator,
+                        init_elevator_button,
+                    ),
+                    call_elevator,
+                    (move_platform, move_elevator),
+                    track_platform_delta,
+                    carry_player,
+                )
+                    .chain(),
+            )
+            .add_systems(Update, (init_one_way_platform, drop_through))
+            .add_systems(
+                Update,
+                (
+                    tick_platform_clock.run_if(in_state(GameState::Playing)),
+                    init_timed_platform,
+                    cycle_timed_platform,
+                )
+                    .chain(),
+            )
+            .add_systems(PostProcessCollisions, one_way_platform);
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Reflect)]
+pub enum LoopMode {
+    /// Travels back and forth, turning around at the last waypoint
+    #[default]
+    PingPong,
+    /// Heads back to the first waypoint after the last one
+    Cycle,
+}
+
+/// Moves the collider through `waypoints`, offsets from where it's placed in the level.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct MovingPlatform {
+    pub waypoints: Vec<Vec3>,
+    pub speed: f32,
+    pub loop_mode: LoopMode,
+}
+
+/// Progress through waypoints given as offsets from `origin`.
+#[derive(Component)]
+pub struct PlatformRoute {
+    origin: Vec3,
+    target: usize,
+    reversed: bool,
+}
+
+impl PlatformRoute {
+    pub fn new(origin: Vec3) -> Self {
+        Self {
+            origin,
+            target: 0,
+            reversed: false,
+        }
+    }
+
+    /// Moves `position` by `travel` along the route.
+    pub fn step(
+        &mut self,
+        position: &mut Vec3,
+        waypoints: &[Vec3],
+        loop_mode: LoopMode,
+        mut travel: f32,
+    ) {
+        // Travel left over after reaching a waypoint goes into the next leg,
+        // so the body doesn't stall for a frame when it turns around
+        for _ in 0..waypoints.len() {
+            let target = self.origin + waypoints[self.target];
+            let distance = position.distance(target);
+            if distance > travel {
+                *position += (target - *position) / distance * travel;
+                break;
+            }
+            *position = target;
+            travel -= distance;
+            self.advance(waypoints.len(), loop_mode);
+        }
+    }
+
+    fn advance(&mut self, len: usize, loop_mode: LoopMode) {
+        if len < 2 {
+            return;
+        }
+        match loop_mode {
+            LoopMode::Cycle => self.target = (self.target + 1) % len,
+            LoopMode::PingPong => {
+                if self.target == len - 1 {
+                    self.reversed = true;
+                } else if self.target == 0 {
+                    self.reversed = false;
+                }
+                if self.reversed {
+                    self.target -= 1;
+                } else {
+                    self.target += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Spins the collider around `axis` through its origin.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct RotatingPlatform {
+    pub axis: Vec3,
+    /// Radians per second
+    pub angular_speed: f32,
+}
+
+/// How a platform moved since the last frame.
+#[derive(Component)]
+pub struct PlatformDelta {
+    last_position: Vec3,
+    last_rotation: Quat,
+    pub translation: Vec3,
+    pub rotation: Quat,
+}
+
+impl PlatformDelta {
+    fn new(position: &Position, rotation: &Rotation) -> Self {
+        Self {
+            last_position: position.0,
+            last_rotation: rotation.0,
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+        }
+    }
+}
+
+/// Solid only from the `normal` side, so it can be jumped through from below.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct OneWayPlatform {
+    pub normal: Vec3,
+}
+
+impl Default for OneWayPlatform {
+    fn default() -> Self {
+        Self { normal: Vec3::Y }
+    }
+}
+
+/// Bodies currently passing through a one-way platform. Tracked per platform
+/// so stacked platforms don't affect each other.
+#[derive(Component, Default)]
+struct OneWayPassing(HashSet<Entity>);
+
+fn init_moving_platform(
+    mut commands: Commands,
+    platforms: Query<
+        (Entity, &Position, &Rotation),
+        (With<MovingPlatform>, Without<PlatformRoute>),
+    >,
+) {
+    // Waits for the collider to have a `Position`
+    for (entity, position, rotation) in platforms.iter() {
+        commands.entity(entity).insert((
+            PlatformRoute::new(position.0),
+            PlatformDelta::new(position, rotation),
+            RigidBody::Kinematic,
+        ));
+    }
+}
+
+fn init_rotating_platform(
+    mut commands: Commands,
+    platforms: Query<(Entity, &RotatingPlatform, &Position, &Rotation), Without<PlatformDelta>>,
+) {
+    for (entity, platform, position, rotation) in platforms.iter() {
+        commands.entity(entity).insert((
+            PlatformDelta::new(position, rotation),
+            RigidBody::Kinematic,
+            AngularVelocity(platform.axis.normalize_or_zero() * platform.angular_speed),
+        ));
+    }
+}
+
+// Moved through its velocity rather than its position, so Tnua sees the
+// ground moving and keeps the player in step with it. Aimed from where the
+// body is each frame, so the physics timestep doesn't drift it off the route.
+fn move_platform(
+    mut platforms: Query<(
+        &MovingPlatform,
+        &mut PlatformRoute,
+        &Position,
+        &mut LinearVelocity,
+    )>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    // Held still for the frame, rather than keeping on at the last velocity
+    if dt <= 0. {
+        for (_, _, _, mut velocity) in platforms.iter_mut() {
+            velocity.0 = Vec3::ZERO;
+        }
+        return;
+    }
+    for (platform, mut route, position, mut velocity) in platforms.iter_mut() {
+        let mut target = position.0;
+        route.step(
+            &mut target,
+            &platform.waypoints,
+            platform.loop_mode,
+            platform.speed * dt,
+        );
+        velocity.0 = (target - position.0) / dt;
+    }
+}
+
+fn track_platform_delta(mut platforms: Query<(&Position, &Rotation, &mut PlatformDelta)>) {
+    for (position, rotation, mut delta) in platforms.iter_mut() {
+        delta.translation = position.0 - delta.last_position;
+        delta.rotation = rotation.0 * delta.last_rotation.inverse();
+        delta.last_position = position.0;
+        delta.last_rotation = rotation.0;
+    }
+}
+
+/// Turns the player along with the platform they stand on. Being carried is
+/// left to Tnua, which moves the player with the ground velocity, and keeps
+/// that momentum on stepping off.
+fn carry_player(
+    platforms: Query<&PlatformDelta>,
+    mut player: Query<
+        (&TnuaProximitySensor, &mut Rotation),
+        (With<Player>, Without<PlatformDelta>),
+    >,
+) {
+    for (sensor, mut rotation) in player.iter_mut() {
+        let Some(delta) = sensor
+            .output
+            .as_ref()
+            .and_then(|output| platforms.get(output.entity).ok())
+        else {
+            continue;
+        };
+
+        // Only around the vertical
+        let (yaw, _, _) = delta.rotation.to_euler(EulerRot::YXZ);
+        rotation.0 = Quat::from_rotation_y(yaw) * rotation.0;
+    }
+}
+
+fn init_one_way_platform(mut commands: Commands, platforms: Query<Entity, Added<OneWayPlatform>>) {
+    for entity in platforms.iter() {
+        commands.entity(entity).insert(OneWayPassing::default());
+    }
+}
+
+/// Down + Jump lets the player fall through the one-way platform they stand on.
+fn drop_through(
+    player: Query<(Entity, &ActionState<Action>, &TnuaProximitySensor), With<Player>>,
+    mut platforms: Query<&mut OneWayPassing>,
+) {
+    for (entity, input, sensor) in player.iter() {
+        if !(input.pressed(Action::Down) && input.just_pressed(Action::Jump)) {
+            continue;
+        }
+        let Some(ground) = sensor.output.as_ref() else {
+            continue;
+        };
+        if let Ok(mut passing) = platforms.get_mut(ground.entity) {
+            passing.0.insert(entity);
+        }
+    }
+}
+
+fn one_way_platform(
+    mut platforms: Query<(Entity, &OneWayPlatform, &mut OneWayPassing)>,
+    velocities: Query<&LinearVelocity>,
+    mut collisions: ResMut<Collisions>,
+) {
+    // Passing ends once the body no longer overlaps the platform
+    for (platform_entity, _, mut passing) in platforms.iter_mut() {
+        passing
+            .0
+            .retain(|&entity| collisions.get(platform_entity, entity).is_some());
+    }
+
+    collisions.retain(|contacts| {
+        let (platform_entity, other) = if platforms.contains(contacts.entity1) {
+            (contacts.entity1, contacts.entity2)
+        } else if platforms.contains(contacts.entity2) {
+            (contacts.entity2, contacts.entity1)
+        } else {
+            return true;
+        };
+        let Ok((_, platform, mut passing)) = platforms.get_mut(platform_entity) else {
+            return true;
+        };
+
+        if passing.0.contains(&other) {
+            return false;
+        }
+
+        // Moving along the normal means rising through it from below
+        let velocity = velocities.get(other).map_or(Vec3::ZERO, |v| v.0);
+        if velocity.dot(platform.normal) > 0. {
+            passing.0.insert(other);
+            return false;
+        }
+
+        true
+    });
+}
+
+// Elevator
+
+/// Lift travelling between two stops, offsets from where it's placed in the level.
+/// Called by interacting with it, an `ElevatorButton` or an `ActivateEvent`.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Elevator {
+    pub bottom: Vec3,
+    pub top: Vec3,
+    pub speed: f32,
+}
+
+/// Interactable that calls the elevator with the given `TargetName`.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct ElevatorButton {
+    pub elevator: TargetName,
+}
+
+#[derive(Component)]
+struct ElevatorState {
+    origin: Vec3,
+    going_up: bool,
+    /// Called while travelling, turns around once the current stop is reached
+    queued: bool,
+}
+
+impl ElevatorState {
+    fn stop(&self, elevator: &Elevator) -> Vec3 {
+        self.origin
+            + if self.going_up {
+                elevator.top
+            } else {
+                elevator.bottom
+            }
+    }
+}
+
+fn init_elevator(
+    mut commands: Commands,
+    elevators: Query<(Entity, &Position, &Rotation), (With<Elevator>, Without<ElevatorState>)>,
+) {
+    // Waits for the collider to have a `Position`
+    for (entity, position, rotation) in elevators.iter() {
+        commands.entity(entity).insert((
+            ElevatorState {
+                origin: position.0,
+                going_up: false,
+                queued: false,
+            },
+            PlatformDelta::new(position, rotation),
+            RigidBody::Kinematic,
+            Interactable,
+        ));
+    }
+}
+
+fn init_elevator_button(mut commands: Commands, buttons: Query<Entity, Added<ElevatorButton>>) {
+    for entity in buttons.iter() {
+        commands.entity(entity).insert(Interactable);
+    }
+}
+
+fn call_elevator(
+    mut interact: EventReader<InteractEvent>,
+    mut activate: EventReader<ActivateEvent>,
+    buttons: Query<&ElevatorButton>,
+    names: Query<(Entity, &TargetName)>,
+    mut elevators: Query<(&Elevator, &mut ElevatorState, &Position)>,
+) {
+    let called = interact
+        .read()
+        .map(|ev| {
+            buttons
+                .get(ev.target)
+                .ok()
+                .and_then(|button| find_target(&names, &button.elevator))
+                .unwrap_or(ev.target)
+        })
+        .chain(activate.read().map(|ev| ev.target))
+        .collect::<Vec<_>>();
+
+    for entity in called {
+        let Ok((elevator, mut state, position)) = elevators.get_mut(entity) else {
+            continue;
+        };
+
+        // Calls during travel are queued instead of reversing on the spot
+        if position.0.distance(state.stop(elevator)) > f32::EPSILON {
+            state.queued = true;
+        } else {
+            state.going_up = !state.going_up;
+        }
+    }
+}
+
+// Through the velocity like `move_platform`
+fn move_elevator(
+    mut elevators: Query<(
+        &Elevator,
+        &mut ElevatorState,
+        &Position,
+        &mut LinearVelocity,
+    )>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    if dt <= 0. {
+        for (_, _, _, mut velocity) in elevators.iter_mut() {
+            velocity.0 = Vec3::ZERO;
+        }
+        return;
+    }
+    for (elevator, mut state, position, mut velocity) in elevators.iter_mut() {
+        let stop = state.stop(elevator);
+        let distance = position.0.distance(stop);
+        let travel = elevator.speed * dt;
+
+        if distance > travel {
+            velocity.0 = (stop - position.0) / distance * elevator.speed;
+        } else {
+            // Lands exactly on the stop this frame
+            velocity.0 = (stop - position.0) / dt;
+            if state.queued {
+                state.queued = false;
+                state.going_up = !state.going_up;
+            }
+        }
+    }
+}
+
+// Timed platforms
+
+/// Solid and visible for `on_duration`, then gone for `off_duration`. All of
+/// them run off the same `PlatformClock`, shifted by `phase` seconds.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct TimedPlatform {
+    pub on_duration: f32,
+    pub off_duration: f32,
+    pub phase: f32,
+}
+
+/// Seconds of play behind every `TimedPlatform`, paused outside of `Playing`
+/// so the rhythm of a section stays as it was left.
+#[derive(Resource, Default, Debug)]
+pub struct PlatformClock(pub f32);
+
+/// Collision layers of a timed platform while it's gone, to restore afterwards.
+#[derive(Component, Default)]
+struct TimedPlatformState {
+    hidden_layers: Option<CollisionLayers>,
+}
+
+/// Flashing before vanishing, for this long before the end of `on_duration`.
+const TIMED_PLATFORM_WARNING: f32 = 0.8;
+/// Flashes per second of the warning.
+const TIMED_PLATFORM_FLASH_RATE: f32 = 6.;
+
+fn tick_platform_clock(mut clock: ResMut<PlatformClock>, time: Res<Time>) {
+    clock.0 += time.delta_seconds();
+}
+
+fn init_timed_platform(mut commands: Commands, platforms: Query<Entity, Added<TimedPlatform>>) {
+    for entity in platforms.iter() {
+        commands
+            .entity(entity)
+            .insert(TimedPlatformState::default());
+    }
+}
+
+// Off is done with collision layers rather than removing the collider, which
+// is generated asynchronously. Whoever stood on it just starts falling.
+fn cycle_timed_platform(
+    mut commands: Commands,
+    mut platforms: Query<(
+        Entity,
+        &TimedPlatform,
+        &mut TimedPlatformState,
+        &mut Visibility,
+        Option<&CollisionLayers>,
+    )>,
+    clock: Res<PlatformClock>,
+) {
+    for (entity, platform, mut state, mut visibility, layers) in platforms.iter_mut() {
+        let cycle = platform.on_duration + platform.off_duration;
+        if cycle <= 0. {
+            continue;
+        }
+        let t = (clock.0 + platform.phase).rem_euclid(cycle);
+        let solid = t < platform.on_duration;
+
+        if solid {
+            if let Some(layers) = state.hidden_layers.take() {
+                commands.entity(entity).insert(layers);
+            }
+        } else if state.hidden_layers.is_none() {
+            state.hidden_layers = Some(layers.copied().unwrap_or_default());
+            commands.entity(entity).insert(CollisionLayers::none());
+        }
+
+        let left = platform.on_duration - t;
+        let shown = solid
+            && (left > TIMED_PLATFORM_WARNING
+                || (left * TIMED_PLATFORM_FLASH_RATE * 2.).floor() as i32 % 2 == 0);
+        visibility.set_if_neq(if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::scene::ScenePlugin;
+    use bevy::time::TimeUpdateStrategy;
+    use bevy_tnua::builtins::TnuaBuiltinWalk;
+    use bevy_tnua::controller::{TnuaController, TnuaControllerBundle, TnuaControllerPlugin};
+    use bevy_tnua::TnuaUserControlsSystemSet;
+    use bevy_tnua_xpbd3d::TnuaXpbd3dPlugin;
+
+    use super::*;
+    use crate::player::PlayerDimensions;
+
+    fn stand_still(mut player: Query<&mut TnuaController>) {
+        let dimensions = PlayerDimensions::default();
+        for mut controller in player.iter_mut() {
+            controller.basis(TnuaBuiltinWalk {
+                float_height: dimensions.float_height(),
+                ..default()
+            });
+        }
+    }
+
+    fn time_step(dt: f32) -> TimeUpdateStrategy {
+        TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(dt))
+    }
+
+    #[test]
+    fn fast_platform_carries_the_player() {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            AssetPlugin::default(),
+            ScenePlugin,
+            PhysicsPlugins::default(),
+            TnuaXpbd3dPlugin,
+            TnuaControllerPlugin,
+        ))
+        .init_asset::<Mesh>()
+        .insert_resource(time_step(1. / 60.))
+        .add_systems(
+            Update,
+            (
+                init_moving_platform,
+                move_platform,
+                track_platform_delta,
+                carry_player,
+            )
+                .chain(),
+        )
+        .add_systems(Update, stand_still.in_set(TnuaUserControlsSystemSet));
+
+        // Far faster than the player walks, back and forth
+        let platform = app
+            .world
+            .spawn((
+                MovingPlatform {
+                    waypoints: vec![Vec3::ZERO, Vec3::X * 6.],
+                    speed: 12.,
+                    loop_mode: LoopMode::PingPong,
+                },
+                Collider::cuboid(20., 0.5, 20.),
+                TransformBundle::default(),
+                Position(Vec3::ZERO),
+            ))
+            .id();
+        let dimensions = PlayerDimensions::default();
+        let start = Vec3::Y * (0.25 + dimensions.float_height());
+        let player = app
+            .world
+            .spawn((
+                Player,
+                dimensions.collider(),
+                RigidBody::Dynamic,
+                LockedAxes::new().lock_rotation_x().lock_rotation_z(),
+                TnuaControllerBundle::default(),
+                TransformBundle::from_transform(Transform::from_translation(start)),
+                Position(start),
+            ))
+            .id();
+
+        // Settles onto the platform
+        for _ in 0..30 {
+            app.update();
+        }
+        let offset = |app: &App| {
+            let platform = app.world.get::<Position>(platform).unwrap().0;
+            let player = app.world.get::<Position>(player).unwrap().0;
+            (player - platform) * Vec3::new(1., 0., 1.)
+        };
+        let settled = offset(&app);
+
+        for frame in 0..180 {
+            app.update();
+            let drift = offset(&app).distance(settled);
+            assert!(
+                drift < 0.25,
+                "player slid {drift} off its spot by frame {frame}"
+            );
+        }
+        // It went somewhere, with the player on it
+        assert_ne!(app.world.get::<Position>(platform).unwrap().0, Vec3::ZERO);
+
+        // A frame of no time leaves the platform still, not at its last velocity
+        app.insert_resource(time_step(0.));
+        app.update();
+        assert_eq!(
+            app.world.get::<LinearVelocity>(platform).unwrap().0,
+            Vec3::ZERO
+        );
+    }
+}
@@ -1,29 +1,64 @@
+use std::ops::Range;
+
+use bevy::asset::LoadState;
+use bevy::core::FrameCount;
+use bevy::core_pipeline::bloom::BloomSettings;
 use bevy::ecs::system::EntityCommands;
+use bevy::pbr::NotShadowCaster;
 use bevy::prelude::*;
-use bevy_debug_text_overlay::screen_print;
-use bevy_tnua::{builtins::TnuaBuiltinWalk, controller::TnuaController, TnuaUserControlsSystemSet};
-use bevy_tnua::{control_helpers::TnuaCrouchEnforcerPlugin, prelude::*};
+use bevy::render::camera::Viewport;
+use bevy::utils::{HashMap, Instant};
+use bevy::window::PrimaryWindow;
+use bevy_tnua::{
+    builtins::{TnuaBuiltinCrouch, TnuaBuiltinWalk},
+    controller::TnuaController,
+    TnuaProximitySensor, TnuaUserControlsSystemSet,
+};
+use bevy_tnua::{
+    control_helpers::{TnuaCrouchEnforcer, TnuaCrouchEnforcerPlugin},
+    prelude::*,
+};
 use bevy_tnua_xpbd3d::*;
 use bevy_xpbd_3d::prelude::*;
 use leafwing_input_manager::prelude::*;
 use seldom_state::prelude::*;
-use seldom_state::trigger::AndTrigger;
+use seldom_state::trigger::{AndTrigger, OrTrigger};
 use smooth_bevy_cameras::{LookTransform, LookTransformBundle, LookTransformPlugin, Smoother};
 
-use crate::terrain::Ladder;
+use crate::game::GameState;
+use crate::gravity::PlayerUp;
+use crate::health::{DamageEvent, Invulnerability};
+use crate::input::{add_action_state, Action, InputDevices};
+use crate::interaction::{InteractEvent, InteractionConfig, InteractionRayCaster, RaycastSource};
+use crate::platform::OneWayPlatform;
+use crate::terrain::{Ladder, MonkeyBars, RopeSegment, SurfaceKind, SurfaceMaterial};
+use crate::zone::{CameraZone, WaterVolume, WindExposure, ZoneSensor};
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, startup);
+        app.init_resource::<PlayerDimensions>()
+            .add_systems(Startup, startup)
+            .add_systems(
+                Update,
+                build_player_atlas.run_if(resource_exists::<PlayerFrameImages>()),
+            );
         build_player_add(app);
         build_movement(app);
         build_player_camera(app);
+        #[cfg(feature = "inspector")]
+        build_inspector_types(app);
 
         app.add_plugins(StateMachinePlugin::default());
         app.add_event::<LadderInteractionBeginEvent>()
-            .add_event::<LadderInteractionEndEvent>();
+            .add_event::<LadderInteractionEndEvent>()
+            .add_event::<MonkeyBarsInteractionBeginEvent>()
+            .add_event::<RopeInteractionBeginEvent>()
+            .add_event::<PlayerJumpedEvent>()
+            .add_event::<PlayerLandedEvent>()
+            .add_event::<GroundPoundEvent>()
+            .add_event::<FootstepEvent>();
         // Required to apply LinearVelocity
         app.add_systems(
             Update,
@@ -31,7 +66,19 @@ impl Plugin for PlayerPlugin {
                 .after(seldom_state::set::StateSet::Transition)
                 .before(TnuaPipelineStages::Motors),
         );
-        app.add_systems(Update, player_interaction);
+        app.add_systems(
+            Update,
+            (
+                ladder_interaction,
+                ladder_top_mount,
+                leave_ladder,
+                monkey_bars_interaction,
+                rope_interaction,
+            )
+                .run_if(in_state(GameState::Playing)),
+        );
+        build_respawn(app);
+        build_blob_shadow(app);
     }
 }
 
@@ -40,100 +87,793 @@ fn startup(
     assets: Res<AssetServer>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    let mut mats = vec![];
+    commands.insert_resource(PlayerFrameImages(
+        PLAYER_FRAME_PATHS.map(|path| assets.load(path)).to_vec(),
+    ));
+
+    // Masked out entirely until the atlas is in
+    let material = materials.add(StandardMaterial {
+        base_color: Color::NONE,
+        alpha_mode: AlphaMode::Mask(0.1),
+        double_sided: true,
+        cull_mode: None,
+        ..default()
+    });
+    commands.insert_resource(PlayerAnimations {
+        material,
+        frames: vec![],
+        animations: HashMap::from([
+            ("idle", SpriteAnimation::still(0)),
+            (
+                "run",
+                SpriteAnimation {
+                    duration: 0.4,
+                    frames: 0..2,
+                },
+            ),
+            ("crouch", SpriteAnimation::still(2)),
+            ("jump", SpriteAnimation::still(2)),
+            ("fall", SpriteAnimation::still(2)),
+            (
+                "climb",
+                SpriteAnimation {
+                    duration: 0.5,
+                    frames: 0..2,
+                },
+            ),
+        ]),
+    });
+    commands.insert_resource(PlayerMovementConfig::default());
+}
+
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct Player;
+
+/// Tuning knobs shared by the player movement systems.
+#[derive(Resource, Debug, Clone)]
+pub struct PlayerMovementConfig {
+    pub walk_speed: f32,
+    /// Multiplies `walk_speed` while sprinting
+    pub sprint_factor: f32,
+    pub jump_height: f32,
+    pub ladder_speed: f32,
+    /// How far past the top of a ladder the player can walk off and still
+    /// grab onto it
+    pub ladder_top_reach: f32,
+    /// Maximum falling speed while sliding down a wall
+    pub wall_slide_speed: f32,
+    /// Horizontal speed away from the wall given by a wall jump
+    pub wall_jump_detach_speed: f32,
+    /// Time it takes to climb onto a grabbed ledge
+    pub mantle_duration: f32,
+    /// Time it takes to line up with a grabbed ladder before climbing it
+    pub ladder_mount_duration: f32,
+    /// Fraction of the ground acceleration available while airborne
+    pub air_control: f32,
+    /// Steepest ground, in radians, the player can stand on without sliding off
+    pub max_slope_angle: f32,
+    pub slope_slide_speed: f32,
+    pub swim_speed: f32,
+    /// Upward acceleration while submerged, as a multiple of gravity
+    pub swim_buoyancy: f32,
+    /// How quickly the water brings the swimming velocity to the desired one
+    pub swim_drag: f32,
+    /// Height of the leap out of the water when jumping at the surface
+    pub swim_leap_height: f32,
+    /// Landing faster than this is safe, anything above hurts
+    pub fall_damage_speed: f32,
+    /// Damage per unit of landing speed over `fall_damage_speed`, zero to turn
+    /// fall damage off
+    pub fall_damage_per_speed: f32,
+    /// Ground covered by a dash
+    pub dash_distance: f32,
+    pub dash_duration: f32,
+    /// Seconds from the start of a dash until the next one
+    pub dash_cooldown: f32,
+    /// I-frames at the start of a dash, zero for none
+    pub dash_invulnerability: f32,
+    /// Downward speed of a ground pound
+    pub ground_pound_speed: f32,
+}
+
+impl Default for PlayerMovementConfig {
+    fn default() -> Self {
+        Self {
+            walk_speed: 2.0,
+            sprint_factor: 1.75,
+            jump_height: 1.0,
+            ladder_speed: 2.0,
+            ladder_top_reach: 0.5,
+            wall_slide_speed: 1.0,
+            wall_jump_detach_speed: 4.0,
+            mantle_duration: 0.3,
+            ladder_mount_duration: 0.15,
+            air_control: 1. / 3.,
+            max_slope_angle: 50_f32.to_radians(),
+            slope_slide_speed: 4.0,
+            swim_speed: 1.5,
+            swim_buoyancy: 1.2,
+            swim_drag: 3.0,
+            swim_leap_height: 0.8,
+            fall_damage_speed: 12.0,
+            fall_damage_per_speed: 10.0,
+            dash_distance: 3.0,
+            dash_duration: 0.2,
+            dash_cooldown: 0.8,
+            dash_invulnerability: 0.2,
+            ground_pound_speed: 15.0,
+        }
+    }
+}
+
+/// Frames of the player sprite: the two run frames, then the still frame.
+/// There are no jump, fall or climb frames yet, so those animations reuse these.
+const PLAYER_FRAME_PATHS: [&str; 3] = [
+    "sprites/cute_apple_run_0_cropped.png",
+    "sprites/cute_apple_run_1_cropped.png",
+    "sprites/cute_apple_still_cropped.png",
+];
+
+/// Frame images still loading, packed into the atlas once they're all in.
+#[derive(Resource)]
+struct PlayerFrameImages(Vec<Handle<Image>>);
+
+/// The material showing the player's sprite atlas, where each frame is, and
+/// the named animations made of them.
+#[derive(Resource)]
+pub struct PlayerAnimations {
+    material: Handle<StandardMaterial>,
+    /// UV rect of each frame in the atlas, in the order of `PLAYER_FRAME_PATHS`.
+    /// Empty until the atlas is built.
+    frames: Vec<Rect>,
+    animations: HashMap<&'static str, SpriteAnimation>,
+}
+
+/// Range of atlas frames shown one after another over `duration`.
+#[derive(Debug, Clone)]
+struct SpriteAnimation {
+    /// Seconds per cycle at normal speed
+    duration: f32,
+    frames: Range<usize>,
+}
+
+impl SpriteAnimation {
+    fn still(frame: usize) -> Self {
+        Self {
+            duration: 1.,
+            frames: frame..frame + 1,
+        }
+    }
+
+    fn frame_at(&self, phase: f32) -> usize {
+        let offset = (phase * self.frames.len() as f32) as usize;
+        self.frames.start + offset.min(self.frames.len().saturating_sub(1))
+    }
+}
+
+impl PlayerAnimations {
+    fn get(&self, name: &str) -> Option<&SpriteAnimation> {
+        self.animations.get(name)
+    }
+
+    pub fn material(&self) -> &Handle<StandardMaterial> {
+        &self.material
+    }
+
+    pub fn is_ready(&self) -> bool {
+        !self.frames.is_empty()
+    }
+}
+
+fn build_player_atlas(
+    mut commands: Commands,
+    pending: Res<PlayerFrameImages>,
+    assets: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut animations: ResMut<PlayerAnimations>,
+) {
+    let mut builder = TextureAtlasBuilder::default();
+    for handle in &pending.0 {
+        let Some(image) = images.get(handle) else {
+            if assets.load_state(handle.id()) == LoadState::Failed {
+                error!("Failed to load a player sprite frame, the player stays invisible");
+                commands.remove_resource::<PlayerFrameImages>();
+            }
+            return;
+        };
+        builder.add_texture(handle.id(), image);
+    }
+    commands.remove_resource::<PlayerFrameImages>();
 
-    for i in 0..=1 {
-        let image = assets.load::<Image>(format!("sprites/cute_apple_run_{i}_cropped.png"));
-        mats.push(materials.add(StandardMaterial {
-            alpha_mode: AlphaMode::Mask(0.1),
-            double_sided: true,
-            cull_mode: None,
-            ..image.into()
-        }));
+    let atlas = match builder.finish(&mut images) {
+        Ok(atlas) => atlas,
+        Err(e) => {
+            error!("Failed to build the player sprite atlas: {e:?}");
+            return;
+        }
+    };
+    animations.frames = pending
+        .0
+        .iter()
+        .filter_map(|handle| atlas.get_texture_index(handle))
+        .map(|i| {
+            let rect = atlas.textures[i];
+            Rect::from_corners(rect.min / atlas.size, rect.max / atlas.size)
+        })
+        .collect();
+    if let Some(material) = materials.get_mut(&animations.material) {
+        material.base_color = Color::WHITE;
+        material.base_color_texture = Some(atlas.texture);
     }
+}
 
-    commands.insert_resource(PlayerImages(mats));
+/// Corners of `shape::Quad`, in the order of its vertices.
+const QUAD_CORNERS: [Vec2; 4] = [
+    Vec2::new(0., 1.),
+    Vec2::new(0., 0.),
+    Vec2::new(1., 0.),
+    Vec2::new(1., 1.),
+];
+
+fn quad_uvs(frame: Rect) -> Vec<[f32; 2]> {
+    QUAD_CORNERS
+        .iter()
+        .map(|corner| (frame.min + *corner * frame.size()).to_array())
+        .collect()
 }
 
+/// Plays the named animations of `PlayerAnimations` on a sprite quad of its
+/// own, by moving the quad's UVs to the frame in the atlas.
 #[derive(Component, Debug)]
-pub struct Player;
+pub struct SpriteAnimator {
+    animation: &'static str,
+    /// Multiplies the speed the animation plays at
+    rate: f32,
+    /// Progress through the current cycle, in 0..1
+    phase: f32,
+    /// Frame the quad's UVs currently show
+    frame: Option<usize>,
+}
 
-#[derive(Resource)]
-struct PlayerImages(Vec<Handle<StandardMaterial>>);
+impl Default for SpriteAnimator {
+    fn default() -> Self {
+        Self {
+            animation: "idle",
+            rate: 1.,
+            phase: 0.,
+            frame: None,
+        }
+    }
+}
+
+impl SpriteAnimator {
+    /// Switches to the animation from its start, unless it's the one playing.
+    pub fn play(&mut self, name: &'static str, rate: f32) {
+        if self.animation != name {
+            self.animation = name;
+            self.phase = 0.;
+        }
+        self.rate = rate;
+    }
+}
+
+// Rope and ledge states hold entities, which can't be defaulted for reflection
+#[cfg(feature = "inspector")]
+fn build_inspector_types(app: &mut App) {
+    app.register_type::<Player>()
+        .register_type::<DoubleJump>()
+        .register_type::<Stamina>()
+        .register_type::<PlayerGrounded>()
+        .register_type::<PlayerJumping>()
+        .register_type::<PlayerWallSliding>()
+        .register_type::<PlayerSliding>()
+        .register_type::<PlayerMantling>()
+        .register_type::<PlayerDashing>()
+        .register_type::<PlayerGroundPounding>()
+        .register_type::<PlayerSwimming>()
+        .register_type::<PlayerMountingLadder>()
+        .register_type::<PlayerMovingOnLadder>()
+        .register_type::<PlayerHangingTraversal>();
+}
 
 fn build_player_add(app: &mut App) {
-    app.add_systems(Update, add_player);
+    app.add_systems(Update, add_player.run_if(in_state(GameState::Playing)));
 }
 
-#[derive(Component, Clone, Debug)]
-struct PlayerGrounded;
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+pub struct PlayerGrounded;
 
-#[derive(Component, Clone, Debug)]
-struct PlayerJumping;
+/// Airborne after a jump, rising or falling.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct PlayerJumping {
+    /// Started in mid-air using one of the `DoubleJump` extra jumps
+    air_jump: bool,
+    /// Kicking off a wall with this normal
+    wall_jump: Option<Vec3>,
+}
+
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+struct PlayerWallSliding {
+    normal: Vec3,
+}
+
+/// Sliding down ground steeper than `PlayerMovementConfig::max_slope_angle`.
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+struct PlayerSliding;
+
+#[derive(Clone, Copy, Debug)]
+struct Ledge {
+    entity: Entity,
+    /// Point on top of the ledge the player grabs
+    top: Vec3,
+    /// Normal of the wall below the ledge, pointing towards the player
+    normal: Vec3,
+    /// Up of the player who found it
+    up: Vec3,
+}
 
 #[derive(Component, Clone, Debug)]
-struct PlayerMovingOnLadder {
+struct PlayerLedgeHanging(Ledge);
+
+/// Climbing from `PlayerLedgeHanging` onto the top of the ledge.
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+struct PlayerMantling {
+    from: Option<Vec3>,
+    to: Vec3,
+    up: Vec3,
+    elapsed: f32,
+}
+
+/// Short burst of speed along the ground or through the air, back to
+/// `PlayerGrounded` once over.
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+struct PlayerDashing {
+    /// Picked on the first frame, from the input or else the facing
+    direction: Option<Vec3>,
+    elapsed: f32,
+}
+
+/// Slamming straight down from mid-air, back to `PlayerGrounded` on impact.
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+pub struct PlayerGroundPounding;
+
+/// When the player may dash again.
+#[derive(Component, Default, Debug)]
+struct DashCooldown {
+    ready_at: f32,
+    /// The one dash allowed in the air is spent until landing
+    air_dash_used: bool,
+}
+
+/// Ledge within reach while falling, updated every frame.
+#[derive(Component, Default, Debug)]
+struct LedgeContact {
+    ledge: Option<Ledge>,
+    /// Can't be grabbed again until landing, so releasing doesn't loop into a re-grab
+    last_grabbed: Option<Entity>,
+}
+
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+struct PlayerSwimming;
+
+/// How deep below the water surface the player's center is, updated every frame.
+#[derive(Component, Default, Debug)]
+struct WaterContact {
+    depth: Option<f32>,
+}
+
+/// Surface the player stands on, updated every frame. Default while airborne.
+#[derive(Component, Default, Debug)]
+struct GroundSurface(SurfaceMaterial);
+
+/// Vertical speed while airborne, kept to tell how hard the player lands.
+#[derive(Component, Default, Debug)]
+struct LandingTracker {
+    airborne: bool,
+    vertical_speed: f32,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct PlayerJumpedEvent {
+    pub entity: Entity,
+    pub air_jump: bool,
+}
+
+/// A ground pound hitting the ground, right after its `PlayerLandedEvent`.
+#[derive(Event, Clone, Debug)]
+pub struct GroundPoundEvent {
+    pub entity: Entity,
+    pub position: Vec3,
+    /// What was landed on
+    pub ground: Option<Entity>,
+}
+
+/// Touching the ground after being airborne, `PlayerJumping` or not.
+#[derive(Event, Clone, Debug)]
+pub struct PlayerLandedEvent {
+    pub entity: Entity,
+    /// Downward speed just before touching down
+    pub impact_speed: f32,
+}
+
+/// Wall the airborne player is pushing against while falling, updated every frame.
+#[derive(Component, Default, Debug)]
+struct WallContact {
+    normal: Option<Vec3>,
+}
+
+/// Presses as the fixed ticks saw them, which the state machine goes by
+/// rather than the frame's. A replay feeds the recorded states a tick at a
+/// time, so its transitions follow the same presses as the recording's.
+#[derive(Component, Default, Debug)]
+struct TickPresses {
+    /// Pressed since the last tick, so a frame without one doesn't drop them
+    pending: Vec<Action>,
+    /// Frame the latest tick ran in
+    frame: u32,
+    /// Pressed on the ticks of `frame`
+    just_pressed: Vec<Action>,
+    /// Held on the latest tick
+    pressed: Vec<Action>,
+}
+
+/// Grace window after walking off a ledge during which a jump is still accepted.
+#[derive(Component, Debug)]
+struct CoyoteTime {
+    window: f32,
+    last_grounded: Option<f32>,
+}
+
+impl Default for CoyoteTime {
+    fn default() -> Self {
+        Self {
+            window: 0.12,
+            last_grounded: None,
+        }
+    }
+}
+
+/// Jump press remembered for a short while so it isn't dropped just before landing.
+#[derive(Component, Debug)]
+struct JumpBuffer {
+    window: f32,
+    pressed_at: Option<f32>,
+}
+
+impl Default for JumpBuffer {
+    fn default() -> Self {
+        Self {
+            window: 0.1,
+            pressed_at: None,
+        }
+    }
+}
+
+/// Optional ability allowing extra jumps while airborne.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct DoubleJump {
+    pub max_extra_jumps: u32,
+    /// Height of each extra jump, usually lower than the ground jump
+    pub height: f32,
+    remaining: u32,
+}
+
+impl Default for DoubleJump {
+    fn default() -> Self {
+        Self {
+            max_extra_jumps: 1,
+            height: 0.7,
+            remaining: 1,
+        }
+    }
+}
+
+/// Optional resource drained by sprinting. Sprint is blocked once it runs out.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+    /// Per second while sprinting
+    pub drain_rate: f32,
+    /// Per second while not sprinting
+    pub regen_rate: f32,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Self {
+            current: 1.0,
+            max: 1.0,
+            drain_rate: 0.25,
+            regen_rate: 0.2,
+        }
+    }
+}
+
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+pub struct PlayerMovingOnLadder {
     face_normal: Vec3,
     top: Vec3,
     bottom: Vec3,
 }
 
+/// Lining up with a ladder, from wherever it was grabbed at to `to` on its
+/// climbing path, before `PlayerMovingOnLadder`.
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+struct PlayerMountingLadder {
+    ladder: PlayerMovingOnLadder,
+    from: Option<(Vec3, Quat)>,
+    to: Vec3,
+    rotation: Quat,
+    elapsed: f32,
+}
+
 #[derive(Event, Clone)]
 struct LadderInteractionBeginEvent {
     entity: Entity,
     face_normal: Vec3,
     top: Vec3,
     bottom: Vec3,
+    /// Where on the climbing path the player starts
+    to: Vec3,
+    /// Facing the ladder
+    rotation: Quat,
 }
 
 #[derive(Event, Clone)]
 struct LadderInteractionEndEvent(Entity);
 
-const PLAYER_HEIGHT: f32 = 1.0;
-const PLAYER_WIDTH: f32 = 1.0;
+/// Hanging beneath monkey bars, moving along the line between `start` and `end`.
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+struct PlayerHangingTraversal {
+    start: Vec3,
+    end: Vec3,
+}
+
+#[derive(Event, Clone)]
+struct MonkeyBarsInteractionBeginEvent {
+    entity: Entity,
+    start: Vec3,
+    end: Vec3,
+}
+
+/// Holding on to a rope, parented to `segment` at `offset` along its length.
+#[derive(Component, Clone, Debug)]
+struct PlayerClimbingRope {
+    segment: Entity,
+    offset: f32,
+}
 
-#[derive(Component)]
-struct InteractionRayCaster;
+#[derive(Event, Clone)]
+struct RopeInteractionBeginEvent {
+    entity: Entity,
+    segment: Entity,
+    offset: f32,
+}
+
+/// Impulse per second applied to the held rope segment while swinging.
+const ROPE_SWING_IMPULSE: f32 = 2.0;
+
+/// A foot touching down in the run animation, on `ground` if there's any.
+#[derive(Event, Clone, Debug)]
+pub struct FootstepEvent {
+    pub player: Entity,
+    pub ground: Option<Entity>,
+    pub surface: SurfaceKind,
+}
+
+/// Quad showing the player, child of the player entity.
+#[derive(Component, Debug)]
+struct PlayerSprite {
+    facing_left: bool,
+    /// Run frame the last footstep was sent on
+    step_frame: Option<usize>,
+}
+
+/// Size of the player, which the collider, the float height, the sprite and
+/// the climbing offsets are all fitted to.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PlayerDimensions {
+    pub height: f32,
+    pub width: f32,
+    /// How much lower the top of the body is while crouching, at most the
+    /// length of the capsule's cylinder
+    pub crouch_drop: f32,
+}
+
+impl Default for PlayerDimensions {
+    fn default() -> Self {
+        Self {
+            height: 1.0,
+            width: 1.0,
+            crouch_drop: 0.25,
+        }
+    }
+}
+
+impl PlayerDimensions {
+    /// Of the capsule, narrower than the sprite
+    pub fn radius(&self) -> f32 {
+        self.width / 4.
+    }
+
+    pub fn collider(&self) -> Collider {
+        Collider::capsule(self.height / 4., self.radius())
+    }
+
+    /// Shorter by `crouch_drop`, lowered by half of it so the feet stay put
+    pub fn crouch_collider(&self) -> Collider {
+        Collider::capsule((self.height / 4. - self.crouch_drop).max(0.), self.radius())
+    }
+
+    pub fn crouch_float_offset(&self) -> f32 {
+        -self.crouch_drop / 2.
+    }
+
+    /// Where Tnua holds the center above the ground, with the bottom of the
+    /// sprite touching it
+    pub fn float_height(&self) -> f32 {
+        self.height / 2.
+    }
+
+    pub fn sprite_size(&self) -> Vec2 {
+        Vec2::new(self.width, self.height)
+    }
+}
+
+/// Looks for headroom above the player's head, whichever way is up.
+pub(crate) fn crouch_enforcer(dimensions: &PlayerDimensions, up: Vec3) -> TnuaCrouchEnforcer {
+    let radius = dimensions.radius();
+    TnuaCrouchEnforcer::new(dimensions.height / 2. * up, move |cmd| {
+        cmd.insert(TnuaXpbd3dSensorShape(Collider::cylinder(0., radius)));
+    })
+}
 
 fn add_player(
     mut commands: Commands,
-    player: Query<Entity, Added<Player>>,
-    images: Res<PlayerImages>,
+    player: Query<(Entity, Option<&PlayerSlot>), Added<Player>>,
+    animations: Res<PlayerAnimations>,
     mut meshes: ResMut<Assets<Mesh>>,
+    local_players: Res<LocalPlayers>,
+    dimensions: Res<PlayerDimensions>,
+    interaction: Res<InteractionConfig>,
 ) {
-    for entity in player.iter() {
+    for (entity, slot) in player.iter() {
         commands
             .entity(entity)
             .insert(Name::new("Player"))
-            .insert((
-                Collider::capsule(PLAYER_HEIGHT / 4., PLAYER_WIDTH / 4.),
-                RigidBody::Dynamic,
-            ))
+            .insert((dimensions.collider(), RigidBody::Dynamic))
             .insert(LockedAxes::new().lock_rotation_x().lock_rotation_z())
             .insert(TnuaControllerBundle::default())
-            .insert(player_state_machine(entity))
+            // Keeps the player crouched while there's no headroom to stand up
+            .insert(crouch_enforcer(&dimensions, Vec3::Y))
             .insert((
-                meshes.add(Mesh::from(shape::Quad::new(Vec2::new(
-                    PLAYER_WIDTH,
-                    PLAYER_HEIGHT,
-                )))),
-                images.0[0].clone(),
-                VisibilityBundle::default(),
+                CoyoteTime::default(),
+                JumpBuffer::default(),
+                WallContact::default(),
+                LedgeContact::default(),
+                WaterContact::default(),
+                LandingTracker::default(),
+                GroundSurface::default(),
+                DashCooldown::default(),
+                CrouchShape::default(),
+                TickPresses::default(),
             ))
+            .insert(player_state_machine(entity, *dimensions))
+            .insert(VisibilityBundle::default())
             .with_children(|builder| {
+                // Separate from the body, so billboarding doesn't turn the body around
+                let mesh = Mesh::from(shape::Quad::new(dimensions.sprite_size()));
+                builder.spawn((
+                    Name::new("PlayerSprite"),
+                    PlayerSprite {
+                        facing_left: false,
+                        step_frame: None,
+                    },
+                    SpriteAnimator::default(),
+                    PbrBundle {
+                        mesh: meshes.add(mesh),
+                        material: animations.material.clone(),
+                        ..default()
+                    },
+                ));
                 // RayCaster for interaction
                 builder.spawn((
                     Name::new("InteractionRayCaster"),
                     InteractionRayCaster,
+                    RaycastSource::default(),
                     RayCaster::new(Vec3::ZERO, -Vec3::Z)
-                        .with_max_time_of_impact(PLAYER_WIDTH * 0.8)
+                        .with_max_time_of_impact(interaction.range)
+                        .with_query_filter(
+                            SpatialQueryFilter::new().without_entities([builder.parent_entity()]),
+                        ),
+                    SpatialBundle::default(),
+                ));
+                // Looks for monkey bars overhead
+                builder.spawn((
+                    Name::new("InteractionRayCaster"),
+                    InteractionRayCaster,
+                    RayCaster::new(Vec3::ZERO, Vec3::Y)
+                        .with_max_time_of_impact(dimensions.height)
                         .with_query_filter(
                             SpatialQueryFilter::new().without_entities([builder.parent_entity()]),
                         ),
                     SpatialBundle::default(),
                 ));
             });
-        add_action_state(commands.entity(entity));
+        // The first split-screen player takes the keyboard, the others a gamepad each
+        let devices = match slot.map_or(0, |slot| slot.0) {
+            _ if local_players.0 <= 1 => InputDevices::All,
+            0 => InputDevices::KeyboardMouse,
+            slot => InputDevices::Gamepad(Gamepad::new(slot - 1)),
+        };
+        add_action_state(commands.entity(entity), devices);
+    }
+}
+
+/// `JustPressedTrigger` by fixed tick: pressed on a tick of this frame.
+struct TickJustPressed(Action);
+
+impl Trigger for TickJustPressed {
+    type Param<'w, 's> = (Query<'w, 's, &'static TickPresses>, Res<'w, FrameCount>);
+
+    type Ok = ();
+
+    type Err = ();
+
+    fn trigger(
+        &self,
+        entity: Entity,
+        (query, frame): <<Self as Trigger>::Param<'_, '_> as bevy::ecs::system::SystemParam>::Item<
+            '_,
+            '_,
+        >,
+    ) -> Result<Self::Ok, Self::Err> {
+        query
+            .get(entity)
+            .ok()
+            .filter(|presses| presses.frame == frame.0 && presses.just_pressed.contains(&self.0))
+            .map(|_| ())
+            .ok_or(())
+    }
+}
+
+/// `PressedTrigger` by fixed tick: held on the latest tick.
+struct TickPressed(Action);
+
+impl Trigger for TickPressed {
+    type Param<'w, 's> = Query<'w, 's, &'static TickPresses>;
+
+    type Ok = ();
+
+    type Err = ();
+
+    fn trigger(
+        &self,
+        entity: Entity,
+        query: <<Self as Trigger>::Param<'_, '_> as bevy::ecs::system::SystemParam>::Item<'_, '_>,
+    ) -> Result<Self::Ok, Self::Err> {
+        query
+            .get(entity)
+            .ok()
+            .filter(|presses| presses.pressed.contains(&self.0))
+            .map(|_| ())
+            .ok_or(())
     }
 }
 
@@ -160,363 +900,2924 @@ impl Trigger for IsJumping {
     }
 }
 
-fn player_state_machine(entity: Entity) -> impl Bundle {
-    let initial = PlayerGrounded;
-    let state_machine = StateMachine::default()
-        .trans::<PlayerGrounded>(JustPressedTrigger(Action::Jump), PlayerJumping)
-        .trans::<PlayerJumping>(
-            AndTrigger(IsJumping, PressedTrigger(Action::Jump)),
-            PlayerJumping,
-        )
-        .trans::<PlayerJumping>(
-            AndTrigger(IsJumping.not(), PressedTrigger(Action::Jump).not()),
-            PlayerGrounded,
-        )
-        .trans_builder::<PlayerGrounded, _, PlayerMovingOnLadder>(
-            EventTrigger::<LadderInteractionBeginEvent>::default(),
-            move |_prev, ev| {
-                if ev.entity != entity {
-                    return None;
-                }
-                Some(PlayerMovingOnLadder {
-                    face_normal: ev.face_normal,
-                    top: ev.top,
-                    bottom: ev.bottom,
-                })
-            },
-        )
-        .trans_builder::<PlayerMovingOnLadder, _, PlayerGrounded>(
-            EventTrigger::<LadderInteractionEndEvent>::default(),
-            move |_prev, ev| {
-                if ev.0 != entity {
-                    return None;
-                }
-                Some(PlayerGrounded)
-            },
-        )
-        .on_enter::<PlayerMovingOnLadder>(|entity| {
-            entity
-                .remove::<TnuaControllerBundle>()
-                .insert(RigidBody::Kinematic)
-                .insert((LinearVelocity::ZERO, AngularVelocity::ZERO));
-        })
-        .on_exit::<PlayerMovingOnLadder>(|entity| {
-            entity
-                .insert(TnuaControllerBundle::default())
-                .insert(RigidBody::Dynamic);
-        });
+/// Grounded, or still within the coyote window since leaving the ground.
+struct CanJump;
 
-    (initial, state_machine)
-}
+impl Trigger for CanJump {
+    type Param<'w, 's> = (
+        Query<'w, 's, (&'static TnuaController, &'static CoyoteTime)>,
+        Res<'w, Time<Fixed>>,
+    );
 
-// Player movement
+    type Ok = ();
 
-fn build_movement(app: &mut App) {
-    app.add_plugins((
-        TnuaXpbd3dPlugin,
-        TnuaControllerPlugin,
-        TnuaCrouchEnforcerPlugin,
-    ))
-    .add_plugins(InputManagerPlugin::<Action>::default())
-    .add_systems(
-        FixedUpdate,
-        (player_jumping, player_movement_walk, player_movement_ladder)
-            .in_set(TnuaUserControlsSystemSet),
-    )
-    .add_systems(Update, player_animation);
-}
-
-#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect)]
-enum Action {
-    Up,
-    Down,
-    Left,
-    Right,
-    Jump,
-    Interact,
-}
-
-fn add_action_state(mut entity: EntityCommands) {
-    entity.insert(InputManagerBundle::<Action> {
-        action_state: default(),
-        input_map: InputMap::new([
-            // WASD
-            (KeyCode::W, Action::Up),
-            (KeyCode::S, Action::Down),
-            (KeyCode::A, Action::Left),
-            (KeyCode::D, Action::Right),
-            // Cursor keys
-            (KeyCode::Up, Action::Up),
-            (KeyCode::Down, Action::Down),
-            (KeyCode::Left, Action::Left),
-            (KeyCode::Right, Action::Right),
-            // Space
-            (KeyCode::Space, Action::Jump),
-            // E
-            (KeyCode::E, Action::Interact),
-        ]),
-    });
+    type Err = ();
+
+    fn trigger(
+        &self,
+        entity: Entity,
+        (query, time): <<Self as Trigger>::Param<'_, '_> as bevy::ecs::system::SystemParam>::Item<
+            '_,
+            '_,
+        >,
+    ) -> Result<Self::Ok, Self::Err> {
+        let Ok((controller, coyote)) = query.get(entity) else {
+            return Err(());
+        };
+
+        if controller.is_airborne().is_ok_and(|airborne| !airborne) {
+            return Ok(());
+        }
+
+        coyote
+            .last_grounded
+            .filter(|&t| time.elapsed_seconds() - t < coyote.window)
+            .map(|_| ())
+            .ok_or(())
+    }
+}
+
+/// Grounded with a jump press still in the buffer.
+struct BufferedJump;
+
+impl Trigger for BufferedJump {
+    type Param<'w, 's> = (
+        Query<'w, 's, (&'static TnuaController, &'static JumpBuffer)>,
+        Res<'w, Time<Fixed>>,
+    );
+
+    type Ok = ();
+
+    type Err = ();
+
+    fn trigger(
+        &self,
+        entity: Entity,
+        (query, time): <<Self as Trigger>::Param<'_, '_> as bevy::ecs::system::SystemParam>::Item<
+            '_,
+            '_,
+        >,
+    ) -> Result<Self::Ok, Self::Err> {
+        let Ok((controller, buffer)) = query.get(entity) else {
+            return Err(());
+        };
+
+        if !controller.is_airborne().is_ok_and(|airborne| !airborne) {
+            return Err(());
+        }
+
+        buffer
+            .pressed_at
+            .filter(|&t| time.elapsed_seconds() - t < buffer.window)
+            .map(|_| ())
+            .ok_or(())
+    }
+}
+
+struct TouchingWall;
+
+impl Trigger for TouchingWall {
+    type Param<'w, 's> = Query<'w, 's, &'static WallContact>;
+
+    type Ok = Vec3;
+
+    type Err = ();
+
+    fn trigger(
+        &self,
+        entity: Entity,
+        query: <<Self as Trigger>::Param<'_, '_> as bevy::ecs::system::SystemParam>::Item<'_, '_>,
+    ) -> Result<Self::Ok, Self::Err> {
+        query
+            .get(entity)
+            .ok()
+            .and_then(|contact| contact.normal)
+            .ok_or(())
+    }
+}
+
+struct NearLedge;
+
+impl Trigger for NearLedge {
+    type Param<'w, 's> = Query<'w, 's, &'static LedgeContact>;
+
+    type Ok = Ledge;
+
+    type Err = ();
+
+    fn trigger(
+        &self,
+        entity: Entity,
+        query: <<Self as Trigger>::Param<'_, '_> as bevy::ecs::system::SystemParam>::Item<'_, '_>,
+    ) -> Result<Self::Ok, Self::Err> {
+        query
+            .get(entity)
+            .ok()
+            .and_then(|contact| contact.ledge)
+            .ok_or(())
+    }
+}
+
+struct CanAirJump;
+
+impl Trigger for CanAirJump {
+    type Param<'w, 's> = Query<'w, 's, &'static DoubleJump>;
+
+    type Ok = ();
+
+    type Err = ();
+
+    fn trigger(
+        &self,
+        entity: Entity,
+        query: <<Self as Trigger>::Param<'_, '_> as bevy::ecs::system::SystemParam>::Item<'_, '_>,
+    ) -> Result<Self::Ok, Self::Err> {
+        query
+            .get(entity)
+            .ok()
+            .filter(|double_jump| double_jump.remaining > 0)
+            .map(|_| ())
+            .ok_or(())
+    }
+}
+
+/// Off cooldown, and with the air dash left when airborne.
+struct CanDash;
+
+impl Trigger for CanDash {
+    type Param<'w, 's> = (
+        Query<'w, 's, (&'static TnuaController, &'static DashCooldown)>,
+        Res<'w, Time<Fixed>>,
+    );
+
+    type Ok = ();
+
+    type Err = ();
+
+    fn trigger(
+        &self,
+        entity: Entity,
+        (query, time): <<Self as Trigger>::Param<'_, '_> as bevy::ecs::system::SystemParam>::Item<
+            '_,
+            '_,
+        >,
+    ) -> Result<Self::Ok, Self::Err> {
+        let Ok((controller, cooldown)) = query.get(entity) else {
+            return Err(());
+        };
+
+        if time.elapsed_seconds() < cooldown.ready_at {
+            return Err(());
+        }
+        if controller.is_airborne().unwrap_or(true) && cooldown.air_dash_used {
+            return Err(());
+        }
+        Ok(())
+    }
+}
+
+struct IsAirborne;
+
+impl Trigger for IsAirborne {
+    type Param<'w, 's> = Query<'w, 's, &'static TnuaController>;
+
+    type Ok = ();
+
+    type Err = ();
+
+    fn trigger(
+        &self,
+        entity: Entity,
+        query: <<Self as Trigger>::Param<'_, '_> as bevy::ecs::system::SystemParam>::Item<'_, '_>,
+    ) -> Result<Self::Ok, Self::Err> {
+        query
+            .get(entity)
+            .ok()
+            .filter(|controller| controller.is_airborne().is_ok_and(|airborne| airborne))
+            .map(|_| ())
+            .ok_or(())
+    }
+}
+
+/// Standing on ground steeper than the slope limit.
+struct OnSteepSlope;
+
+impl Trigger for OnSteepSlope {
+    type Param<'w, 's> = (
+        Query<'w, 's, (&'static TnuaProximitySensor, Option<&'static PlayerUp>)>,
+        Res<'w, PlayerMovementConfig>,
+    );
+
+    type Ok = ();
+
+    type Err = ();
+
+    fn trigger(
+        &self,
+        entity: Entity,
+        (query, config): <<Self as Trigger>::Param<'_, '_> as bevy::ecs::system::SystemParam>::Item<
+            '_,
+            '_,
+        >,
+    ) -> Result<Self::Ok, Self::Err> {
+        let (sensor, up) = query.get(entity).map_err(|_| ())?;
+        // Against the player's up, so a flipped player walks the ceiling
+        let up = up.map_or(Vec3::Y, PlayerUp::up);
+        sensor
+            .output
+            .as_ref()
+            .filter(|ground| ground.normal.angle_between(up) > config.max_slope_angle)
+            .map(|_| ())
+            .ok_or(())
+    }
+}
+
+/// At least `min_depth` under the water surface.
+struct InWater {
+    min_depth: f32,
+}
+
+impl Trigger for InWater {
+    type Param<'w, 's> = Query<'w, 's, &'static WaterContact>;
+
+    type Ok = ();
+
+    type Err = ();
+
+    fn trigger(
+        &self,
+        entity: Entity,
+        query: <<Self as Trigger>::Param<'_, '_> as bevy::ecs::system::SystemParam>::Item<'_, '_>,
+    ) -> Result<Self::Ok, Self::Err> {
+        query
+            .get(entity)
+            .ok()
+            .and_then(|contact| contact.depth)
+            .filter(|&depth| depth >= self.min_depth)
+            .map(|_| ())
+            .ok_or(())
+    }
+}
+
+// Different depths to enter and leave, so bobbing at the surface doesn't
+// flicker. Swimming ends with the player half out of the water.
+const SWIM_ENTER_DEPTH: f32 = 0.2;
+
+/// Holding Down on a one-way platform turns Jump into dropping through it.
+struct CanDropThrough;
+
+impl Trigger for CanDropThrough {
+    type Param<'w, 's> = (
+        Query<'w, 's, (&'static TnuaProximitySensor, &'static TickPresses)>,
+        Query<'w, 's, (), With<OneWayPlatform>>,
+    );
+
+    type Ok = ();
+
+    type Err = ();
+
+    fn trigger(
+        &self,
+        entity: Entity,
+        (query, platforms): <<Self as Trigger>::Param<'_, '_> as bevy::ecs::system::SystemParam>::Item<
+            '_,
+            '_,
+        >,
+    ) -> Result<Self::Ok, Self::Err> {
+        let Ok((sensor, presses)) = query.get(entity) else {
+            return Err(());
+        };
+
+        let on_one_way = sensor
+            .output
+            .as_ref()
+            .is_some_and(|output| platforms.contains(output.entity));
+        if on_one_way && presses.pressed.contains(&Action::Down) {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+}
+
+fn player_state_machine(entity: Entity, dimensions: PlayerDimensions) -> impl Bundle {
+    let initial = PlayerGrounded;
+    // Down+Jump in the air, ahead of the air jump that'd take the press otherwise
+    let ground_pound = || {
+        AndTrigger(
+            IsAirborne,
+            AndTrigger(TickPressed(Action::Down), TickJustPressed(Action::Jump)),
+        )
+    };
+    let state_machine = StateMachine::default()
+        .trans::<PlayerGrounded>(ground_pound(), PlayerGroundPounding)
+        .trans::<PlayerJumping>(ground_pound(), PlayerGroundPounding)
+        .trans::<PlayerGrounded>(
+            AndTrigger(
+                TickJustPressed(Action::Jump),
+                AndTrigger(CanJump, CanDropThrough.not()),
+            ),
+            PlayerJumping::default(),
+        )
+        .trans::<PlayerGrounded>(
+            AndTrigger(
+                TickJustPressed(Action::Jump),
+                AndTrigger(CanAirJump, CanDropThrough.not()),
+            ),
+            PlayerJumping {
+                air_jump: true,
+                ..default()
+            },
+        )
+        .trans::<PlayerGrounded>(BufferedJump, PlayerJumping::default())
+        .trans_builder::<PlayerJumping, _, PlayerJumping>(
+            AndTrigger(IsJumping, TickPressed(Action::Jump)),
+            |prev, _| Some(prev.clone()),
+        )
+        .trans_builder::<PlayerGrounded, _, PlayerWallSliding>(TouchingWall, |_prev, normal| {
+            Some(PlayerWallSliding { normal })
+        })
+        .trans_builder::<PlayerJumping, _, PlayerWallSliding>(TouchingWall, |_prev, normal| {
+            Some(PlayerWallSliding { normal })
+        })
+        .trans_builder::<PlayerWallSliding, _, PlayerJumping>(
+            TickJustPressed(Action::Jump),
+            |prev, _| {
+                Some(PlayerJumping {
+                    wall_jump: Some(prev.normal),
+                    ..default()
+                })
+            },
+        )
+        .trans::<PlayerWallSliding>(TouchingWall.not(), PlayerGrounded)
+        .trans::<PlayerGrounded>(
+            AndTrigger(TickJustPressed(Action::Dash), CanDash),
+            PlayerDashing::default(),
+        )
+        .trans::<PlayerJumping>(
+            AndTrigger(TickJustPressed(Action::Dash), CanDash),
+            PlayerDashing::default(),
+        )
+        .trans::<PlayerDashing>(DoneTrigger::Success, PlayerGrounded)
+        .trans::<PlayerGroundPounding>(DoneTrigger::Success, PlayerGrounded)
+        .trans::<PlayerGrounded>(OnSteepSlope, PlayerSliding)
+        .trans::<PlayerSliding>(OnSteepSlope.not(), PlayerGrounded)
+        .trans_builder::<PlayerGrounded, _, PlayerLedgeHanging>(NearLedge, |_prev, ledge| {
+            Some(PlayerLedgeHanging(ledge))
+        })
+        .trans_builder::<PlayerJumping, _, PlayerLedgeHanging>(NearLedge, |_prev, ledge| {
+            Some(PlayerLedgeHanging(ledge))
+        })
+        .trans_builder::<PlayerWallSliding, _, PlayerLedgeHanging>(NearLedge, |_prev, ledge| {
+            Some(PlayerLedgeHanging(ledge))
+        })
+        .trans::<PlayerLedgeHanging>(
+            OrTrigger(TickJustPressed(Action::Down), TickJustPressed(Action::Jump)),
+            PlayerGrounded,
+        )
+        .trans_builder::<PlayerLedgeHanging, _, PlayerMantling>(
+            TickJustPressed(Action::Up),
+            move |prev, _| {
+                let ledge = prev.0;
+                Some(PlayerMantling {
+                    from: None,
+                    to: ledge.top + ledge.up * dimensions.float_height()
+                        - ledge.normal * dimensions.radius(),
+                    up: ledge.up,
+                    elapsed: 0.,
+                })
+            },
+        )
+        .trans::<PlayerMantling>(DoneTrigger::Success, PlayerGrounded)
+        .trans::<PlayerJumping>(
+            AndTrigger(IsJumping.not(), TickPressed(Action::Jump).not()),
+            PlayerGrounded,
+        )
+        .trans_builder::<PlayerGrounded, _, PlayerMountingLadder>(
+            EventTrigger::<LadderInteractionBeginEvent>::default(),
+            move |_prev, ev| {
+                if ev.entity != entity {
+                    return None;
+                }
+                Some(PlayerMountingLadder {
+                    ladder: PlayerMovingOnLadder {
+                        face_normal: ev.face_normal,
+                        top: ev.top,
+                        bottom: ev.bottom,
+                    },
+                    from: None,
+                    to: ev.to,
+                    rotation: ev.rotation,
+                    elapsed: 0.,
+                })
+            },
+        )
+        .trans_builder::<PlayerMountingLadder, _, PlayerMovingOnLadder>(
+            DoneTrigger::Success,
+            |prev, _| Some(prev.ladder.clone()),
+        )
+        .trans_builder::<PlayerMovingOnLadder, _, PlayerGrounded>(
+            EventTrigger::<LadderInteractionEndEvent>::default(),
+            move |_prev, ev| {
+                if ev.0 != entity {
+                    return None;
+                }
+                Some(PlayerGrounded)
+            },
+        )
+        .trans_builder::<PlayerGrounded, _, PlayerHangingTraversal>(
+            EventTrigger::<MonkeyBarsInteractionBeginEvent>::default(),
+            move |_prev, ev| {
+                if ev.entity != entity {
+                    return None;
+                }
+                Some(PlayerHangingTraversal {
+                    start: ev.start,
+                    end: ev.end,
+                })
+            },
+        )
+        .trans::<PlayerHangingTraversal>(TickJustPressed(Action::Jump), PlayerGrounded)
+        .trans_builder::<PlayerGrounded, _, PlayerClimbingRope>(
+            EventTrigger::<RopeInteractionBeginEvent>::default(),
+            move |_prev, ev| {
+                if ev.entity != entity {
+                    return None;
+                }
+                Some(PlayerClimbingRope {
+                    segment: ev.segment,
+                    offset: ev.offset,
+                })
+            },
+        )
+        .trans::<PlayerClimbingRope>(TickJustPressed(Action::Jump), PlayerGrounded)
+        // Falling into water cancels the jump
+        .trans::<PlayerGrounded>(
+            InWater {
+                min_depth: SWIM_ENTER_DEPTH,
+            },
+            PlayerSwimming,
+        )
+        .trans::<PlayerJumping>(
+            InWater {
+                min_depth: SWIM_ENTER_DEPTH,
+            },
+            PlayerSwimming,
+        )
+        .trans::<PlayerDashing>(
+            InWater {
+                min_depth: SWIM_ENTER_DEPTH,
+            },
+            PlayerSwimming,
+        )
+        .trans::<PlayerGroundPounding>(
+            InWater {
+                min_depth: SWIM_ENTER_DEPTH,
+            },
+            PlayerSwimming,
+        )
+        .trans::<PlayerSwimming>(
+            InWater {
+                min_depth: -dimensions.height / 2.,
+            }
+            .not(),
+            PlayerGrounded,
+        )
+        .on_enter::<PlayerMountingLadder>(enter_kinematic)
+        .on_exit::<PlayerMountingLadder>(exit_kinematic)
+        .on_enter::<PlayerMovingOnLadder>(enter_kinematic)
+        .on_exit::<PlayerMovingOnLadder>(exit_kinematic)
+        .on_enter::<PlayerLedgeHanging>(enter_kinematic)
+        .on_exit::<PlayerLedgeHanging>(exit_kinematic)
+        .on_enter::<PlayerMantling>(enter_kinematic)
+        .on_exit::<PlayerMantling>(exit_kinematic)
+        .on_enter::<PlayerHangingTraversal>(enter_kinematic)
+        .on_exit::<PlayerHangingTraversal>(exit_kinematic)
+        .on_enter::<PlayerClimbingRope>(enter_kinematic)
+        .on_exit::<PlayerClimbingRope>(|entity| {
+            entity.remove_parent_in_place();
+            exit_kinematic(entity);
+        })
+        // Water takes over from Tnua, but the body stays dynamic for buoyancy
+        .on_enter::<PlayerSwimming>(|entity| {
+            entity.remove::<TnuaControllerBundle>();
+        })
+        .on_exit::<PlayerSwimming>(|entity| {
+            entity.insert(TnuaControllerBundle::default());
+        });
+
+    (initial, state_machine)
+}
+
+/// Hands the player over from Tnua to manual translation.
+fn enter_kinematic(entity: &mut EntityCommands) {
+    entity
+        .remove::<TnuaControllerBundle>()
+        .insert(RigidBody::Kinematic)
+        .insert((LinearVelocity::ZERO, AngularVelocity::ZERO));
+}
+
+fn exit_kinematic(entity: &mut EntityCommands) {
+    entity
+        .insert(TnuaControllerBundle::default())
+        .insert(RigidBody::Dynamic);
+}
+
+// Player movement
+
+fn build_movement(app: &mut App) {
+    app.add_plugins((
+        TnuaXpbd3dPlugin,
+        TnuaControllerPlugin,
+        TnuaCrouchEnforcerPlugin,
+    ))
+    // These step by `Time<Fixed>` alone, so a replay of the same inputs tick
+    // by tick moves the same however the frames fall
+    .add_systems(
+        FixedUpdate,
+        (
+            player_jumping,
+            player_movement_walk,
+            player_crouching,
+            player_wall_sliding,
+            player_slope_sliding,
+            player_ledge_hanging,
+            player_mantling,
+            player_mounting_ladder,
+            player_dashing,
+            player_ground_pounding,
+            player_movement_ladder,
+            player_movement_monkey_bars,
+            player_movement_rope,
+            player_swimming,
+        )
+            .in_set(TnuaUserControlsSystemSet)
+            .run_if(in_state(GameState::Playing)),
+    )
+    .add_systems(
+        Update,
+        (
+            (player_animation, animate_sprites, emit_footsteps).chain(),
+            billboard_player_sprite,
+        )
+            .run_if(in_state(GameState::Playing)),
+    )
+    .add_systems(
+        PreUpdate,
+        collect_presses
+            .after(leafwing_input_manager::plugin::InputManagerSystem::Update)
+            .run_if(in_state(GameState::Playing)),
+    )
+    // The replay goes in between, see `ReplayPlugin`
+    .add_systems(
+        FixedUpdate,
+        (tick_input, latch_presses, buffer_jump_input)
+            .chain()
+            .before(TnuaUserControlsSystemSet)
+            .run_if(in_state(GameState::Playing)),
+    )
+    .add_systems(
+        Update,
+        (
+            detect_wall,
+            detect_ledge,
+            detect_water,
+            detect_surface,
+            detect_headroom,
+            (detect_landing, fall_damage).chain(),
+        )
+            .before(seldom_state::set::StateSet::Transition)
+            .run_if(in_state(GameState::Playing)),
+    );
+}
+
+// `PlayerJumping -> PlayerGrounded` also happens mid-air on releasing the
+// jump button, so landing is told from Tnua instead
+fn detect_landing(
+    mut player: Query<
+        (
+            Entity,
+            Option<&TnuaController>,
+            Option<&TnuaProximitySensor>,
+            &LinearVelocity,
+            &Position,
+            &mut LandingTracker,
+            Has<PlayerGroundPounding>,
+        ),
+        With<Player>,
+    >,
+    mut landed: EventWriter<PlayerLandedEvent>,
+    mut ground_pound: EventWriter<GroundPoundEvent>,
+) {
+    for (entity, controller, sensor, velocity, position, mut tracker, pounding) in player.iter_mut()
+    {
+        // Climbing, swimming and the like are neither
+        let Some(airborne) = controller.and_then(|c| c.is_airborne().ok()) else {
+            tracker.airborne = false;
+            continue;
+        };
+
+        if airborne {
+            // The contact zeroes the velocity on touching down, so the speed of
+            // the frame before is what counts
+            tracker.vertical_speed = velocity.y;
+        } else if tracker.airborne {
+            landed.send(PlayerLandedEvent {
+                entity,
+                impact_speed: (-tracker.vertical_speed).max(0.),
+            });
+            // The state only ends on the transition after this
+            if pounding {
+                ground_pound.send(GroundPoundEvent {
+                    entity,
+                    position: position.0,
+                    ground: sensor
+                        .and_then(|sensor| sensor.output.as_ref())
+                        .map(|output| output.entity),
+                });
+            }
+        }
+        tracker.airborne = airborne;
+    }
+}
+
+// The collider may sit on a mesh below the node carrying the material
+fn detect_surface(
+    mut player: Query<(&TnuaProximitySensor, &mut GroundSurface), With<Player>>,
+    surfaces: Query<&SurfaceMaterial>,
+    parents: Query<&Parent>,
+) {
+    for (sensor, mut surface) in player.iter_mut() {
+        surface.0 = sensor
+            .output
+            .as_ref()
+            .and_then(|output| {
+                std::iter::once(output.entity)
+                    .chain(parents.iter_ancestors(output.entity))
+                    .find_map(|entity| surfaces.get(entity).ok())
+            })
+            .copied()
+            .unwrap_or_default();
+    }
+}
+
+/// Whether the player has the crouched collider on, and room to stand up.
+#[derive(Component, Debug)]
+struct CrouchShape {
+    crouched: bool,
+    headroom: bool,
+}
+
+impl Default for CrouchShape {
+    fn default() -> Self {
+        Self {
+            crouched: false,
+            headroom: true,
+        }
+    }
+}
+
+// Shrinks the collider to fit under an overhang while crouching, and only
+// grows it back once a cast of the crouched body up by the difference is clear
+fn detect_headroom(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &Position,
+            &Rotation,
+            Option<&TnuaController>,
+            Option<&PlayerUp>,
+            &mut CrouchShape,
+        ),
+        With<Player>,
+    >,
+    sensors: Query<(), With<Sensor>>,
+    spatial_query: SpatialQuery,
+    dimensions: Res<PlayerDimensions>,
+) {
+    for (entity, position, rotation, controller, up, mut shape) in player.iter_mut() {
+        let crouching =
+            controller.is_some_and(|c| c.concrete_action::<TnuaBuiltinCrouch>().is_some());
+
+        shape.headroom = !shape.crouched
+            || spatial_query
+                .shape_hits(
+                    &dimensions.crouch_collider(),
+                    position.0,
+                    rotation.0,
+                    up.map_or(Vec3::Y, PlayerUp::up),
+                    dimensions.crouch_drop,
+                    4,
+                    true,
+                    SpatialQueryFilter::new().without_entities([entity]),
+                )
+                .iter()
+                .all(|hit| sensors.contains(hit.entity));
+
+        if crouching && !shape.crouched {
+            shape.crouched = true;
+            commands.entity(entity).insert(dimensions.crouch_collider());
+        } else if !crouching && shape.crouched && shape.headroom {
+            shape.crouched = false;
+            commands.entity(entity).insert(dimensions.collider());
+        }
+    }
+}
+
+// Water breaks the fall however fast the player comes down, and a ground
+// pound is meant to land hard
+fn fall_damage(
+    mut landed: EventReader<PlayerLandedEvent>,
+    player: Query<&WaterContact, (With<Player>, Without<PlayerGroundPounding>)>,
+    mut damage: EventWriter<DamageEvent>,
+    config: Res<PlayerMovementConfig>,
+) {
+    for event in landed.read() {
+        if player
+            .get(event.entity)
+            .map_or(true, |water| water.depth.is_some())
+        {
+            continue;
+        }
+        let excess = event.impact_speed - config.fall_damage_speed;
+        if excess > 0. && config.fall_damage_per_speed > 0. {
+            damage.send(DamageEvent {
+                target: event.entity,
+                amount: excess * config.fall_damage_per_speed,
+            });
+        }
+    }
+}
+
+fn detect_water(
+    sensors: Query<(&ZoneSensor, &Position, &CollidingEntities)>,
+    water: Query<&WaterVolume>,
+    mut player: Query<(Entity, &Position, &mut WaterContact), With<Player>>,
+) {
+    for (entity, position, mut contact) in player.iter_mut() {
+        contact.depth = sensors
+            .iter()
+            .filter(|(sensor, _, colliding)| {
+                colliding.contains(&entity) && water.get(sensor.zone).is_ok_and(|water| water.0)
+            })
+            .map(|(sensor, sensor_position, _)| {
+                sensor_position.y + sensor.half_extents.y - position.y
+            })
+            .reduce(f32::max);
+    }
+}
+
+fn player_jumping(
+    mut player: Query<
+        (
+            Entity,
+            Ref<PlayerJumping>,
+            &ActionState<Action>,
+            &mut TnuaController,
+            &mut CoyoteTime,
+            &mut JumpBuffer,
+            Option<&mut DoubleJump>,
+            &mut LinearVelocity,
+        ),
+        (With<Player>, With<PlayerJumping>),
+    >,
+    config: Res<PlayerMovementConfig>,
+    mut jumped: EventWriter<PlayerJumpedEvent>,
+) {
+    for (
+        entity,
+        jumping_state,
+        input,
+        mut controller,
+        mut coyote,
+        mut buffer,
+        mut double_jump,
+        mut velocity,
+    ) in player.iter_mut()
+    {
+        if jumping_state.is_added() {
+            // Consume the grace window so it can't be reused mid-air
+            coyote.last_grounded = None;
+            // Any jump consumes the buffered press, otherwise it'd replay on landing
+            buffer.pressed_at = None;
+        }
+
+        // Re-entering the state while the jump is still ongoing is not a new jump
+        let new_jump =
+            jumping_state.is_added() && controller.concrete_action::<TnuaBuiltinJump>().is_none();
+        if new_jump {
+            jumped.send(PlayerJumpedEvent {
+                entity,
+                air_jump: jumping_state.air_jump,
+            });
+        }
+
+        let height = if jumping_state.air_jump {
+            let Some(double_jump) = double_jump.as_mut() else {
+                continue;
+            };
+            if new_jump {
+                double_jump.remaining = double_jump.remaining.saturating_sub(1);
+            }
+            double_jump.height
+        } else {
+            config.jump_height
+        };
+
+        if let Some(normal) = jumping_state.wall_jump {
+            if new_jump {
+                velocity.0 += normal * config.wall_jump_detach_speed;
+            }
+        }
+
+        if jumping_state.is_added() || input.pressed(Action::Jump) {
+            controller.action(TnuaBuiltinJump {
+                height,
+                allow_in_air: jumping_state.air_jump || jumping_state.wall_jump.is_some(),
+                ..default()
+            });
+        }
+    }
+}
+
+fn detect_wall(
+    mut player: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &LinearVelocity,
+            Option<&TnuaController>,
+            &mut WallContact,
+        ),
+        With<Player>,
+    >,
+    spatial_query: SpatialQuery,
+    dimensions: Res<PlayerDimensions>,
+) {
+    const WALL_DETECTION_MARGIN: f32 = 0.1;
+
+    for (entity, gtransform, velocity, controller, mut contact) in player.iter_mut() {
+        let falling = velocity.y <= 0.
+            && controller.is_some_and(|c| c.is_airborne().is_ok_and(|airborne| airborne));
+        // Only walls the player is steering into count
+        let direction = controller
+            .and_then(|c| c.concrete_basis::<TnuaBuiltinWalk>())
+            .and_then(|(walk, _)| walk.desired_velocity.try_normalize());
+
+        contact.normal = direction.filter(|_| falling).and_then(|direction| {
+            spatial_query
+                .cast_ray(
+                    gtransform.translation(),
+                    direction,
+                    dimensions.radius() + WALL_DETECTION_MARGIN,
+                    true,
+                    SpatialQueryFilter::new().without_entities([entity]),
+                )
+                .map(|hit| hit.normal)
+                .filter(|normal| normal.y.abs() < 0.3)
+        });
+    }
+}
+
+fn detect_ledge(
+    mut player: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &LinearVelocity,
+            Option<&TnuaController>,
+            Option<&PlayerUp>,
+            &mut LedgeContact,
+        ),
+        With<Player>,
+    >,
+    spatial_query: SpatialQuery,
+    dimensions: Res<PlayerDimensions>,
+) {
+    for (entity, gtransform, velocity, controller, up, mut contact) in player.iter_mut() {
+        let up = up.map_or(Vec3::Y, PlayerUp::up);
+        let airborne = controller.is_some_and(|c| c.is_airborne().is_ok_and(|airborne| airborne));
+        if controller.is_some() && !airborne {
+            contact.last_grabbed = None;
+        }
+
+        let direction = controller
+            .and_then(|c| c.concrete_basis::<TnuaBuiltinWalk>())
+            .and_then(|(walk, _)| walk.desired_velocity.try_normalize());
+
+        let last_grabbed = contact.last_grabbed;
+        contact.ledge = direction
+            .filter(|_| airborne && velocity.dot(up) <= 0.)
+            .and_then(|direction| {
+                find_ledge(
+                    &spatial_query,
+                    &dimensions,
+                    entity,
+                    gtransform.translation(),
+                    direction,
+                    up,
+                )
+            })
+            .filter(|ledge| Some(ledge.entity) != last_grabbed);
+    }
+}
+
+fn find_ledge(
+    spatial_query: &SpatialQuery,
+    dimensions: &PlayerDimensions,
+    player: Entity,
+    origin: Vec3,
+    direction: Vec3,
+    up: Vec3,
+) -> Option<Ledge> {
+    const LEDGE_REACH: f32 = 0.2;
+    const LEDGE_GRAB_HEIGHT: f32 = 0.3;
+    const LEDGE_SURFACE_DEPTH: f32 = 0.05;
+
+    let filter = SpatialQueryFilter::new().without_entities([player]);
+    let reach = dimensions.radius() + LEDGE_REACH;
+
+    // Hands at the top of the capsule must be touching a wall...
+    let hands = origin + up * dimensions.height / 4.;
+    let wall = spatial_query.cast_ray(hands, direction, reach, true, filter.clone())?;
+    if wall.normal.dot(up).abs() >= 0.3 {
+        return None;
+    }
+
+    // ...which ends right above them
+    let above = hands + up * LEDGE_GRAB_HEIGHT;
+    if spatial_query
+        .cast_ray(above, direction, reach, true, filter.clone())
+        .is_some()
+    {
+        return None;
+    }
+
+    let probe = above + direction * (wall.time_of_impact + LEDGE_SURFACE_DEPTH);
+    let top = spatial_query.cast_ray(probe, -up, LEDGE_GRAB_HEIGHT, true, filter)?;
+    if top.normal.dot(up) < 0.7 {
+        return None;
+    }
+
+    Some(Ledge {
+        entity: top.entity,
+        top: probe - up * top.time_of_impact,
+        normal: wall.normal,
+        up,
+    })
+}
+
+fn player_ledge_hanging(
+    mut player: Query<
+        (&PlayerLedgeHanging, &mut Transform, &mut LedgeContact),
+        (With<Player>, Added<PlayerLedgeHanging>),
+    >,
+    dimensions: Res<PlayerDimensions>,
+) {
+    for (hanging, mut transform, mut contact) in player.iter_mut() {
+        let ledge = hanging.0;
+        // Hang with the hands at the ledge, just off the wall
+        transform.translation =
+            ledge.top + ledge.normal * dimensions.radius() - ledge.up * dimensions.height / 4.;
+        contact.last_grabbed = Some(ledge.entity);
+    }
+}
+
+fn player_mantling(
+    mut commands: Commands,
+    mut player: Query<(Entity, &mut PlayerMantling, &mut Transform), With<Player>>,
+    time: Res<Time<Fixed>>,
+    config: Res<PlayerMovementConfig>,
+) {
+    for (entity, mut mantling, mut transform) in player.iter_mut() {
+        let from = *mantling.from.get_or_insert(transform.translation);
+        mantling.elapsed += time.delta_seconds();
+        let t = (mantling.elapsed / config.mantle_duration).min(1.);
+
+        // Pull up first, then step forward onto the ledge
+        let up = from + mantling.up * mantling.up.dot(mantling.to - from);
+        transform.translation = if t < 0.5 {
+            from.lerp(up, t * 2.)
+        } else {
+            up.lerp(mantling.to, t * 2. - 1.)
+        };
+
+        if t >= 1. {
+            commands.entity(entity).insert(Done::Success);
+        }
+    }
+}
+
+// Climbing control waits for the player to be lined up with the ladder
+fn player_mounting_ladder(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &mut PlayerMountingLadder,
+            &mut Transform,
+            &mut LinearVelocity,
+        ),
+        With<Player>,
+    >,
+    time: Res<Time<Fixed>>,
+    config: Res<PlayerMovementConfig>,
+) {
+    for (entity, mut mounting, mut transform, mut velocity) in player.iter_mut() {
+        let (from, from_rotation) = *mounting
+            .from
+            .get_or_insert((transform.translation, transform.rotation));
+        mounting.elapsed += time.delta_seconds();
+        let t = (mounting.elapsed / config.ladder_mount_duration).min(1.);
+
+        transform.translation = from.lerp(mounting.to, t);
+        transform.rotation = from_rotation.slerp(mounting.rotation, t);
+        velocity.0 = Vec3::ZERO;
+
+        if t >= 1. {
+            commands.entity(entity).insert(Done::Success);
+        }
+    }
+}
+
+fn player_dashing(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &ActionState<Action>,
+            &mut PlayerDashing,
+            &mut DashCooldown,
+            &mut TnuaController,
+            &mut LinearVelocity,
+            &Rotation,
+            Option<&PlayerUp>,
+            Option<&mut Invulnerability>,
+        ),
+        With<Player>,
+    >,
+    cameras: Query<(&CameraTarget, &GlobalTransform)>,
+    time: Res<Time<Fixed>>,
+    config: Res<PlayerMovementConfig>,
+    dimensions: Res<PlayerDimensions>,
+) {
+    for (
+        entity,
+        input,
+        mut dashing,
+        mut cooldown,
+        mut controller,
+        mut velocity,
+        rotation,
+        up,
+        invulnerability,
+    ) in player.iter_mut()
+    {
+        let up = up.map_or(Vec3::Y, PlayerUp::up);
+        let airborne = controller.is_airborne().unwrap_or(true);
+
+        let direction = match dashing.direction {
+            Some(direction) => direction,
+            None => {
+                let forward = player_camera(&cameras, entity)
+                    .and_then(|camera| (camera.forward() * Vec3::new(1., 0., 1.)).try_normalize())
+                    .unwrap_or(Vec3::NEG_Z);
+                let right = forward.cross(up);
+                let axis = input
+                    .clamped_axis_pair(Action::Move)
+                    .map_or(Vec2::ZERO, |axis| axis.xy());
+                // Standing still dashes the way the player faces
+                let direction = (right * axis.x + forward * axis.y)
+                    .try_normalize()
+                    .or_else(|| (rotation.0 * Vec3::NEG_Z * Vec3::new(1., 0., 1.)).try_normalize())
+                    .unwrap_or(forward);
+
+                cooldown.ready_at = time.elapsed_seconds() + config.dash_cooldown;
+                if airborne {
+                    cooldown.air_dash_used = true;
+                }
+                if let Some(mut invulnerability) = invulnerability {
+                    invulnerability.grant(config.dash_invulnerability);
+                }
+                dashing.direction = Some(direction);
+                direction
+            }
+        };
+
+        // Set outright rather than through the walk acceleration, so the
+        // burst is at full speed from the first frame
+        let dash_velocity = direction * config.dash_distance / config.dash_duration;
+        velocity.x = dash_velocity.x;
+        velocity.z = dash_velocity.z;
+        // Straight across in the air instead of falling
+        if airborne {
+            velocity.y = 0.;
+        }
+        controller.basis(TnuaBuiltinWalk {
+            desired_velocity: dash_velocity,
+            desired_forward: direction,
+            float_height: dimensions.float_height(),
+            up,
+            air_acceleration: TnuaBuiltinWalk::default().acceleration,
+            ..default()
+        });
+
+        dashing.elapsed += time.delta_seconds();
+        if dashing.elapsed >= config.dash_duration {
+            commands.entity(entity).insert(Done::Success);
+        }
+    }
+}
+
+fn player_ground_pounding(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &mut TnuaController,
+            &mut LinearVelocity,
+            Option<&PlayerUp>,
+        ),
+        // Ended already, e.g. by bouncing off an enemy, awaiting the transition
+        (With<Player>, With<PlayerGroundPounding>, Without<Done>),
+    >,
+    config: Res<PlayerMovementConfig>,
+    dimensions: Res<PlayerDimensions>,
+) {
+    for (entity, mut controller, mut velocity, up) in player.iter_mut() {
+        if controller.is_airborne().is_ok_and(|airborne| !airborne) {
+            commands.entity(entity).insert(Done::Success);
+            continue;
+        }
+
+        let up = up.map_or(Vec3::Y, PlayerUp::up);
+        // No drifting sideways, so it comes down where it was aimed
+        velocity.0 = -up * config.ground_pound_speed;
+        controller.basis(TnuaBuiltinWalk {
+            desired_velocity: Vec3::ZERO,
+            float_height: dimensions.float_height(),
+            up,
+            air_acceleration: 0.,
+            ..default()
+        });
+    }
+}
+
+fn player_wall_sliding(
+    mut player: Query<&mut LinearVelocity, (With<Player>, With<PlayerWallSliding>)>,
+    config: Res<PlayerMovementConfig>,
+) {
+    for mut velocity in player.iter_mut() {
+        velocity.y = velocity.y.max(-config.wall_slide_speed);
+    }
+}
+
+/// Free 3D movement while submerged. Jump and Crouch swim up and down,
+/// since Up/Down already steer horizontally through `Action::Move`.
+fn player_swimming(
+    mut player: Query<
+        (
+            Entity,
+            &ActionState<Action>,
+            &WaterContact,
+            &mut LinearVelocity,
+            &mut Rotation,
+        ),
+        (With<Player>, With<PlayerSwimming>),
+    >,
+    cameras: Query<(&CameraTarget, &GlobalTransform)>,
+    gravity: Res<Gravity>,
+    time: Res<Time<Fixed>>,
+    config: Res<PlayerMovementConfig>,
+) {
+    for (entity, input, contact, mut velocity, mut rotation) in player.iter_mut() {
+        let forward = player_camera(&cameras, entity)
+            .and_then(|camera| (camera.forward() * Vec3::new(1., 0., 1.)).try_normalize())
+            .unwrap_or(Vec3::NEG_Z);
+        let right = forward.cross(Vec3::Y);
+
+        let depth = contact.depth.unwrap_or(0.);
+
+        // Leap out at the surface, e.g. onto the edge of the pool
+        if input.just_pressed(Action::Jump) && depth < SWIM_ENTER_DEPTH {
+            velocity.y = (2. * gravity.0.length() * config.swim_leap_height).sqrt();
+            continue;
+        }
+
+        let direction = input
+            .clamped_axis_pair(Action::Move)
+            .map_or(Vec2::ZERO, |axis| axis.xy())
+            .clamp_length_max(1.);
+        let mut vertical = 0.;
+        if input.pressed(Action::Jump) {
+            vertical += 1.;
+        }
+        if input.pressed(Action::Crouch) {
+            vertical -= 1.;
+        }
+        let desired = (right * direction.x + forward * direction.y + Vec3::Y * vertical)
+            .clamp_length_max(1.)
+            * config.swim_speed;
+
+        let dt = time.delta_seconds();
+        if depth > 0. {
+            velocity.0 -= gravity.0 * config.swim_buoyancy * dt;
+        }
+        let drag = (config.swim_drag * dt).min(1.);
+        velocity.0 += (desired - velocity.0) * drag;
+
+        let heading = desired * Vec3::new(1., 0., 1.);
+        if heading != Vec3::ZERO {
+            rotation.0 = Quat::from_rotation_y(f32::atan2(-heading.x, -heading.z));
+        }
+    }
+}
+
+/// Replaces walking with a slide down the slope while it's too steep to stand on.
+fn player_slope_sliding(
+    mut player: Query<
+        (&mut TnuaController, &TnuaProximitySensor, Option<&PlayerUp>),
+        (With<Player>, With<PlayerSliding>),
+    >,
+    config: Res<PlayerMovementConfig>,
+    dimensions: Res<PlayerDimensions>,
+) {
+    for (mut controller, sensor, up) in player.iter_mut() {
+        let Some(ground) = sensor.output.as_ref() else {
+            continue;
+        };
+
+        let up = up.map_or(Vec3::Y, PlayerUp::up);
+        let downhill = (-up).reject_from(ground.normal).normalize_or_zero();
+        controller.basis(TnuaBuiltinWalk {
+            desired_velocity: downhill * config.slope_slide_speed,
+            desired_forward: (downhill * Vec3::new(1., 0., 1.)).normalize_or_zero(),
+            float_height: dimensions.float_height(),
+            up,
+            ..default()
+        });
+    }
+}
+
+// Every frame, whether a tick follows or not
+fn collect_presses(
+    mut player: Query<(&ActionState<Action>, &mut TickPresses)>,
+    actions: Res<ToggleActions<Action>>,
+) {
+    // A replay puts in the states of each tick itself
+    if !actions.enabled {
+        return;
+    }
+    for (input, mut presses) in player.iter_mut() {
+        for action in input.get_just_pressed() {
+            if !presses.pending.contains(&action) {
+                presses.pending.push(action);
+            }
+        }
+    }
+}
+
+/// Starts the input of a fixed tick: a press is just pressed on one tick
+/// only, the next one if it came in a frame without a tick.
+pub fn tick_input(
+    mut player: Query<(&mut ActionState<Action>, &mut TickPresses)>,
+    frame: Res<FrameCount>,
+) {
+    let now = Instant::now();
+    for (mut input, mut presses) in player.iter_mut() {
+        if presses.frame == frame.0 {
+            input.tick(now, now);
+        }
+        for action in std::mem::take(&mut presses.pending) {
+            // From released, in case it's still held by now
+            input.release(action);
+            input.press(action);
+        }
+    }
+}
+
+/// Notes the presses the tick goes by, once a replay has put in its states.
+pub fn latch_presses(
+    mut player: Query<(&ActionState<Action>, &mut TickPresses)>,
+    frame: Res<FrameCount>,
+) {
+    for (input, mut presses) in player.iter_mut() {
+        if presses.frame != frame.0 {
+            presses.frame = frame.0;
+            presses.just_pressed.clear();
+        }
+        for action in input.get_just_pressed() {
+            if !presses.just_pressed.contains(&action) {
+                presses.just_pressed.push(action);
+            }
+        }
+        presses.pressed = input.get_pressed();
+    }
+}
+
+fn buffer_jump_input(
+    mut player: Query<
+        (&ActionState<Action>, &mut JumpBuffer),
+        (With<Player>, Without<PlayerMovingOnLadder>),
+    >,
+    time: Res<Time<Fixed>>,
+) {
+    for (input, mut buffer) in player.iter_mut() {
+        if input.just_pressed(Action::Jump) {
+            buffer.pressed_at = Some(time.elapsed_seconds());
+        } else if buffer
+            .pressed_at
+            .is_some_and(|t| time.elapsed_seconds() - t >= buffer.window)
+        {
+            buffer.pressed_at = None;
+        }
+    }
+}
+
+fn insert_or_modify<T: Component>(
+    commands: &mut Commands,
+    entity: Entity,
+    component: &mut Option<Mut<T>>,
+    insert: impl Fn() -> T,
+    modify: impl FnOnce(&mut T),
+) {
+    if let Some(mut c) = component.as_mut() {
+        modify(&mut c);
+    } else {
+        let mut c = insert();
+        modify(&mut c);
+        commands.entity(entity).insert(c);
+    }
+}
+
+fn player_movement_walk(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &ActionState<Action>,
+            Option<&mut TnuaController>,
+            &mut CoyoteTime,
+            Option<&mut DoubleJump>,
+            Option<&mut Stamina>,
+            Has<PlayerJumping>,
+            Option<&PlayerUp>,
+            &GroundSurface,
+            &mut DashCooldown,
+        ),
+        (
+            With<Player>,
+            Or<(
+                With<PlayerGrounded>,
+                With<PlayerJumping>,
+                With<PlayerWallSliding>,
+            )>,
+        ),
+    >,
+    cameras: Query<(&CameraTarget, &GlobalTransform)>,
+    time: Res<Time<Fixed>>,
+    config: Res<PlayerMovementConfig>,
+    dimensions: Res<PlayerDimensions>,
+    mut last_camera_forward: Local<HashMap<Entity, Vec3>>,
+) {
+    for (
+        entity,
+        input,
+        mut controller,
+        mut coyote,
+        double_jump,
+        mut stamina,
+        jumping,
+        up,
+        surface,
+        mut dash_cooldown,
+    ) in player.iter_mut()
+    {
+        let up = up.map_or(Vec3::Y, PlayerUp::up);
+        // Yaw of the player's camera projected onto the ground. Keeps the last
+        // valid direction when the camera looks straight down.
+        if let Some(forward) = player_camera(&cameras, entity)
+            .map(|camera| camera.forward() * Vec3::new(1., 0., 1.))
+            .and_then(|forward| forward.try_normalize())
+        {
+            last_camera_forward.insert(entity, forward);
+        }
+        let forward = last_camera_forward
+            .get(&entity)
+            .copied()
+            .unwrap_or(Vec3::NEG_Z);
+        // Mirrored on the ceiling, where the camera is upside down as well
+        let right = forward.cross(up);
+
+        let grounded = controller
+            .as_deref()
+            .is_some_and(|c| c.is_airborne().is_ok_and(|airborne| !airborne));
+        if grounded && !jumping {
+            coyote.last_grounded = Some(time.elapsed_seconds());
+            // Air jumps are refilled on landing, not on entering `PlayerGrounded`
+            // which also happens mid-air once the jump button is released
+            if let Some(mut double_jump) = double_jump {
+                double_jump.remaining = double_jump.max_extra_jumps;
+            }
+            dash_cooldown.air_dash_used = false;
+        }
+
+        // Keys produce full-magnitude input, the stick anything in between
+        let direction = input
+            .clamped_axis_pair(Action::Move)
+            .map_or(Vec2::ZERO, |axis| axis.xy())
+            .clamp_length_max(1.);
+
+        let sprinting = grounded
+            && !jumping
+            && input.pressed(Action::Sprint)
+            && direction != Vec2::ZERO
+            && stamina.as_ref().is_none_or(|stamina| stamina.current > 0.);
+        if let Some(stamina) = stamina.as_mut() {
+            stamina.current = if sprinting {
+                (stamina.current - stamina.drain_rate * time.delta_seconds()).max(0.)
+            } else {
+                (stamina.current + stamina.regen_rate * time.delta_seconds()).min(stamina.max)
+            };
+        }
+
+        let speed = if sprinting {
+            config.walk_speed * config.sprint_factor
+        } else {
+            config.walk_speed
+        };
+        let movement =
+            ((right * direction.x + forward * direction.y) * speed).clamp_length_max(speed);
+
+        insert_or_modify(
+            &mut commands,
+            entity,
+            &mut controller,
+            || TnuaController::default(),
+            |c| {
+                c.basis(TnuaBuiltinWalk {
+                    desired_velocity: movement,
+                    desired_forward: movement.normalize_or_zero(),
+                    float_height: dimensions.float_height(),
+                    up,
+                    coyote_time: coyote.window,
+                    // Slippery ice takes long to speed up and to stop, sticky mud hardly at all
+                    acceleration: TnuaBuiltinWalk::default().acceleration * surface.0.friction,
+                    air_acceleration: TnuaBuiltinWalk::default().acceleration * config.air_control,
+                    ..default()
+                });
+            },
+        );
+    }
+}
+
+fn player_crouching(
+    mut player: Query<
+        (
+            &ActionState<Action>,
+            &mut TnuaController,
+            &mut TnuaCrouchEnforcer,
+            &CrouchShape,
+        ),
+        (With<Player>, With<PlayerGrounded>),
+    >,
+    dimensions: Res<PlayerDimensions>,
+) {
+    for (input, mut controller, mut crouch_enforcer, shape) in player.iter_mut() {
+        // The enforcer only sees straight up from the middle, standing up
+        // under the edge of an overhang is caught by the headroom cast
+        if input.pressed(Action::Crouch) || !shape.headroom {
+            controller.action(crouch_enforcer.enforcing(TnuaBuiltinCrouch {
+                float_offset: dimensions.crouch_float_offset(),
+                ..default()
+            }));
+        }
+    }
+}
+
+fn player_movement_ladder(
+    mut player: Query<
+        (
+            Entity,
+            &ActionState<Action>,
+            &PlayerMovingOnLadder,
+            &mut Transform,
+        ),
+        With<Player>,
+    >,
+    time: Res<Time<Fixed>>,
+    mut ladder_end: EventWriter<LadderInteractionEndEvent>,
+    config: Res<PlayerMovementConfig>,
+    dimensions: Res<PlayerDimensions>,
+) {
+    for (entity, input, ladder, mut transform) in player.iter_mut() {
+        // let frac = (transform.translation.y - ladder.ladder_bottom.y)
+        //     / (ladder.ladder_top.y - ladder.ladder_bottom.y);
+
+        // Inclined ladders are climbed along their axis rather than vertically
+        let axis = (ladder.top - ladder.bottom).normalize_or_zero();
+        let height = (ladder.top - ladder.bottom).length();
+        let cur_pos = (transform.translation - ladder.bottom).dot(axis);
+
+        if input.pressed(Action::Up) {
+            if cur_pos > height + dimensions.height / 2. {
+                ladder_end.send(LadderInteractionEndEvent(entity));
+                transform.translation -= ladder.face_normal * dimensions.width * 0.8;
+            } else {
+                transform.translation += config.ladder_speed * axis * time.delta_seconds();
+            }
+        }
+        if input.pressed(Action::Down) {
+            if cur_pos < 0.1 {
+                ladder_end.send(LadderInteractionEndEvent(entity));
+            } else {
+                transform.translation -= config.ladder_speed * axis * time.delta_seconds();
+            }
+        }
+    }
+}
+
+fn player_movement_monkey_bars(
+    mut player: Query<
+        (
+            Entity,
+            &ActionState<Action>,
+            &PlayerHangingTraversal,
+            &mut Transform,
+        ),
+        With<Player>,
+    >,
+    cameras: Query<(&CameraTarget, &GlobalTransform)>,
+    time: Res<Time<Fixed>>,
+    config: Res<PlayerMovementConfig>,
+) {
+    for (entity, input, bars, mut transform) in player.iter_mut() {
+        let camera_right = player_camera(&cameras, entity).map_or(Vec3::X, |camera| camera.right());
+        let length = (bars.end - bars.start).length();
+        let mut axis = (bars.end - bars.start).normalize_or_zero();
+        // Right input moves towards the right of the screen
+        let mut origin = bars.start;
+        if axis.dot(camera_right) < 0. {
+            axis = -axis;
+            origin = bars.end;
+        }
+
+        let mut direction = 0.;
+        if input.pressed(Action::Right) {
+            direction += 1.;
+        }
+        if input.pressed(Action::Left) {
+            direction -= 1.;
+        }
+
+        let cur_pos = (transform.translation - origin).dot(axis);
+        let new_pos =
+            (cur_pos + direction * config.ladder_speed * time.delta_seconds()).clamp(0., length);
+        transform.translation = origin + axis * new_pos;
+    }
+}
+
+fn player_movement_rope(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &ActionState<Action>,
+            &mut PlayerClimbingRope,
+            &mut Transform,
+            Option<&Parent>,
+        ),
+        With<Player>,
+    >,
+    mut segments: Query<(&RopeSegment, &GlobalTransform, &mut ExternalImpulse)>,
+    cameras: Query<(&CameraTarget, &GlobalTransform)>,
+    time: Res<Time<Fixed>>,
+    config: Res<PlayerMovementConfig>,
+) {
+    for (entity, input, mut rope, mut transform, parent) in player.iter_mut() {
+        let camera_right = player_camera(&cameras, entity).map_or(Vec3::X, |camera| camera.right());
+        let Ok((segment, segment_gtransform, mut impulse)) = segments.get_mut(rope.segment) else {
+            continue;
+        };
+
+        let mut offset = rope.offset;
+        if input.pressed(Action::Up) {
+            offset += config.ladder_speed * time.delta_seconds();
+        }
+        if input.pressed(Action::Down) {
+            offset -= config.ladder_speed * time.delta_seconds();
+        }
+
+        // Hand over to the neighbouring segment, stopping at either end of the rope
+        if offset > segment.half_length {
+            if let Some(above) = segment.above {
+                rope.segment = above;
+                offset -= segment.half_length * 2.;
+            } else {
+                offset = segment.half_length;
+            }
+        } else if offset < -segment.half_length {
+            if let Some(below) = segment.below {
+                rope.segment = below;
+                offset += segment.half_length * 2.;
+            } else {
+                offset = -segment.half_length;
+            }
+        }
+        rope.offset = offset;
+
+        if parent.map(|parent| parent.get()) != Some(rope.segment) {
+            commands.entity(entity).set_parent(rope.segment);
+        }
+
+        // Left/Right pumps the rope to swing it
+        let mut swing = 0.;
+        if input.pressed(Action::Right) {
+            swing += 1.;
+        }
+        if input.pressed(Action::Left) {
+            swing -= 1.;
+        }
+        if swing != 0. {
+            impulse.apply_impulse(camera_right * swing * ROPE_SWING_IMPULSE * time.delta_seconds());
+        }
+
+        // Stay upright while the segment tilts
+        let (_, segment_rotation, _) = segment_gtransform.to_scale_rotation_translation();
+        transform.translation = Vec3::Y * offset;
+        transform.rotation = segment_rotation.inverse();
+    }
+}
+
+/// Each new frame of the run cycle is a step.
+fn emit_footsteps(
+    player: Query<(Option<&TnuaProximitySensor>, &GroundSurface), With<Player>>,
+    mut sprite: Query<(&Parent, &SpriteAnimator, &mut PlayerSprite)>,
+    mut footstep: EventWriter<FootstepEvent>,
+) {
+    for (parent, animator, mut sprite) in sprite.iter_mut() {
+        if animator.animation != "run" {
+            sprite.step_frame = None;
+            continue;
+        }
+        if animator.frame == sprite.step_frame {
+            continue;
+        }
+        sprite.step_frame = animator.frame;
+
+        let Ok((sensor, surface)) = player.get(parent.get()) else {
+            continue;
+        };
+        let ground = sensor
+            .and_then(|sensor| sensor.output.as_ref())
+            .map(|output| output.entity);
+        footstep.send(FootstepEvent {
+            player: parent.get(),
+            ground,
+            surface: surface.0.kind,
+        });
+    }
+}
+
+/// Sprite lean in radians per unit of wind acceleration.
+const WIND_LEAN_PER_FORCE: f32 = 0.02;
+const MAX_WIND_LEAN: f32 = 0.3;
+
+/// Turns the sprite to the camera, mirrored when the player moves to the left of the screen.
+fn billboard_player_sprite(
+    player: Query<
+        (
+            &GlobalTransform,
+            &LinearVelocity,
+            Option<&PlayerUp>,
+            Option<&WindExposure>,
+        ),
+        With<Player>,
+    >,
+    mut sprite: Query<(&Parent, &mut Transform, &mut PlayerSprite)>,
+    cameras: Query<(&CameraTarget, &GlobalTransform)>,
+) {
+    for (parent, mut transform, mut sprite) in sprite.iter_mut() {
+        let Ok((player_gtransform, velocity, up, wind)) = player.get(parent.get()) else {
+            continue;
+        };
+        // Split-screen shows the sprite to the other camera edge-on at worst
+        let Some(camera) = player_camera(&cameras, parent.get()) else {
+            continue;
+        };
+
+        // Keeps facing the last way the player went when not moving sideways
+        let sideways = velocity.dot(camera.right());
+        if sideways.abs() > 0.1 {
+            sprite.facing_left = sideways < 0.;
+        }
+
+        // Turned around the player's own up, upside down on the ceiling
+        let up_rotation = up.map_or(Quat::IDENTITY, |up| up.rotation);
+        let to_camera =
+            up_rotation.inverse() * (camera.translation() - player_gtransform.translation());
+        let facing = up_rotation * Quat::from_rotation_y(f32::atan2(to_camera.x, to_camera.z));
+        // Leans into wind blowing across the view, top towards where it comes from
+        let wind_across = wind.map_or(0., |wind| wind.force.dot(camera.right()));
+        let lean = (wind_across * WIND_LEAN_PER_FORCE).clamp(-MAX_WIND_LEAN, MAX_WIND_LEAN);
+        let facing = facing * Quat::from_rotation_z(lean);
+        // Undoes the body's own rotation, e.g. when facing a ladder
+        let (_, body_rotation, _) = player_gtransform.to_scale_rotation_translation();
+        transform.rotation = body_rotation.inverse() * facing;
+        transform.scale.x = if sprite.facing_left { -1. } else { 1. };
+    }
+}
+
+fn player_animation(
+    player: Query<
+        (
+            Option<&TnuaController>,
+            &LinearVelocity,
+            &ActionState<Action>,
+            Has<PlayerJumping>,
+            Has<PlayerMovingOnLadder>,
+            Has<PlayerSliding>,
+        ),
+        With<Player>,
+    >,
+    mut sprite: Query<(&Parent, &mut SpriteAnimator), With<PlayerSprite>>,
+    config: Res<PlayerMovementConfig>,
+) {
+    for (parent, mut animator) in sprite.iter_mut() {
+        let Ok((controller, velocity, input, jumping, on_ladder, sliding)) =
+            player.get(parent.get())
+        else {
+            continue;
+        };
+
+        // Picked by the state the player is in, falling back to the walk basis.
+        // `rate` scales how fast the animation plays.
+        let (name, rate) = if on_ladder {
+            // Only climbing moves the hands, hanging still on the ladder doesn't
+            let climbing = input.pressed(Action::Up) || input.pressed(Action::Down);
+            ("climb", if climbing { 1. } else { 0. })
+        } else if jumping {
+            (if velocity.y > 0. { "jump" } else { "fall" }, 1.)
+        } else if sliding
+            || controller.is_some_and(|c| c.concrete_action::<TnuaBuiltinCrouch>().is_some())
+        {
+            ("crouch", 1.)
+        } else {
+            let speed = controller
+                .and_then(|c| c.concrete_basis::<TnuaBuiltinWalk>())
+                .map_or(0., |(_, state)| state.running_velocity.length());
+            if speed == 0. {
+                ("idle", 1.)
+            } else {
+                // Feet keep up with the ground, faster when sprinting and
+                // slower with the stick half tilted
+                ("run", speed / config.walk_speed)
+            }
+        };
+
+        animator.play(name, rate);
+    }
+}
+
+/// Steps every `SpriteAnimator`, after whatever picks their animations.
+pub fn animate_sprites(
+    mut sprites: Query<(&mut SpriteAnimator, &Handle<Mesh>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    animations: Res<PlayerAnimations>,
+    time: Res<Time>,
+) {
+    for (mut animator, mesh) in sprites.iter_mut() {
+        let Some(animation) = animations.get(animator.animation) else {
+            continue;
+        };
+        animator.phase =
+            (animator.phase + time.delta_seconds() * animator.rate / animation.duration).fract();
+        let frame = animation.frame_at(animator.phase);
+
+        if animator.frame == Some(frame) {
+            continue;
+        }
+        // Nothing to show before the atlas is built
+        let (Some(rect), Some(mesh)) = (animations.frames.get(frame), meshes.get_mut(mesh)) else {
+            continue;
+        };
+        animator.frame = Some(frame);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, quad_uvs(*rect));
+    }
+}
+
+// Blob shadow
+
+fn build_blob_shadow(app: &mut App) {
+    app.init_resource::<BlobShadowConfig>()
+        .add_systems(Update, (add_blob_shadow, update_blob_shadow).chain());
+}
+
+/// Dark disc on the ground below the player, since the flat sprite doesn't
+/// cast a shadow worth seeing. Shows where a jump will come down.
+#[derive(Resource, Debug, Clone)]
+pub struct BlobShadowConfig {
+    pub enabled: bool,
+    /// Ground further below than this gets no shadow
+    pub max_distance: f32,
+    /// Relative to `PlayerDimensions::width`
+    pub radius: f32,
+    /// Alpha right at the ground, fading out towards `max_distance`
+    pub opacity: f32,
+}
+
+impl Default for BlobShadowConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_distance: 8.,
+            radius: 0.35,
+            opacity: 0.5,
+        }
+    }
+}
+
+#[derive(Component)]
+struct BlobShadowRayCaster;
+
+#[derive(Component)]
+struct BlobShadow;
+
+/// Lifted off the ground so it doesn't z-fight.
+const BLOB_SHADOW_LIFT: f32 = 0.02;
+
+fn add_blob_shadow(
+    mut commands: Commands,
+    player: Query<Entity, Added<Player>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<BlobShadowConfig>,
+    dimensions: Res<PlayerDimensions>,
+) {
+    for entity in player.iter() {
+        let mesh = meshes.add(Mesh::from(shape::Circle::new(
+            config.radius * dimensions.width,
+        )));
+        // Its own material, faded by the height of each player
+        let material = materials.add(StandardMaterial {
+            base_color: Color::rgba(0., 0., 0., config.opacity),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        });
+        commands.entity(entity).with_children(|builder| {
+            builder.spawn((
+                Name::new("BlobShadowRayCaster"),
+                BlobShadowRayCaster,
+                RayCaster::new(Vec3::ZERO, Vec3::NEG_Y)
+                    .with_max_time_of_impact(config.max_distance)
+                    .with_query_filter(SpatialQueryFilter::new().without_entities([entity])),
+                SpatialBundle::default(),
+            ));
+            builder.spawn((
+                Name::new("BlobShadow"),
+                BlobShadow,
+                NotShadowCaster,
+                PbrBundle {
+                    mesh,
+                    material,
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+            ));
+        });
+    }
+}
+
+fn update_blob_shadow(
+    player: Query<
+        (
+            &GlobalTransform,
+            &Children,
+            Option<&PlayerUp>,
+            Has<PlayerMovingOnLadder>,
+        ),
+        With<Player>,
+    >,
+    mut rays: Query<(&mut RayCaster, &RayHits), With<BlobShadowRayCaster>>,
+    mut shadows: Query<
+        (&mut Transform, &mut Visibility, &Handle<StandardMaterial>),
+        With<BlobShadow>,
+    >,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<BlobShadowConfig>,
+) {
+    for (player_transform, children, up, on_ladder) in player.iter() {
+        let Some(ray_entity) = children.iter().copied().find(|&child| rays.contains(child)) else {
+            continue;
+        };
+        let Some(shadow_entity) = children
+            .iter()
+            .copied()
+            .find(|&child| shadows.contains(child))
+        else {
+            continue;
+        };
+        let Ok((mut ray, hits)) = rays.get_mut(ray_entity) else {
+            continue;
+        };
+        let Ok((mut transform, mut visibility, material)) = shadows.get_mut(shadow_entity) else {
+            continue;
+        };
+
+        // Down towards the ceiling when gravity is flipped. The body itself
+        // isn't turned over, so its space is the world's but for the yaw.
+        let down = -up.map_or(Vec3::Y, PlayerUp::up);
+        if ray.direction != down {
+            ray.direction = down;
+        }
+
+        let hit = hits
+            .iter()
+            .min_by(|a, b| a.time_of_impact.total_cmp(&b.time_of_impact));
+        let Some(hit) = hit.filter(|_| config.enabled && !on_ladder) else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        visibility.set_if_neq(Visibility::Inherited);
+
+        let point = ray.global_origin() + ray.global_direction() * hit.time_of_impact;
+        let (_, player_rotation, player_translation) =
+            player_transform.to_scale_rotation_translation();
+        // Flat on the ground, which the disc faces with its +Z
+        transform.translation = player_rotation.inverse()
+            * (point + hit.normal * BLOB_SHADOW_LIFT - player_translation);
+        transform.rotation =
+            player_rotation.inverse() * Quat::from_rotation_arc(Vec3::Z, hit.normal);
+
+        // Smaller and fainter the higher up the player is
+        let height = (hit.time_of_impact / config.max_distance).clamp(0., 1.);
+        transform.scale = Vec3::splat(1. - height * 0.5);
+        let alpha = config.opacity * (1. - height);
+        if materials
+            .get(material)
+            .is_some_and(|m| m.base_color.a() != alpha)
+        {
+            if let Some(material) = materials.get_mut(material) {
+                material.base_color.set_a(alpha);
+            }
+        }
+    }
+}
+
+// Respawn
+
+fn build_respawn(app: &mut App) {
+    app.add_event::<PlayerDiedEvent>()
+        .add_systems(Update, respawn_player.run_if(in_state(GameState::Playing)));
+}
+
+/// Where the player comes back after dying, the spawn point or the last checkpoint.
+#[derive(Resource, Debug, Clone)]
+pub struct PlayerRespawn {
+    pub transform: Transform,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct PlayerDiedEvent {
+    pub entity: Entity,
+}
+
+fn respawn_player(
+    mut died: EventReader<PlayerDiedEvent>,
+    respawn: Option<Res<PlayerRespawn>>,
+    mut player: Query<
+        (
+            &mut Transform,
+            &mut Position,
+            &mut Rotation,
+            &mut LinearVelocity,
+        ),
+        With<Player>,
+    >,
+) {
+    let Some(respawn) = respawn else {
+        return;
+    };
+
+    for ev in died.read() {
+        let Ok((mut transform, mut position, mut rotation, mut velocity)) =
+            player.get_mut(ev.entity)
+        else {
+            continue;
+        };
+
+        debug_print!("respawning {:?}", ev.entity);
+
+        *transform = respawn.transform;
+        position.0 = respawn.transform.translation;
+        rotation.0 = respawn.transform.rotation;
+        velocity.0 = Vec3::ZERO;
+    }
+}
+
+// Player camera
+
+fn build_player_camera(app: &mut App) {
+    app.add_plugins(LookTransformPlugin)
+        .init_resource::<CameraConfig>()
+        .init_resource::<LocalPlayers>()
+        .add_systems(
+            Update,
+            (assign_cameras, add_look_transform, face_camera_on_spawn).chain(),
+        )
+        .add_systems(PostUpdate, split_screen_viewports)
+        .add_systems(Update, add_shake_trauma)
+        .add_systems(
+            Update,
+            hide_player_in_first_person.run_if(in_state(GameState::Playing)),
+        )
+        // On top of what the smoother wrote this frame
+        .add_systems(
+            PostUpdate,
+            apply_camera_shake.before(TransformSystem::TransformPropagate),
+        )
+        .add_systems(
+            Update,
+            (blend_camera_zone, player_following_camera)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+#[derive(Component, Debug)]
+pub struct PlayerFollowingCamera;
+
+/// Player the camera follows and takes the orbit input of.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CameraTarget(pub Entity);
+
+/// Index of a split-screen player, 0 being the leftmost view.
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct PlayerSlot(pub usize);
+
+/// Players spawned at each spawn point, each given its own camera and view.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct LocalPlayers(pub usize);
+
+impl Default for LocalPlayers {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+fn player_camera<'a>(
+    cameras: &'a Query<(&CameraTarget, &GlobalTransform)>,
+    player: Entity,
+) -> Option<&'a GlobalTransform> {
+    cameras
+        .iter()
+        .find(|(target, _)| target.0 == player)
+        .map(|(_, gtransform)| gtransform)
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct CameraConfig {
+    /// Closest the eye gets to the player, keeping it outside of the player's body
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Distance change per mouse wheel step
+    pub wheel_zoom_step: f32,
+    /// Distance change per second while the zoom button is held
+    pub axis_zoom_speed: f32,
+    /// Orbit angle per pixel of mouse motion
+    pub mouse_sensitivity: f32,
+    /// Orbit speed in radians per second at full stick deflection
+    pub stick_sensitivity: f32,
+    pub invert_y: bool,
+    /// Pitch range of the eye above the horizon, short of straight down or up
+    pub min_pitch: f32,
+    pub max_pitch: f32,
+    /// Gap kept between the eye and the terrain it's pulled in front of
+    pub collision_skin: f32,
+    /// How quickly the eye goes back out once nothing is in the way
+    pub collision_recovery: f32,
+    /// Trauma lost per second
+    pub shake_decay: f32,
+    /// Jitter at full trauma
+    pub shake_max_offset: f32,
+    pub shake_max_angle: f32,
+    /// Landing faster than this shakes the camera
+    pub hard_landing_speed: f32,
+    /// Trauma of a landing at `hard_landing_speed`, harder ones add more
+    pub landing_trauma: f32,
+    /// Added to the landing trauma of a ground pound
+    pub ground_pound_trauma: f32,
+    pub damage_trauma: f32,
+    /// Rate of the blend between camera modes, in modes per second
+    pub mode_transition_speed: f32,
+    /// Seconds of horizontal movement the target is put ahead of the player
+    pub look_ahead_time: f32,
+    pub max_look_ahead: f32,
+    /// Rate of the blend into and out of camera zones, per second
+    pub zone_blend_speed: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            min_distance: 2.,
+            max_distance: 15.,
+            wheel_zoom_step: 0.5,
+            axis_zoom_speed: 5.,
+            mouse_sensitivity: 0.005,
+            stick_sensitivity: 3.,
+            invert_y: false,
+            min_pitch: -20f32.to_radians(),
+            max_pitch: 80f32.to_radians(),
+            collision_skin: 0.2,
+            collision_recovery: 4.,
+            shake_decay: 1.5,
+            shake_max_offset: 0.15,
+            shake_max_angle: 2f32.to_radians(),
+            hard_landing_speed: 8.,
+            landing_trauma: 0.3,
+            ground_pound_trauma: 0.4,
+            damage_trauma: 0.5,
+            mode_transition_speed: 4.,
+            look_ahead_time: 0.3,
+            max_look_ahead: 1.5,
+            zone_blend_speed: 2.,
+        }
+    }
+}
+
+#[derive(Component, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CameraMode {
+    #[default]
+    ThirdPerson,
+    /// From the player's head, looking where the orbit would look at the player
+    FirstPerson,
+}
+
+/// Camera zone framing blended into the orbit, `weight` 0 being no zone at all.
+#[derive(Component, Default, Debug)]
+struct CameraZoneBlend {
+    offset: Vec3,
+    distance: f32,
+    pitch: f32,
+    weight: f32,
 }
 
-fn player_jumping(
-    mut player: Query<
-        (
-            Ref<PlayerJumping>,
-            &ActionState<Action>,
-            &mut TnuaController,
-        ),
-        (With<Player>, With<PlayerJumping>),
-    >,
-) {
-    for (jumping_state, input, mut controller) in player.iter_mut() {
-        if jumping_state.is_added() || input.pressed(Action::Jump) {
-            controller.action(TnuaBuiltinJump {
-                height: 1.0,
-                ..default()
-            });
-        }
+/// Trauma in 0..=1, shaking the camera while it decays.
+#[derive(Component, Default, Debug)]
+pub struct CameraShake {
+    pub trauma: f32,
+}
+
+impl CameraShake {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.);
     }
 }
 
-fn insert_or_modify<T: Component>(
-    commands: &mut Commands,
-    entity: Entity,
-    component: &mut Option<Mut<T>>,
-    insert: impl Fn() -> T,
-    modify: impl FnOnce(&mut T),
+/// Where the eye orbits around the player. `yaw` 0 looks along -Z, `pitch` is
+/// the elevation of the eye.
+#[derive(Component, Debug)]
+struct CameraOrbit {
+    yaw: f32,
+    pitch: f32,
+    /// Distance set by the zoom
+    distance: f32,
+    /// Distance actually used, shorter while terrain is in the way
+    current_distance: f32,
+    /// 0 in third person, 1 in first person, in between while switching
+    first_person_blend: f32,
+}
+
+impl CameraOrbit {
+    fn direction(&self, pitch: f32) -> Vec3 {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, -pitch, 0.) * Vec3::Z
+    }
+}
+
+fn add_look_transform(
+    mut commands: Commands,
+    player: Query<(Entity, &Transform), (Added<PlayerFollowingCamera>, Without<LookTransform>)>,
+    config: Res<CameraConfig>,
 ) {
-    if let Some(mut c) = component.as_mut() {
-        modify(&mut c);
-    } else {
-        let mut c = insert();
-        modify(&mut c);
-        commands.entity(entity).insert(c);
+    for (entity, transform) in player.iter() {
+        let distance = transform
+            .translation
+            .length()
+            .clamp(config.min_distance, config.max_distance);
+        commands.entity(entity).insert((
+            LookTransformBundle {
+                transform: LookTransform::new(transform.translation, Vec3::ZERO, Vec3::Y),
+                smoother: Smoother::new(0.9),
+            },
+            CameraOrbit {
+                yaw: f32::atan2(transform.translation.x, transform.translation.z),
+                pitch: (transform.translation.normalize_or_zero().y)
+                    .asin()
+                    .clamp(config.min_pitch, config.max_pitch),
+                distance,
+                current_distance: distance,
+                first_person_blend: 0.,
+            },
+            CameraMode::default(),
+            CameraZoneBlend::default(),
+            CameraShake::default(),
+        ));
     }
 }
 
-fn player_movement_walk(
+// The camera from `main` goes to the first player, later ones get a new camera each
+fn assign_cameras(
     mut commands: Commands,
-    mut player: Query<
-        (Entity, &ActionState<Action>, Option<&mut TnuaController>),
-        (With<Player>, Or<(With<PlayerGrounded>, With<PlayerJumping>)>),
-    >,
+    players: Query<(Entity, Option<&PlayerSlot>), Added<Player>>,
+    mut cameras: Query<(Entity, Option<&mut CameraTarget>), With<PlayerFollowingCamera>>,
+    alive: Query<(), With<Player>>,
 ) {
-    const MOVEMENT_SPEED: f32 = 2.0;
+    let mut free = cameras
+        .iter_mut()
+        .filter(|(_, target)| {
+            target
+                .as_ref()
+                .map_or(true, |target| !alive.contains(target.0))
+        })
+        .collect::<Vec<_>>()
+        .into_iter();
 
-    for (entity, input, mut controller) in player.iter_mut() {
-        let mut movement = Vec3::ZERO;
+    // Lower slots first, so a single reused camera goes to the first player
+    let mut players = players.iter().collect::<Vec<_>>();
+    players.sort_by_key(|(_, slot)| slot.map_or(0, |slot| slot.0));
 
-        if input.pressed(Action::Up) {
-            movement.z -= MOVEMENT_SPEED;
-        }
-        if input.pressed(Action::Down) {
-            movement.z += MOVEMENT_SPEED;
+    for (player, slot) in players {
+        if let Some((camera, target)) = free.next() {
+            match target {
+                Some(mut target) => target.0 = player,
+                None => {
+                    commands.entity(camera).insert(CameraTarget(player));
+                }
+            }
+            continue;
         }
-        if input.pressed(Action::Left) {
-            movement.x -= MOVEMENT_SPEED;
+        let slot = slot.copied().unwrap_or_default();
+        commands.spawn((
+            Camera3dBundle {
+                transform: Transform::from_translation(Vec3::new(0.0, 4.0, 6.0))
+                    .looking_at(Vec3::ZERO, Vec3::Y),
+                camera: Camera {
+                    hdr: true,
+                    order: slot.0 as isize,
+                    ..default()
+                },
+                ..default()
+            },
+            BloomSettings {
+                intensity: 0.1,
+                ..default()
+            },
+            Name::new(format!("PlayerCamera{}", slot.0)),
+            PlayerFollowingCamera,
+            CameraTarget(player),
+        ));
+    }
+}
+
+/// Cameras following a player share the window side by side, in slot order.
+fn split_screen_viewports(
+    mut cameras: Query<(&mut Camera, Option<&CameraTarget>), With<PlayerFollowingCamera>>,
+    slots: Query<Option<&PlayerSlot>, With<Player>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+
+    let mut views = cameras
+        .iter_mut()
+        .filter_map(|(camera, target)| {
+            let slot = slots.get(target?.0).ok()?.copied().unwrap_or_default();
+            Some((slot, camera))
+        })
+        .collect::<Vec<_>>();
+    views.sort_by_key(|(slot, _)| slot.0);
+
+    let count = views.len() as u32;
+    let size = UVec2::new(window.physical_width(), window.physical_height());
+    let width = size.x / count.max(1);
+    for (i, (_, mut camera)) in views.into_iter().enumerate() {
+        let viewport = (count > 1).then(|| Viewport {
+            physical_position: UVec2::new(width * i as u32, 0),
+            physical_size: UVec2::new(width, size.y),
+            ..default()
+        });
+        let bounds = |viewport: &Viewport| (viewport.physical_position, viewport.physical_size);
+        if camera.viewport.as_ref().map(bounds) != viewport.as_ref().map(bounds) {
+            camera.viewport = viewport;
         }
-        if input.pressed(Action::Right) {
-            movement.x += MOVEMENT_SPEED;
+        if !camera.is_active {
+            camera.is_active = true;
         }
+    }
 
-        movement = movement.clamp_length_max(MOVEMENT_SPEED);
+    // A camera left over from a despawned player would draw over the others,
+    // but with no player at all it still has to show the menus
+    for (mut camera, target) in cameras.iter_mut() {
+        if target.map_or(true, |target| !slots.contains(target.0)) {
+            if camera.is_active != (count == 0) {
+                camera.is_active = count == 0;
+            }
+            if camera.viewport.is_some() {
+                camera.viewport = None;
+            }
+        }
+    }
+}
 
-        insert_or_modify(
-            &mut commands,
-            entity,
-            &mut controller,
-            || TnuaController::default(),
-            |c| {
-                c.basis(TnuaBuiltinWalk {
-                    desired_velocity: movement,
-                    desired_forward: movement.normalize_or_zero(),
-                    float_height: PLAYER_HEIGHT / 2.,
-                    ..default()
-                });
-            },
-        );
+// Start out behind the player, looking the way they face
+fn face_camera_on_spawn(
+    player: Query<(Entity, &Transform), Added<Player>>,
+    mut camera: Query<(&CameraTarget, &mut CameraOrbit)>,
+) {
+    for (entity, transform) in player.iter() {
+        let (yaw, _, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        for (target, mut orbit) in camera.iter_mut() {
+            if target.0 == entity {
+                orbit.yaw = yaw;
+            }
+        }
     }
 }
 
-fn player_movement_ladder(
-    mut player: Query<
+fn player_following_camera(
+    mut camera: Query<
+        (
+            &mut LookTransform,
+            &mut CameraOrbit,
+            &mut CameraMode,
+            &CameraZoneBlend,
+            &CameraTarget,
+        ),
+        With<PlayerFollowingCamera>,
+    >,
+    player: Query<
         (
             Entity,
+            &GlobalTransform,
+            &LinearVelocity,
             &ActionState<Action>,
-            &PlayerMovingOnLadder,
-            &mut Transform,
+            Option<&PlayerUp>,
         ),
         With<Player>,
     >,
+    bodies: Query<(&RigidBody, Has<Sensor>)>,
+    spatial_query: SpatialQuery,
+    config: Res<CameraConfig>,
     time: Res<Time>,
-    mut ladder_end: EventWriter<LadderInteractionEndEvent>,
+    dimensions: Res<PlayerDimensions>,
 ) {
-    const LADDER_SPEED: f32 = 2.0;
+    for (mut camera, mut orbit, mut mode, zone, target) in camera.iter_mut() {
+        let Ok((player_entity, player, velocity, input, up)) = player.get(target.0) else {
+            continue;
+        };
 
-    for (entity, input, ladder, mut transform) in player.iter_mut() {
-        // let frac = (transform.translation.y - ladder.ladder_bottom.y)
-        //     / (ladder.ladder_top.y - ladder.ladder_bottom.y);
+        // Wheel and d-pad up zoom in
+        let zoom_in = input.value(Action::Zoom) * config.wheel_zoom_step
+            + input.value(Action::ZoomAxis) * config.axis_zoom_speed * time.delta_seconds();
 
-        let height = ladder.top.y - ladder.bottom.y;
-        let cur_pos = transform.translation.y - ladder.bottom.y;
+        // Moving the mouse right or down turns the view right or down
+        let look = input
+            .axis_pair(Action::Look)
+            .map_or(Vec2::ZERO, |axis| axis.xy())
+            * config.mouse_sensitivity
+            + input
+                .axis_pair(Action::LookAxis)
+                .map_or(Vec2::ZERO, |axis| axis.xy() * Vec2::new(1., -1.))
+                * config.stick_sensitivity
+                * time.delta_seconds();
+        let look = if config.invert_y {
+            look * Vec2::new(1., -1.)
+        } else {
+            look
+        };
 
-        if input.pressed(Action::Up) {
-            if cur_pos > height + PLAYER_HEIGHT / 2. {
-                ladder_end.send(LadderInteractionEndEvent(entity));
-                transform.translation -= ladder.face_normal * PLAYER_WIDTH * 0.8;
-            } else {
-                transform.translation += LADDER_SPEED * Vec3::Y * time.delta_seconds();
-            }
-        }
-        if input.pressed(Action::Down) {
-            if cur_pos < 0.1 {
-                ladder_end.send(LadderInteractionEndEvent(entity));
-            } else {
-                transform.translation -= LADDER_SPEED * Vec3::Y * time.delta_seconds();
-            }
+        if input.just_pressed(Action::CameraMode) {
+            *mode = match *mode {
+                CameraMode::ThirdPerson => CameraMode::FirstPerson,
+                CameraMode::FirstPerson => CameraMode::ThirdPerson,
+            };
         }
+        let (min_pitch, max_pitch) = match *mode {
+            CameraMode::ThirdPerson => (config.min_pitch, config.max_pitch),
+            // Looking up isn't limited by the ground behind the player
+            CameraMode::FirstPerson => (-config.max_pitch, config.max_pitch),
+        };
+
+        orbit.distance = (orbit.distance - zoom_in).clamp(config.min_distance, config.max_distance);
+        orbit.yaw -= look.x;
+        orbit.pitch = (orbit.pitch + look.y).clamp(min_pitch, max_pitch);
+
+        let blend_target = match *mode {
+            CameraMode::ThirdPerson => 0.,
+            CameraMode::FirstPerson => 1.,
+        };
+        let blend_step = config.mode_transition_speed * time.delta_seconds();
+        orbit.first_person_blend +=
+            (blend_target - orbit.first_person_blend).clamp(-blend_step, blend_step);
+
+        // Only the goal is set here, `Smoother` eases the camera towards it,
+        // so starting and stopping don't jerk the view.
+        // Walking follows the camera's yaw, so it stays camera-relative.
+        let look_ahead = (velocity.0 * Vec3::new(1., 0., 1.) * config.look_ahead_time)
+            .clamp_length_max(config.max_look_ahead);
+        let third_person = 1. - orbit.first_person_blend;
+        let target = player.translation() + (look_ahead + zone.offset * zone.weight) * third_person;
+
+        // A camera zone overrides the player's own framing
+        let distance = orbit.distance + (zone.distance - orbit.distance) * zone.weight;
+        let pitch = match *mode {
+            CameraMode::ThirdPerson => orbit.pitch + (zone.pitch - orbit.pitch) * zone.weight,
+            CameraMode::FirstPerson => orbit.pitch,
+        };
+        // The whole rig turns over with the player's gravity, so the ceiling
+        // they walk on reads as the floor
+        let up_rotation = up.map_or(Quat::IDENTITY, |up| up.rotation);
+        let direction = up_rotation * orbit.direction(pitch);
+
+        // Static terrain between the player and the eye pulls the eye in at once,
+        // props that move around are ignored
+        let obstruction = spatial_query
+            .ray_hits(
+                target,
+                direction,
+                distance,
+                16,
+                true,
+                SpatialQueryFilter::new().without_entities([player_entity]),
+            )
+            .into_iter()
+            .filter(|hit| {
+                bodies
+                    .get(hit.entity)
+                    .is_ok_and(|(body, sensor)| body.is_static() && !sensor)
+            })
+            .map(|hit| hit.time_of_impact)
+            .reduce(f32::min);
+        let recovery = (config.collision_recovery * time.delta_seconds()).min(1.);
+        let recovered = orbit.current_distance + (distance - orbit.current_distance) * recovery;
+        orbit.current_distance = obstruction.map_or(recovered, |toi| {
+            recovered.min((toi - config.collision_skin).max(0.))
+        });
+        let third_person_eye = target + direction * orbit.current_distance;
+
+        // Eyes at the head, looking away from where the third-person eye would be
+        let head = player.translation() + up_rotation * Vec3::Y * dimensions.height * 0.4;
+        let blend = orbit.first_person_blend;
+        camera.eye = third_person_eye.lerp(head, blend);
+        camera.target = target.lerp(head - direction, blend);
+        camera.up = up_rotation * Vec3::Y;
     }
 }
 
-fn player_animation(
-    mut player: Query<(&mut Handle<StandardMaterial>, &TnuaController), With<Player>>,
-    player_images: Res<PlayerImages>,
+fn blend_camera_zone(
+    sensors: Query<(&ZoneSensor, &CollidingEntities)>,
+    zones: Query<&CameraZone>,
+    mut camera: Query<(&mut CameraZoneBlend, &CameraTarget)>,
+    config: Res<CameraConfig>,
     time: Res<Time>,
-    mut walk_start_time: Local<Option<f32>>,
 ) {
-    for (mut mat, controller) in player.iter_mut() {
-        match controller.concrete_basis::<TnuaBuiltinWalk>() {
-            None => {
-                *mat = player_images.0[0].clone();
-                continue;
-            }
-            Some(walk) => {
-                let speed = walk.1.running_velocity.length();
-
-                if speed == 0. {
-                    *mat = player_images.0[0].clone();
-                    continue;
-                }
+    let rate = (config.zone_blend_speed * time.delta_seconds()).min(1.);
+    for (mut blend, target) in camera.iter_mut() {
+        let zone = sensors
+            .iter()
+            .filter(|(_, colliding)| colliding.contains(&target.0))
+            .filter_map(|(sensor, _)| zones.get(sensor.zone).ok())
+            .max_by_key(|zone| zone.priority);
 
-                let walk_start_time = if let Some(t) = *walk_start_time {
-                    t
-                } else {
-                    let t = time.elapsed_seconds();
-                    *walk_start_time = Some(t);
-                    t
-                };
-
-                const WALK_ANIMATION_DURATION: f32 = 0.4;
-                const WALK_ANIMATION_FRAMES: [(f32, usize); 2] = [(0.0, 0), (0.6, 1)];
-
-                let m = ((time.elapsed_seconds() - walk_start_time) % WALK_ANIMATION_DURATION)
-                    / WALK_ANIMATION_DURATION;
-
-                for af in WALK_ANIMATION_FRAMES.into_iter().rev() {
-                    if m >= af.0 {
-                        *mat = player_images.0[af.1].clone();
-                        break;
-                    }
+        match zone {
+            Some(zone) => {
+                // Entering from no zone starts at the zone's framing, fading it in.
+                // Going from one zone to another eases between the two.
+                if blend.weight == 0. {
+                    blend.offset = zone.offset;
+                    blend.distance = zone.distance;
+                    blend.pitch = zone.pitch;
                 }
+                blend.offset = blend.offset.lerp(zone.offset, rate);
+                blend.distance += (zone.distance - blend.distance) * rate;
+                blend.pitch += (zone.pitch - blend.pitch) * rate;
+                blend.weight = (blend.weight + rate).min(1.);
             }
+            // The last zone's framing is kept while fading out
+            None => blend.weight = (blend.weight - rate).max(0.),
         }
     }
 }
 
-// Player camera
+/// The sprite would fill the view from inside the player's head.
+/// Split-screen views of the other players don't see them either.
+fn hide_player_in_first_person(
+    camera: Query<(&CameraOrbit, &CameraTarget)>,
+    mut player: Query<&mut Visibility, With<Player>>,
+) {
+    for (orbit, target) in camera.iter() {
+        let Ok(mut visibility) = player.get_mut(target.0) else {
+            continue;
+        };
+        visibility.set_if_neq(if orbit.first_person_blend > 0.5 {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        });
+    }
+}
 
-fn build_player_camera(app: &mut App) {
-    app.add_plugins(LookTransformPlugin)
-        .add_systems(Update, add_look_transform)
-        .add_systems(Update, player_following_camera);
+fn add_shake_trauma(
+    mut landed: EventReader<PlayerLandedEvent>,
+    mut ground_pound: EventReader<GroundPoundEvent>,
+    mut damage: EventReader<DamageEvent>,
+    mut camera: Query<(&mut CameraShake, &CameraTarget)>,
+    config: Res<CameraConfig>,
+) {
+    let mut trauma = HashMap::<Entity, f32>::new();
+    for ev in landed.read() {
+        if ev.impact_speed > config.hard_landing_speed {
+            *trauma.entry(ev.entity).or_default() +=
+                config.landing_trauma * ev.impact_speed / config.hard_landing_speed;
+        }
+    }
+    for ev in ground_pound.read() {
+        *trauma.entry(ev.entity).or_default() += config.ground_pound_trauma;
+    }
+    for ev in damage.read() {
+        *trauma.entry(ev.target).or_default() += config.damage_trauma;
+    }
+
+    // Only the view of the player it happened to shakes
+    for (mut shake, target) in camera.iter_mut() {
+        if let Some(&trauma) = trauma.get(&target.0) {
+            shake.add_trauma(trauma);
+        }
+    }
 }
 
-#[derive(Component, Debug)]
-pub struct PlayerFollowingCamera;
+// Sum of sines, smooth but irregular enough to pass for noise
+fn shake_noise(t: f32, seed: f32) -> f32 {
+    ((t * 23. + seed * 7.).sin() + (t * 37. + seed * 13.).sin() * 0.5) / 1.5
+}
 
-fn add_look_transform(
-    mut commands: Commands,
-    player: Query<(Entity, &Transform), (Added<PlayerFollowingCamera>, Without<LookTransform>)>,
+fn apply_camera_shake(
+    mut camera: Query<(&mut Transform, &mut CameraShake)>,
+    config: Res<CameraConfig>,
+    time: Res<Time>,
 ) {
-    for (entity, transform) in player.iter() {
-        commands.entity(entity).insert(LookTransformBundle {
-            transform: LookTransform::new(transform.translation, Vec3::ZERO, Vec3::Y),
-            smoother: Smoother::new(0.9),
-        });
+    let t = time.elapsed_seconds();
+    for (mut transform, mut shake) in camera.iter_mut() {
+        if shake.trauma <= 0. {
+            continue;
+        }
+
+        // Squared, so small trauma barely shakes
+        let amount = shake.trauma * shake.trauma;
+        let offset = Vec3::new(shake_noise(t, 0.), shake_noise(t, 1.), shake_noise(t, 2.));
+        transform.translation += transform.rotation * offset * config.shake_max_offset * amount;
+        transform.rotation *=
+            Quat::from_rotation_z(shake_noise(t, 3.) * config.shake_max_angle * amount);
+
+        shake.trauma = (shake.trauma - config.shake_decay * time.delta_seconds()).max(0.);
     }
 }
 
-fn player_following_camera(
-    mut camera: Query<&mut LookTransform, With<PlayerFollowingCamera>>,
-    player: Query<&GlobalTransform, With<Player>>,
+fn ladder_interaction(
+    mut interact: EventReader<InteractEvent>,
+    ladders: Query<(&Ladder, &Position), Without<Player>>,
+    player: Query<(Has<PlayerGrounded>, &Transform), With<Player>>,
+    mut ladder_begin: EventWriter<LadderInteractionBeginEvent>,
+    dimensions: Res<PlayerDimensions>,
 ) {
-    let Ok(player) = player.get_single() else {
-        return;
-    };
+    for ev in interact.read() {
+        let Ok((ladder, ladder_pos)) = ladders.get(ev.target) else {
+            continue;
+        };
+        let Ok((walking, transform)) = player.get(ev.player) else {
+            error!("Player missing");
+            continue;
+        };
+        if !walking {
+            continue;
+        }
+
+        // align with the center of the ladder
+        let ladder_center =
+            (ev.point - ladder_pos.0).dot(ladder.face_normal) * ladder.face_normal + ladder_pos.0;
+        let (top, bottom) = ladder_path(ladder, ladder_center, &dimensions);
+
+        let along = (transform.translation - bottom)
+            .dot(ladder.up)
+            .clamp(0., (top - bottom).length());
 
-    for mut camera in camera.iter_mut() {
-        camera.target = player.translation();
+        ladder_begin.send(LadderInteractionBeginEvent {
+            entity: ev.player,
+            face_normal: ladder.face_normal,
+            top,
+            bottom,
+            to: bottom + ladder.up * along,
+            rotation: facing_ladder(ladder),
+        });
+
+        debug_print!("begin moving on ladder {:?}", ev.target);
     }
 }
 
-fn player_interaction(
-    ray: Query<(&RayCaster, &RayHits, &Parent), With<InteractionRayCaster>>,
-    ladders: Query<(Entity, &Ladder, &Position, &Rotation, &Collider), Without<Player>>,
-    mut player: Query<(&ActionState<Action>, Has<PlayerGrounded>, &mut Transform), With<Player>>,
+/// Climbing path along the (possibly inclined) ladder axis through `center`
+/// on its face, `bottom` being the player's center when standing at its foot.
+fn ladder_path(ladder: &Ladder, center: Vec3, dimensions: &PlayerDimensions) -> (Vec3, Vec3) {
+    let top = center + ladder.up * ladder.half_height;
+    let bottom = center - ladder.up * (ladder.half_height - dimensions.height / 2.0);
+    (top, bottom)
+}
+
+fn facing_ladder(ladder: &Ladder) -> Quat {
+    Quat::from_rotation_y(ladder.face_normal.xz().angle_between(Vec2::Y))
+}
+
+/// Walking off the platform behind the top of a ladder, toward its face,
+/// grabs onto it near the top, as if about to climb down.
+fn ladder_top_mount(
+    ladders: Query<(&Ladder, &Position), Without<Player>>,
+    player: Query<
+        (Entity, &TnuaController, &LinearVelocity, &Transform),
+        (With<Player>, With<PlayerGrounded>),
+    >,
     mut ladder_begin: EventWriter<LadderInteractionBeginEvent>,
+    config: Res<PlayerMovementConfig>,
+    dimensions: Res<PlayerDimensions>,
+) {
+    for (entity, controller, velocity, transform) in player.iter() {
+        // Only once the ground is gone, walking up to the edge is still walking
+        if !controller.is_airborne().unwrap_or(false) {
+            continue;
+        }
+        let Some(heading) = (velocity.0 * Vec3::new(1., 0., 1.)).try_normalize() else {
+            continue;
+        };
+
+        let ladder = ladders.iter().find(|(ladder, ladder_pos)| {
+            let Some(outward) = (ladder.face_normal * Vec3::new(1., 0., 1.)).try_normalize() else {
+                return false;
+            };
+            // Roughly toward the face, not along or away from the edge
+            if heading.dot(outward) < 0.7 {
+                return false;
+            }
+            let top = ladder_pos.0
+                + ladder.face_normal * ladder.half_depth
+                + ladder.up * ladder.half_height;
+            let offset = transform.translation - top;
+            let across = ladder.up.cross(ladder.face_normal).normalize_or_zero();
+            offset.dot(across).abs() <= ladder.half_width
+                && (-(ladder.half_depth * 2. + dimensions.radius())..=config.ladder_top_reach)
+                    .contains(&offset.dot(ladder.face_normal))
+                && (-config.ladder_top_reach..=dimensions.height).contains(&offset.y)
+        });
+        let Some((ladder, ladder_pos)) = ladder else {
+            continue;
+        };
+
+        let ladder_center = ladder_pos.0 + ladder.face_normal * ladder.half_depth;
+        let (top, bottom) = ladder_path(ladder, ladder_center, &dimensions);
+        ladder_begin.send(LadderInteractionBeginEvent {
+            entity,
+            face_normal: ladder.face_normal,
+            top,
+            bottom,
+            // Half the body over the top, short of climbing off it again
+            to: top,
+            rotation: facing_ladder(ladder),
+        });
+
+        debug_print!("begin moving on ladder from its top");
+    }
+}
+
+fn leave_ladder(
+    player: Query<(Entity, &ActionState<Action>), (With<Player>, With<PlayerMovingOnLadder>)>,
     mut ladder_end: EventWriter<LadderInteractionEndEvent>,
 ) {
-    for (ray, hits, parent) in &ray {
-        screen_print!("hit: {:?}", hits.as_slice());
+    for (entity, input) in player.iter() {
+        if input.just_pressed(Action::Interact) {
+            ladder_end.send(LadderInteractionEndEvent(entity));
+            debug_print!("end moving on ladder");
+        }
+    }
+}
+
+fn monkey_bars_interaction(
+    mut interact: EventReader<InteractEvent>,
+    monkey_bars: Query<(&MonkeyBars, &Position), Without<Player>>,
+    mut player: Query<(Has<PlayerGrounded>, &mut Transform), With<Player>>,
+    mut monkey_bars_begin: EventWriter<MonkeyBarsInteractionBeginEvent>,
+    dimensions: Res<PlayerDimensions>,
+) {
+    for ev in interact.read() {
+        let Ok((bars, bars_pos)) = monkey_bars.get(ev.target) else {
+            continue;
+        };
+        let Ok((walking, mut transform)) = player.get_mut(ev.player) else {
+            error!("Player missing");
+            continue;
+        };
+        if !walking {
+            continue;
+        }
+
+        // Hang with the head just below the bars
+        let hang_center = bars_pos.0 - Vec3::Y * (bars.half_height + dimensions.height / 2.);
+        let start = hang_center - bars.axis * bars.half_length;
+        let end = hang_center + bars.axis * bars.half_length;
+
+        let along = (transform.translation - start)
+            .dot(bars.axis)
+            .clamp(0., bars.half_length * 2.);
+        transform.translation = start + bars.axis * along;
+
+        monkey_bars_begin.send(MonkeyBarsInteractionBeginEvent {
+            entity: ev.player,
+            start,
+            end,
+        });
 
-        let player_entity = parent.get();
+        debug_print!("begin hanging on monkey bars {:?}", ev.target);
+    }
+}
 
-        let Ok((action, walking, mut transform)) = player.get_mut(player_entity) else {
+fn rope_interaction(
+    mut interact: EventReader<InteractEvent>,
+    rope_segments: Query<(&RopeSegment, &GlobalTransform)>,
+    player: Query<(Has<PlayerGrounded>, &Transform), With<Player>>,
+    mut rope_begin: EventWriter<RopeInteractionBeginEvent>,
+) {
+    for ev in interact.read() {
+        let Ok((segment, segment_gtransform)) = rope_segments.get(ev.target) else {
+            continue;
+        };
+        let Ok((walking, transform)) = player.get(ev.player) else {
             error!("Player missing");
             continue;
         };
+        if !walking {
+            continue;
+        }
 
-        if action.just_pressed(Action::Interact) {
-            if walking {
-                for hit in hits.iter() {
-                    if let Some((ladder_entity, ladder, ladder_pos, ladder_rot, col)) =
-                        ladders.get(hit.entity).ok()
-                    {
-                        // align with the center of the ladder
-                        let hit_pos =
-                            ray.global_origin() + ray.global_direction() * hit.time_of_impact;
-                        let ladder_center = (hit_pos - ladder_pos.0).dot(ladder.face_normal)
-                            * ladder.face_normal
-                            + ladder_pos.0;
-                        let player_pos =
-                            Vec3::new(ladder_center.x, transform.translation.y, ladder_center.z);
-                        transform.translation = player_pos;
-                        transform.rotation =
-                            Quat::from_rotation_y(ladder.face_normal.xz().angle_between(Vec2::Y));
-
-                        let aabb = col.compute_aabb(ladder_pos.0, ladder_rot.0);
-                        let half_height = aabb.half_extents().y;
-                        let center = aabb.center().y;
-                        let (top, bottom) = (
-                            center + half_height,
-                            center - half_height + PLAYER_HEIGHT / 2.0,
-                        );
-
-                        ladder_begin.send(LadderInteractionBeginEvent {
-                            entity: player_entity,
-                            face_normal: ladder.face_normal,
-                            top: Vec3::new(player_pos.x, top, player_pos.z),
-                            bottom: Vec3::new(player_pos.x, bottom, player_pos.z),
-                        });
-
-                        screen_print!("begin moving on ladder {ladder_entity:?}");
-                        break;
-                    }
-                }
-            } else {
-                ladder_end.send(LadderInteractionEndEvent(player_entity));
-                screen_print!("end moving on ladder");
-            }
+        // Grab the rope at the player's height
+        let offset = (transform.translation - segment_gtransform.translation())
+            .y
+            .clamp(-segment.half_length, segment.half_length);
+
+        rope_begin.send(RopeInteractionBeginEvent {
+            entity: ev.player,
+            segment: ev.target,
+            offset,
+        });
+
+        debug_print!("begin climbing rope {:?}", ev.target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::scene::ScenePlugin;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    fn capsule_half_height(collider: &Collider) -> f32 {
+        let capsule = collider.shape().as_capsule().expect("not a capsule");
+        capsule.half_height() + capsule.radius
+    }
+
+    #[test]
+    fn dimensions_follow_the_height() {
+        let dimensions = PlayerDimensions {
+            height: 1.6,
+            ..default()
+        };
+        assert_eq!(dimensions.float_height(), dimensions.height / 2.);
+
+        let standing = capsule_half_height(&dimensions.collider());
+        assert!((standing - (dimensions.height / 8. + dimensions.radius())).abs() < 1e-5);
+        // Lowered by `crouch_float_offset`, which keeps the feet where they were
+        let crouching = capsule_half_height(&dimensions.crouch_collider());
+        assert!((standing - crouching + dimensions.crouch_float_offset()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn walk_floats_at_half_the_height() {
+        let height = 1.6;
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(PlayerDimensions {
+                height,
+                ..default()
+            })
+            .init_resource::<PlayerMovementConfig>()
+            .add_systems(Update, player_movement_walk);
+
+        let player = app
+            .world
+            .spawn((
+                Player,
+                PlayerGrounded,
+                ActionState::<Action>::default(),
+                TnuaController::default(),
+                CoyoteTime::default(),
+                DashCooldown::default(),
+                GroundSurface::default(),
+            ))
+            .id();
+        app.update();
+
+        let controller = app.world.get::<TnuaController>(player).unwrap();
+        let (walk, _) = controller
+            .concrete_basis::<TnuaBuiltinWalk>()
+            .expect("no walk basis");
+        assert_eq!(walk.float_height, height / 2.);
+    }
+
+    #[test]
+    fn stays_crouched_under_an_overhang() {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            AssetPlugin::default(),
+            ScenePlugin,
+            PhysicsPlugins::default(),
+        ))
+        .init_asset::<Mesh>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            1. / 60.,
+        )))
+        .init_resource::<PlayerDimensions>()
+        .add_systems(Update, detect_headroom);
+
+        // Crouched, the top of the body is at 0.25 and standing at 0.375
+        let dimensions = PlayerDimensions::default();
+        let bottom = 0.3;
+        app.world.spawn((
+            RigidBody::Static,
+            Collider::cuboid(2., 0.2, 2.),
+            TransformBundle::from_transform(Transform::from_xyz(0., bottom + 0.1, 0.)),
+            Position(Vec3::Y * (bottom + 0.1)),
+        ));
+        let player = app
+            .world
+            .spawn((
+                Player,
+                RigidBody::Kinematic,
+                dimensions.crouch_collider(),
+                TransformBundle::default(),
+                Position(Vec3::ZERO),
+            ))
+            .id();
+        let is_crouch_collider = |app: &App| {
+            let collider = app.world.get::<Collider>(player).unwrap();
+            (capsule_half_height(collider) - capsule_half_height(&dimensions.crouch_collider()))
+                .abs()
+                < 1e-5
+        };
+
+        // Let the spatial query see the overhang before looking for headroom
+        for _ in 0..2 {
+            app.update();
+        }
+        app.world.entity_mut(player).insert(CrouchShape {
+            crouched: true,
+            headroom: true,
+        });
+
+        // Crouch released under the overhang
+        for _ in 0..10 {
+            app.update();
+            let shape = app.world.get::<CrouchShape>(player).unwrap();
+            assert!(!shape.headroom);
+            assert!(shape.crouched);
+            assert!(is_crouch_collider(&app));
+        }
+
+        // Out from under it
+        let clear = Vec3::X * 5.;
+        app.world
+            .entity_mut(player)
+            .insert((Position(clear), Transform::from_translation(clear)));
+        for _ in 0..3 {
+            app.update();
         }
+        let shape = app.world.get::<CrouchShape>(player).unwrap();
+        assert!(shape.headroom);
+        assert!(!shape.crouched);
+        assert!(!is_crouch_collider(&app));
     }
 }
@@ -1,14 +1,55 @@
 use bevy::app::{PluginGroup, PluginGroupBuilder};
 
+use crate::breakable::BreakablePlugin;
+use crate::carry::CarryPlugin;
+use crate::collectible::CollectiblePlugin;
+use crate::enemy::EnemyPlugin;
+use crate::environment::EnvironmentPlugin;
+use crate::game::GamePlugin;
+use crate::gravity::GravityPlugin;
+use crate::health::HealthPlugin;
+use crate::hud::HudPlugin;
+use crate::input::PlayerInputPlugin;
+use crate::interaction::InteractionPlugin;
+use crate::lod::LodPlugin;
+use crate::mechanism::MechanismPlugin;
+use crate::platform::PlatformPlugin;
+use crate::replay::ReplayPlugin;
 use crate::terrain::TerrainPlugin;
 use crate::player::PlayerPlugin;
+use crate::save::SavePlugin;
+use crate::sound::SoundPlugin;
+use crate::speedrun::SpeedrunPlugin;
+use crate::zone::ZonePlugin;
 
 pub struct ReginoPlugins;
 
 impl PluginGroup for ReginoPlugins {
     fn build(self) -> PluginGroupBuilder {
-        PluginGroupBuilder::start::<ReginoPlugins>()
+        let group = PluginGroupBuilder::start::<ReginoPlugins>()
+            .add(GamePlugin)
             .add(TerrainPlugin)
+            .add(EnvironmentPlugin)
+            .add(LodPlugin)
             .add(PlayerPlugin)
+            .add(PlayerInputPlugin)
+            .add(ReplayPlugin)
+            .add(PlatformPlugin)
+            .add(ZonePlugin)
+            .add(GravityPlugin)
+            .add(SavePlugin)
+            .add(InteractionPlugin)
+            .add(CarryPlugin)
+            .add(BreakablePlugin)
+            .add(MechanismPlugin)
+            .add(HealthPlugin)
+            .add(CollectiblePlugin)
+            .add(EnemyPlugin)
+            .add(SpeedrunPlugin)
+            .add(HudPlugin)
+            .add(SoundPlugin);
+        #[cfg(feature = "debug")]
+        let group = group.add(crate::debug::DebugPlugin);
+        group
     }
 }
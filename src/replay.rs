@@ -0,0 +1,781 @@
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy_tnua::TnuaUserControlsSystemSet;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::GameState;
+use crate::input::Action;
+use crate::player::{
+    animate_sprites, latch_presses, tick_input, CameraTarget, Player, PlayerAnimations,
+    PlayerDimensions, PlayerSlot, SpriteAnimator,
+};
+use crate::terrain::{CurrentLevel, LoadLevelEvent};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputReplay>()
+            .init_resource::<RecordingSelection>()
+            .add_event::<StartRecordingEvent>()
+            .add_event::<StopRecordingEvent>()
+            .add_event::<StartReplayEvent>()
+            .add_event::<ReplayDesyncEvent>()
+            .add_systems(
+                Update,
+                (replay_keys, start_recording, stop_recording, start_replay).chain(),
+            )
+            // Ahead of the movement reading the inputs on the same tick, and
+            // of the state machine noting them
+            .add_systems(
+                FixedUpdate,
+                (
+                    record_input.after(tick_input),
+                    replay_input.after(tick_input).before(latch_presses),
+                )
+                    .before(TnuaUserControlsSystemSet)
+                    .run_if(in_state(GameState::Playing)),
+            );
+        build_ghost(app);
+    }
+}
+
+/// Where recordings are written to, one file per run named after the level
+/// and the time it was recorded.
+pub const REPLAY_DIR: &str = "replays";
+
+const TOGGLE_RECORDING_KEY: KeyCode = KeyCode::F6;
+const START_REPLAY_KEY: KeyCode = KeyCode::F7;
+const NEXT_RECORDING_KEY: KeyCode = KeyCode::F8;
+
+/// Restarts the current level and records the players' inputs from their spawn.
+#[derive(Event, Clone, Debug)]
+pub struct StartRecordingEvent;
+
+/// Ends the recording and writes it out.
+#[derive(Event, Clone, Debug)]
+pub struct StopRecordingEvent;
+
+/// Restarts the recorded level and plays the recording at `path` back in
+/// place of the players' devices.
+#[derive(Event, Clone, Debug)]
+pub struct StartReplayEvent {
+    pub path: String,
+}
+
+/// A replayed player first strayed further than `DESYNC_DISTANCE` from where
+/// the recording had it at the start of `tick`. The replay goes on regardless.
+#[derive(Event, Clone, Debug)]
+pub struct ReplayDesyncEvent {
+    pub tick: usize,
+    pub slot: usize,
+    pub recorded: Vec3,
+    pub replayed: Vec3,
+}
+
+/// Further off than this and the replay is taken to have gone its own way.
+const DESYNC_DISTANCE: f32 = 0.01;
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReplayMode {
+    #[default]
+    Off,
+    Recording,
+    Replaying,
+}
+
+/// Inputs of every player by fixed tick, from the first tick they were
+/// spawned on. Both recording and replay restart the level, so the ticks line
+/// up with the same starting state.
+#[derive(Resource, Default, Debug)]
+pub struct InputReplay {
+    pub mode: ReplayMode,
+    level: String,
+    ticks: Vec<ReplayTick>,
+    /// Next tick to play back
+    cursor: usize,
+    /// Whether the restarted level has spawned its players yet
+    started: bool,
+    /// First tick a player was found off its recorded position
+    pub desynced_at: Option<usize>,
+}
+
+impl InputReplay {
+    // The players of the level before the restart may still be around for a tick
+    fn wait_for_spawn(&mut self, spawned: bool) -> bool {
+        self.started |= spawned;
+        !self.started
+    }
+}
+
+/// Action states of a tick and where that left the players, by `PlayerSlot`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ReplayTick {
+    pub inputs: Vec<ActionState<Action>>,
+    pub positions: Vec<[f32; 3]>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ReplayFile {
+    pub level: String,
+    pub ticks: Vec<ReplayTick>,
+}
+
+pub fn read_replay(path: &str) -> Result<ReplayFile, String> {
+    let ron = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    ron::from_str(&ron).map_err(|e| e.to_string())
+}
+
+fn level_name(level: &str) -> &str {
+    Path::new(level)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(level)
+}
+
+fn recording_path(level: &str) -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    format!("{REPLAY_DIR}/{}-{secs}.ron", level_name(level))
+}
+
+/// Recordings made in `level`, the latest first.
+pub fn list_recordings(level: &str) -> Vec<String> {
+    let prefix = format!("{}-", level_name(level));
+    let Ok(entries) = std::fs::read_dir(REPLAY_DIR) else {
+        return vec![];
+    };
+    let mut recordings = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.starts_with(&prefix) && name.ends_with(".ron"))
+        .map(|name| format!("{REPLAY_DIR}/{name}"))
+        .collect::<Vec<_>>();
+    recordings.sort_unstable_by(|a, b| b.cmp(a));
+    recordings
+}
+
+/// Recording picked to replay or race as a ghost, from those made in the
+/// current level.
+#[derive(Resource, Default, Debug)]
+pub struct RecordingSelection {
+    pub recordings: Vec<String>,
+    pub selected: Option<usize>,
+}
+
+impl RecordingSelection {
+    pub fn path(&self) -> Option<&str> {
+        self.selected
+            .and_then(|i| self.recordings.get(i))
+            .map(String::as_str)
+    }
+
+    /// Lists the recordings again and moves on to the one after the selected,
+    /// wrapping around to the latest.
+    fn select_next(&mut self, level: &str) -> Option<&str> {
+        let previous = self.path().map(str::to_string);
+        self.recordings = list_recordings(level);
+        let next = previous
+            .and_then(|previous| self.recordings.iter().position(|path| *path == previous))
+            .map_or(0, |i| i + 1);
+        self.selected = (!self.recordings.is_empty()).then(|| next % self.recordings.len());
+        self.path()
+    }
+}
+
+// F8 goes through the recordings of the level, loading each as the ghost
+fn replay_keys(
+    keys: Res<Input<KeyCode>>,
+    replay: Res<InputReplay>,
+    mut selection: ResMut<RecordingSelection>,
+    level: Res<CurrentLevel>,
+    (mut start_recording, mut stop_recording): (
+        EventWriter<StartRecordingEvent>,
+        EventWriter<StopRecordingEvent>,
+    ),
+    mut start_replay: EventWriter<StartReplayEvent>,
+    mut load_ghost: EventWriter<LoadGhostEvent>,
+) {
+    if keys.just_pressed(TOGGLE_RECORDING_KEY) {
+        if replay.mode == ReplayMode::Recording {
+            stop_recording.send(StopRecordingEvent);
+        } else {
+            start_recording.send(StartRecordingEvent);
+        }
+    }
+    if keys.just_pressed(START_REPLAY_KEY) {
+        // The latest, unless one was picked
+        let path = match selection.path() {
+            Some(path) => Some(path.to_string()),
+            None => list_recordings(&level.0).into_iter().next(),
+        };
+        match path {
+            Some(path) => start_replay.send(StartReplayEvent { path }),
+            None => info!("No recordings of {} to replay", level.0),
+        }
+    }
+    if keys.just_pressed(NEXT_RECORDING_KEY) {
+        match selection.select_next(&level.0) {
+            Some(path) => load_ghost.send(LoadGhostEvent {
+                path: path.to_string(),
+            }),
+            None => info!("No recordings of {} to race", level.0),
+        }
+    }
+}
+
+fn start_recording(
+    mut start: EventReader<StartRecordingEvent>,
+    mut replay: ResMut<InputReplay>,
+    level: Res<CurrentLevel>,
+    mut load: EventWriter<LoadLevelEvent>,
+) {
+    if start.read().last().is_none() {
+        return;
+    }
+    *replay = InputReplay {
+        mode: ReplayMode::Recording,
+        level: level.0.clone(),
+        ..default()
+    };
+    load.send(LoadLevelEvent {
+        path: level.0.clone(),
+        spawn: None,
+    });
+}
+
+// The new recording is selected, to be replayed or raced right away
+fn stop_recording(
+    mut stop: EventReader<StopRecordingEvent>,
+    mut replay: ResMut<InputReplay>,
+    mut selection: ResMut<RecordingSelection>,
+) {
+    if stop.read().last().is_none() || replay.mode != ReplayMode::Recording {
+        return;
+    }
+    replay.mode = ReplayMode::Off;
+
+    let path = recording_path(&replay.level);
+    let file = ReplayFile {
+        level: replay.level.clone(),
+        ticks: std::mem::take(&mut replay.ticks),
+    };
+    // A tick is a line's worth, without the pretty printing
+    let result = ron::ser::to_string(&file)
+        .map_err(|e| e.to_string())
+        .and_then(|ron| {
+            std::fs::create_dir_all(REPLAY_DIR)
+                .and_then(|()| std::fs::write(&path, ron))
+                .map_err(|e| e.to_string())
+        });
+    match result {
+        Ok(()) => {
+            info!("Recorded {} ticks to {path}", file.ticks.len());
+            selection.recordings = list_recordings(&file.level);
+            selection.selected = selection.recordings.iter().position(|p| *p == path);
+        }
+        Err(e) => error!("Failed to save {path}: {e}"),
+    }
+}
+
+fn start_replay(
+    mut start: EventReader<StartReplayEvent>,
+    mut replay: ResMut<InputReplay>,
+    mut load: EventWriter<LoadLevelEvent>,
+) {
+    let Some(StartReplayEvent { path }) = start.read().last() else {
+        return;
+    };
+    let file = match read_replay(path) {
+        Ok(file) => file,
+        Err(e) => {
+            error!("Failed to load {path}: {e}");
+            return;
+        }
+    };
+
+    load.send(LoadLevelEvent {
+        path: file.level.clone(),
+        spawn: None,
+    });
+    *replay = InputReplay {
+        mode: ReplayMode::Replaying,
+        level: file.level,
+        ticks: file.ticks,
+        ..default()
+    };
+}
+
+fn record_input(
+    mut replay: ResMut<InputReplay>,
+    player: Query<(&ActionState<Action>, &GlobalTransform, Option<&PlayerSlot>), With<Player>>,
+    spawned: Query<(), Added<Player>>,
+) {
+    if replay.mode != ReplayMode::Recording || replay.wait_for_spawn(!spawned.is_empty()) {
+        return;
+    }
+    let mut players = player.iter().collect::<Vec<_>>();
+    players.sort_by_key(|(_, _, slot)| slot.map_or(0, |slot| slot.0));
+    // Global, as the player is parented to a rope while climbing it
+    replay.ticks.push(ReplayTick {
+        inputs: players.iter().map(|(state, ..)| (*state).clone()).collect(),
+        positions: players
+            .iter()
+            .map(|(_, transform, _)| transform.translation().to_array())
+            .collect(),
+    });
+}
+
+// The devices are switched off for the length of the replay, so neither they
+// nor the pause menu coming back turn the recorded states over
+fn replay_input(
+    mut replay: ResMut<InputReplay>,
+    mut player: Query<
+        (
+            &mut ActionState<Action>,
+            &GlobalTransform,
+            Option<&PlayerSlot>,
+        ),
+        With<Player>,
+    >,
+    mut actions: ResMut<ToggleActions<Action>>,
+    spawned: Query<(), Added<Player>>,
+    mut desync: EventWriter<ReplayDesyncEvent>,
+) {
+    if replay.mode != ReplayMode::Replaying || replay.wait_for_spawn(!spawned.is_empty()) {
+        return;
+    }
+    let index = replay.cursor;
+    let Some(tick) = replay.ticks.get(index).cloned() else {
+        info!("Replay finished");
+        replay.mode = ReplayMode::Off;
+        actions.enabled = true;
+        return;
+    };
+    replay.cursor += 1;
+
+    if actions.enabled {
+        actions.enabled = false;
+    }
+    for (mut state, transform, slot) in player.iter_mut() {
+        let slot = slot.map_or(0, |slot| slot.0);
+        // Recorded at the same point of the tick, before its inputs moved anyone
+        if let Some(recorded) = tick.positions.get(slot).copied().map(Vec3::from_array) {
+            let replayed = transform.translation();
+            let off = recorded.distance(replayed);
+            if replay.desynced_at.is_none() && off > DESYNC_DISTANCE {
+                warn!("Replay out of sync from tick {index}, player {slot} is {off} off");
+                replay.desynced_at = Some(index);
+                desync.send(ReplayDesyncEvent {
+                    tick: index,
+                    slot,
+                    recorded,
+                    replayed,
+                });
+            }
+        }
+        if let Some(recorded) = tick.inputs.get(slot) {
+            *state = recorded.clone();
+        }
+    }
+}
+
+// Ghost
+
+fn build_ghost(app: &mut App) {
+    app.init_resource::<GhostConfig>()
+        .add_event::<LoadGhostEvent>()
+        .add_systems(
+            Update,
+            (
+                load_ghost,
+                add_ghost_sprite,
+                toggle_ghost,
+                restart_ghost,
+                update_ghost,
+                animate_ghost,
+            )
+                .chain()
+                .before(animate_sprites),
+        )
+        // Ticks along with the recording, which was made a fixed tick at a time
+        .add_systems(FixedUpdate, step_ghost.run_if(in_state(GameState::Playing)));
+}
+
+const TOGGLE_GHOST_KEY: KeyCode = KeyCode::G;
+
+/// Translucent copy of the player retracing a recorded run, to race against.
+#[derive(Resource, Debug, Clone)]
+pub struct GhostConfig {
+    pub visible: bool,
+    /// Alpha of the ghost's sprite
+    pub opacity: f32,
+}
+
+impl Default for GhostConfig {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            opacity: 0.4,
+        }
+    }
+}
+
+/// Replaces the ghost with the first player of the recording at `path`.
+#[derive(Event, Clone, Debug)]
+pub struct LoadGhostEvent {
+    pub path: String,
+}
+
+/// Goes through the recorded positions, starting over each time the live
+/// player spawns. Only shown in the level it was recorded in.
+#[derive(Component, Debug)]
+pub struct GhostPlayer {
+    level: String,
+    positions: Vec<Vec3>,
+    tick: usize,
+}
+
+impl GhostPlayer {
+    fn position(&self) -> Option<Vec3> {
+        self.positions.get(self.tick).copied()
+    }
+
+    /// Per second, over the last tick.
+    fn velocity(&self, timestep: f32) -> Vec3 {
+        let previous = self.tick.checked_sub(1).and_then(|t| self.positions.get(t));
+        match (previous, self.position()) {
+            (Some(previous), Some(current)) => (current - *previous) / timestep,
+            _ => Vec3::ZERO,
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct GhostSprite {
+    facing_left: bool,
+}
+
+fn load_ghost(
+    mut commands: Commands,
+    mut load: EventReader<LoadGhostEvent>,
+    ghosts: Query<Entity, With<GhostPlayer>>,
+) {
+    let Some(LoadGhostEvent { path }) = load.read().last() else {
+        return;
+    };
+    let replay = match read_replay(path) {
+        Ok(replay) => replay,
+        Err(e) => {
+            error!("Failed to load ghost from {path}: {e}");
+            return;
+        }
+    };
+
+    for entity in ghosts.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let positions = replay
+        .ticks
+        .iter()
+        .filter_map(|tick| tick.positions.first())
+        .map(|&position| Vec3::from_array(position))
+        .collect();
+    commands.spawn((
+        Name::new("GhostPlayer"),
+        GhostPlayer {
+            level: replay.level,
+            positions,
+            tick: 0,
+        },
+        SpatialBundle {
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+}
+
+fn add_ghost_sprite(
+    mut commands: Commands,
+    // Waits for the atlas to be built, rather than only the frame it's added
+    ghosts: Query<Entity, (With<GhostPlayer>, Without<Children>)>,
+    animations: Res<PlayerAnimations>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<GhostConfig>,
+    dimensions: Res<PlayerDimensions>,
+) {
+    if !animations.is_ready() {
+        return;
+    }
+    for entity in ghosts.iter() {
+        // A translucent copy of the player's material
+        let Some(mut material) = materials.get(animations.material()).cloned() else {
+            continue;
+        };
+        material.alpha_mode = AlphaMode::Blend;
+        material.base_color.set_a(config.opacity);
+
+        let mesh = Mesh::from(shape::Quad::new(dimensions.sprite_size()));
+        commands.entity(entity).with_children(|builder| {
+            builder.spawn((
+                Name::new("GhostSprite"),
+                GhostSprite { facing_left: false },
+                SpriteAnimator::default(),
+                NotShadowCaster,
+                PbrBundle {
+                    mesh: meshes.add(mesh),
+                    material: materials.add(material),
+                    ..default()
+                },
+            ));
+        });
+    }
+}
+
+fn toggle_ghost(keys: Res<Input<KeyCode>>, mut config: ResMut<GhostConfig>) {
+    if keys.just_pressed(TOGGLE_GHOST_KEY) {
+        config.visible = !config.visible;
+    }
+}
+
+// The recording starts from the spawn as well
+fn restart_ghost(spawned: Query<(), Added<Player>>, mut ghosts: Query<&mut GhostPlayer>) {
+    if spawned.is_empty() {
+        return;
+    }
+    for mut ghost in ghosts.iter_mut() {
+        ghost.tick = 0;
+    }
+}
+
+// Waits at the end of the run once it's through
+fn step_ghost(mut ghosts: Query<&mut GhostPlayer>, player: Query<(), With<Player>>) {
+    if player.is_empty() {
+        return;
+    }
+    for mut ghost in ghosts.iter_mut() {
+        if ghost.tick + 1 < ghost.positions.len() {
+            ghost.tick += 1;
+        }
+    }
+}
+
+fn update_ghost(
+    mut ghosts: Query<(&GhostPlayer, &mut Transform, &mut Visibility)>,
+    player: Query<(), With<Player>>,
+    config: Res<GhostConfig>,
+    level: Res<CurrentLevel>,
+) {
+    for (ghost, mut transform, mut visibility) in ghosts.iter_mut() {
+        let position = ghost.position();
+        let shown = config.visible && !player.is_empty() && ghost.level == level.0;
+        visibility.set_if_neq(if shown && position.is_some() {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+        if let Some(position) = position {
+            transform.translation = position;
+        }
+    }
+}
+
+/// Speed above which the ghost is drawn jumping or falling, as it's only
+/// known where it went and not whether it was on the ground.
+const GHOST_AIRBORNE_SPEED: f32 = 1.;
+
+// Picks the animation from the recorded motion alone, `animate_sprites`
+// plays it like the player's
+fn animate_ghost(
+    ghosts: Query<&GhostPlayer>,
+    mut sprites: Query<(
+        &Parent,
+        &mut Transform,
+        &mut GhostSprite,
+        &mut SpriteAnimator,
+    )>,
+    cameras: Query<&GlobalTransform, With<CameraTarget>>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    // Faces the first player's camera in split-screen
+    let Some(camera) = cameras.iter().next() else {
+        return;
+    };
+    for (parent, mut transform, mut sprite, mut animator) in sprites.iter_mut() {
+        let Ok(ghost) = ghosts.get(parent.get()) else {
+            continue;
+        };
+        let Some(position) = ghost.position() else {
+            continue;
+        };
+        let velocity = ghost.velocity(fixed_time.timestep().as_secs_f32());
+
+        let sideways = velocity.dot(camera.right());
+        if sideways.abs() > 0.1 {
+            sprite.facing_left = sideways < 0.;
+        }
+        let to_camera = camera.translation() - position;
+        transform.rotation = Quat::from_rotation_y(f32::atan2(to_camera.x, to_camera.z));
+        transform.scale.x = if sprite.facing_left { -1. } else { 1. };
+
+        let name = if velocity.y > GHOST_AIRBORNE_SPEED {
+            "jump"
+        } else if velocity.y < -GHOST_AIRBORNE_SPEED {
+            "fall"
+        } else if velocity.xz().length() > 0.1 {
+            "run"
+        } else {
+            "idle"
+        };
+        animator.play(name, 1.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::scene::ScenePlugin;
+    use bevy::time::TimeUpdateStrategy;
+    use bevy_tnua::builtins::TnuaBuiltinWalk;
+    use bevy_tnua::controller::{TnuaController, TnuaControllerBundle, TnuaControllerPlugin};
+    use bevy_tnua_xpbd3d::TnuaXpbd3dPlugin;
+    use bevy_xpbd_3d::prelude::*;
+
+    use super::*;
+
+    const TICKS: usize = 90;
+
+    // Stands in for the player's movement, walking by the direction keys
+    fn walk(mut player: Query<(&ActionState<Action>, &mut TnuaController)>) {
+        let dimensions = PlayerDimensions::default();
+        for (input, mut controller) in player.iter_mut() {
+            let key = |action| if input.pressed(action) { 1. } else { 0. };
+            let direction = Vec3::new(
+                key(Action::Right) - key(Action::Left),
+                0.,
+                key(Action::Down) - key(Action::Up),
+            );
+            controller.basis(TnuaBuiltinWalk {
+                desired_velocity: direction * 4.,
+                float_height: dimensions.float_height(),
+                ..default()
+            });
+        }
+    }
+
+    // Right for half a second, then away from the camera
+    fn press_keys(mut player: Query<&mut ActionState<Action>>, mut tick: Local<usize>) {
+        for mut state in player.iter_mut() {
+            *state = ActionState::default();
+            state.press(if *tick < 32 {
+                Action::Right
+            } else {
+                Action::Up
+            });
+        }
+        *tick += 1;
+    }
+
+    fn replay_app(replay: InputReplay, start: Vec3) -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            AssetPlugin::default(),
+            ScenePlugin,
+            PhysicsPlugins::default(),
+            TnuaXpbd3dPlugin,
+            TnuaControllerPlugin,
+        ))
+        .init_asset::<Mesh>()
+        // A fixed tick every frame
+        .insert_resource(TimeUpdateStrategy::ManualDuration(
+            Time::<Fixed>::default().timestep(),
+        ))
+        .insert_resource(replay)
+        .init_resource::<ToggleActions<Action>>()
+        .add_event::<ReplayDesyncEvent>()
+        .add_systems(FixedUpdate, (record_input, replay_input, walk).chain());
+
+        app.world.spawn((
+            RigidBody::Static,
+            Collider::cuboid(40., 0.5, 40.),
+            TransformBundle::default(),
+            Position(Vec3::ZERO),
+        ));
+        let dimensions = PlayerDimensions::default();
+        let start = start + Vec3::Y * (0.25 + dimensions.float_height());
+        let player = app
+            .world
+            .spawn((
+                Player,
+                ActionState::<Action>::default(),
+                dimensions.collider(),
+                RigidBody::Dynamic,
+                LockedAxes::new().lock_rotation_x().lock_rotation_z(),
+                TnuaControllerBundle::default(),
+                TransformBundle::from_transform(Transform::from_translation(start)),
+                Position(start),
+            ))
+            .id();
+        (app, player)
+    }
+
+    fn replay_positions(ticks: &[ReplayTick], start: Vec3) -> (Vec<Vec3>, Option<usize>) {
+        let (mut app, player) = replay_app(
+            InputReplay {
+                mode: ReplayMode::Replaying,
+                ticks: ticks.to_vec(),
+                ..default()
+            },
+            start,
+        );
+        let positions = (0..TICKS)
+            .map(|_| {
+                app.update();
+                app.world.get::<Position>(player).unwrap().0
+            })
+            .collect();
+        (positions, app.world.resource::<InputReplay>().desynced_at)
+    }
+
+    #[test]
+    fn replays_the_same_way_twice() {
+        let (mut app, _) = replay_app(
+            InputReplay {
+                mode: ReplayMode::Recording,
+                ..default()
+            },
+            Vec3::ZERO,
+        );
+        app.add_systems(FixedUpdate, press_keys.before(record_input));
+        for _ in 0..TICKS * 2 {
+            if app.world.resource::<InputReplay>().ticks.len() >= TICKS {
+                break;
+            }
+            app.update();
+        }
+        let ticks = std::mem::take(&mut app.world.resource_mut::<InputReplay>().ticks);
+        assert_eq!(ticks.len(), TICKS);
+
+        let (first, first_desync) = replay_positions(&ticks, Vec3::ZERO);
+        let (second, second_desync) = replay_positions(&ticks, Vec3::ZERO);
+        assert_eq!(first_desync, None);
+        assert_eq!(second_desync, None);
+        assert_eq!(first, second);
+        // It went somewhere, right and then away
+        let end = *first.last().unwrap();
+        assert!(end.x > 1. && end.z < -1., "ended up at {end}");
+
+        // Started off somewhere else, it's out of sync as soon as it's placed
+        let (_, desync) = replay_positions(&ticks, Vec3::X);
+        assert!(desync.is_some_and(|tick| tick <= 1), "desync at {desync:?}");
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeSet;
+
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::player::{Player, PlayerRespawn};
+use crate::zone::ActivatedCheckpoints;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveSlot>()
+            .init_resource::<CollectedItems>()
+            .add_event::<SaveGameEvent>()
+            .add_event::<LoadGameEvent>()
+            .add_systems(Startup, load_on_startup)
+            .add_systems(Update, (save_game, load_game, restore_player).chain());
+    }
+}
+
+/// Which save file `SaveGameEvent` and `LoadGameEvent` use.
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct SaveSlot(pub u32);
+
+impl SaveSlot {
+    fn path(&self) -> String {
+        format!("save{}.ron", self.0)
+    }
+}
+
+/// Names of the items picked up so far.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct CollectedItems(pub BTreeSet<String>);
+
+#[derive(Event, Clone, Debug)]
+pub struct SaveGameEvent;
+
+#[derive(Event, Clone, Debug)]
+pub struct LoadGameEvent;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+struct SavedTransform {
+    translation: [f32; 3],
+    rotation: [f32; 4],
+}
+
+impl From<&Transform> for SavedTransform {
+    fn from(transform: &Transform) -> Self {
+        Self {
+            translation: transform.translation.to_array(),
+            rotation: transform.rotation.to_array(),
+        }
+    }
+}
+
+impl From<SavedTransform> for Transform {
+    fn from(saved: SavedTransform) -> Self {
+        Transform::from_translation(Vec3::from_array(saved.translation))
+            .with_rotation(Quat::from_array(saved.rotation).normalize())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct SaveData {
+    player: Option<SavedTransform>,
+    respawn: Option<SavedTransform>,
+    checkpoints: BTreeSet<u32>,
+    current_checkpoint: Option<u32>,
+    collected: BTreeSet<String>,
+}
+
+/// Loaded transforms waiting for the player to be spawned by the level.
+#[derive(Resource)]
+struct PendingRestore {
+    player: Option<Transform>,
+    respawn: Option<Transform>,
+}
+
+fn load_on_startup(mut load: EventWriter<LoadGameEvent>) {
+    load.send(LoadGameEvent);
+}
+
+fn save_game(
+    mut save: EventReader<SaveGameEvent>,
+    slot: Res<SaveSlot>,
+    player: Query<&Transform, With<Player>>,
+    respawn: Option<Res<PlayerRespawn>>,
+    checkpoints: Res<ActivatedCheckpoints>,
+    collected: Res<CollectedItems>,
+) {
+    if save.read().count() == 0 {
+        return;
+    }
+
+    let data = SaveData {
+        player: player.get_single().ok().map(SavedTransform::from),
+        respawn: respawn.map(|respawn| SavedTransform::from(&respawn.transform)),
+        checkpoints: checkpoints.activated.clone(),
+        current_checkpoint: checkpoints.current,
+        collected: collected.0.clone(),
+    };
+
+    let path = slot.path();
+    let result = ron::ser::to_string_pretty(&data, ron::ser::PrettyConfig::default())
+        .map_err(|e| e.to_string())
+        .and_then(|ron| std::fs::write(&path, ron).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => info!("Saved game to {path}"),
+        Err(e) => error!("Failed to save {path}: {e}"),
+    }
+}
+
+fn load_game(
+    mut commands: Commands,
+    mut load: EventReader<LoadGameEvent>,
+    slot: Res<SaveSlot>,
+    mut checkpoints: ResMut<ActivatedCheckpoints>,
+    mut collected: ResMut<CollectedItems>,
+) {
+    if load.read().count() == 0 {
+        return;
+    }
+
+    // A missing or broken save leaves the game as a fresh start
+    let path = slot.path();
+    let Ok(ron) = std::fs::read_to_string(&path) else {
+        info!("No save at {path}, starting fresh");
+        return;
+    };
+    let data: SaveData = match ron::from_str(&ron) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Failed to parse {path}, starting fresh: {e}");
+            return;
+        }
+    };
+
+    checkpoints.activated = data.checkpoints;
+    checkpoints.current = data.current_checkpoint;
+    collected.0 = data.collected;
+    commands.insert_resource(PendingRestore {
+        player: data.player.map(Transform::from),
+        respawn: data.respawn.map(Transform::from),
+    });
+}
+
+fn restore_player(
+    mut commands: Commands,
+    pending: Option<Res<PendingRestore>>,
+    mut player: Query<(&mut Transform, Option<&mut Position>), With<Player>>,
+) {
+    let Some(pending) = pending else {
+        return;
+    };
+    // The player only exists once the level has spawned it
+    let Ok((mut transform, position)) = player.get_single_mut() else {
+        return;
+    };
+
+    if let Some(saved) = pending.player {
+        *transform = saved;
+        if let Some(mut position) = position {
+            position.0 = saved.translation;
+        }
+    }
+    // Overrides the spawn point recorded when the level spawned the player
+    if let Some(respawn) = pending.respawn {
+        commands.insert_resource(PlayerRespawn { transform: respawn });
+    }
+    commands.remove_resource::<PendingRestore>();
+}
@@ -0,0 +1,509 @@
+use bevy::asset::LoadState;
+use bevy::audio::{PlaybackMode, Volume};
+use bevy::prelude::*;
+
+use crate::game::GameState;
+use crate::interaction::InteractEvent;
+use crate::player::{FootstepEvent, PlayerFollowingCamera, PlayerJumpedEvent, PlayerLandedEvent};
+use crate::terrain::{Ladder, SurfaceKind};
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<FootstepSound>()
+            .register_type::<AudioEmitter>()
+            .init_resource::<MasterVolume>()
+            .init_resource::<MusicPlayer>()
+            .add_event::<PlayMusic>()
+            .add_event::<StopMusic>()
+            .add_systems(Startup, load_sound_effects)
+            .add_systems(
+                Update,
+                (
+                    init_footstep_sound,
+                    (play_footsteps, play_jump, play_landing),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    add_listener,
+                    play_ladder_creak,
+                    (init_emitter, update_emitter).chain(),
+                ),
+            )
+            .add_systems(OnEnter(GameState::MainMenu), play_menu_music)
+            .add_systems(OnEnter(GameState::Playing), play_gameplay_music)
+            .add_systems(Update, (switch_music, fade_music).chain());
+    }
+}
+
+/// Volume every sound is played at, from 0 (muted) to 1.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct MasterVolume(pub f32);
+
+impl Default for MasterVolume {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+impl MasterVolume {
+    pub fn settings(&self) -> PlaybackSettings {
+        PlaybackSettings::DESPAWN.with_volume(Volume::new_relative(self.0))
+    }
+}
+
+/// Footstep clip for walking on this node instead of the one of its
+/// `SurfaceKind`, e.g. "sounds/grass.ogg".
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct FootstepSound {
+    pub clip: String,
+}
+
+/// Handle to the clip of `FootstepSound`, kept so it stays loaded.
+#[derive(Component)]
+struct FootstepClip(Handle<AudioSource>);
+
+/// Whether `source` can be played yet. Clips still loading, or missing from
+/// `assets/`, are skipped rather than spawned, as the entity of a clip that
+/// never loads never despawns either.
+fn is_loaded(sources: &Assets<AudioSource>, source: &Handle<AudioSource>) -> bool {
+    sources.contains(source.id())
+}
+
+#[derive(Resource)]
+struct SoundEffects {
+    footstep: Handle<AudioSource>,
+    surface_footsteps: Vec<(SurfaceKind, Handle<AudioSource>)>,
+    jump: Handle<AudioSource>,
+    land: Handle<AudioSource>,
+    ladder_creak: Handle<AudioSource>,
+}
+
+fn load_sound_effects(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(SoundEffects {
+        footstep: assets.load("sounds/footstep.ogg"),
+        surface_footsteps: [
+            (SurfaceKind::Grass, "sounds/footstep_grass.ogg"),
+            (SurfaceKind::Stone, "sounds/footstep_stone.ogg"),
+            (SurfaceKind::Wood, "sounds/footstep_wood.ogg"),
+            (SurfaceKind::Ice, "sounds/footstep_ice.ogg"),
+            (SurfaceKind::Mud, "sounds/footstep_mud.ogg"),
+        ]
+        .into_iter()
+        .map(|(kind, path)| (kind, assets.load(path)))
+        .collect(),
+        jump: assets.load("sounds/jump.ogg"),
+        land: assets.load("sounds/land.ogg"),
+        ladder_creak: assets.load("sounds/ladder_creak.ogg"),
+    });
+}
+
+impl SoundEffects {
+    fn footstep_for(&self, kind: SurfaceKind) -> Handle<AudioSource> {
+        self.surface_footsteps
+            .iter()
+            .find(|(surface, _)| *surface == kind)
+            .map_or_else(|| self.footstep.clone(), |(_, clip)| clip.clone())
+    }
+}
+
+fn play_sound(
+    commands: &mut Commands,
+    source: Handle<AudioSource>,
+    volume: &MasterVolume,
+    sources: &Assets<AudioSource>,
+) {
+    if !is_loaded(sources, &source) {
+        return;
+    }
+    commands.spawn(AudioBundle {
+        source,
+        settings: volume.settings(),
+    });
+}
+
+fn init_footstep_sound(
+    mut commands: Commands,
+    surfaces: Query<(Entity, &FootstepSound), Added<FootstepSound>>,
+    assets: Res<AssetServer>,
+) {
+    for (entity, surface) in surfaces.iter() {
+        commands
+            .entity(entity)
+            .insert(FootstepClip(assets.load(&surface.clip)));
+    }
+}
+
+fn play_footsteps(
+    mut commands: Commands,
+    mut footstep: EventReader<FootstepEvent>,
+    surfaces: Query<&FootstepClip>,
+    parents: Query<&Parent>,
+    effects: Res<SoundEffects>,
+    volume: Res<MasterVolume>,
+    sources: Res<Assets<AudioSource>>,
+) {
+    for ev in footstep.read() {
+        // The collider may sit on a mesh below the node carrying the component
+        let clip = ev.ground.and_then(|ground| {
+            std::iter::once(ground)
+                .chain(parents.iter_ancestors(ground))
+                .find_map(|entity| surfaces.get(entity).ok())
+        });
+        let source = match clip {
+            Some(clip) => clip.0.clone(),
+            None => effects.footstep_for(ev.surface),
+        };
+        play_sound(&mut commands, source, &volume, &sources);
+    }
+}
+
+fn play_jump(
+    mut commands: Commands,
+    mut jumped: EventReader<PlayerJumpedEvent>,
+    effects: Res<SoundEffects>,
+    volume: Res<MasterVolume>,
+    sources: Res<Assets<AudioSource>>,
+) {
+    for _ in jumped.read() {
+        play_sound(&mut commands, effects.jump.clone(), &volume, &sources);
+    }
+}
+
+fn play_landing(
+    mut commands: Commands,
+    mut landed: EventReader<PlayerLandedEvent>,
+    effects: Res<SoundEffects>,
+    volume: Res<MasterVolume>,
+    sources: Res<Assets<AudioSource>>,
+) {
+    for _ in landed.read() {
+        play_sound(&mut commands, effects.land.clone(), &volume, &sources);
+    }
+}
+
+// Positional audio
+
+/// Gap between the ears, in world units.
+const LISTENER_EAR_GAP: f32 = 0.3;
+
+// Spatial audio takes a single listener, so split-screen hears from the first camera
+fn add_listener(
+    mut commands: Commands,
+    camera: Query<Entity, Added<PlayerFollowingCamera>>,
+    listener: Query<(), With<SpatialListener>>,
+) {
+    if !listener.is_empty() {
+        return;
+    }
+    if let Some(entity) = camera.iter().next() {
+        commands
+            .entity(entity)
+            .insert(SpatialListener::new(LISTENER_EAR_GAP));
+    }
+}
+
+fn play_ladder_creak(
+    mut commands: Commands,
+    mut interact: EventReader<InteractEvent>,
+    ladders: Query<(), With<Ladder>>,
+    effects: Res<SoundEffects>,
+    volume: Res<MasterVolume>,
+    sources: Res<Assets<AudioSource>>,
+) {
+    for ev in interact.read() {
+        if !ladders.contains(ev.target) || !is_loaded(&sources, &effects.ladder_creak) {
+            continue;
+        }
+        commands.spawn((
+            AudioBundle {
+                source: effects.ladder_creak.clone(),
+                settings: volume.settings().with_spatial(true),
+            },
+            TransformBundle::from_transform(Transform::from_translation(ev.point)),
+        ));
+    }
+}
+
+/// Sound playing around the glTF node, e.g. a waterfall or machinery.
+/// Only heard within `range` of the listener, fading out towards it.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct AudioEmitter {
+    pub clip: String,
+    pub looping: bool,
+    pub range: f32,
+}
+
+#[derive(Component)]
+struct EmitterPlayback {
+    clip: Handle<AudioSource>,
+    /// Child entity playing the clip, only there while in range
+    sound: Option<Entity>,
+    /// A one-shot clip plays once per entering the range
+    played: bool,
+}
+
+fn init_emitter(
+    mut commands: Commands,
+    emitters: Query<(Entity, &AudioEmitter), Added<AudioEmitter>>,
+    assets: Res<AssetServer>,
+) {
+    for (entity, emitter) in emitters.iter() {
+        commands.entity(entity).insert(EmitterPlayback {
+            clip: assets.load(&emitter.clip),
+            sound: None,
+            played: false,
+        });
+    }
+}
+
+fn update_emitter(
+    mut commands: Commands,
+    mut emitters: Query<(
+        Entity,
+        &AudioEmitter,
+        &GlobalTransform,
+        &mut EmitterPlayback,
+    )>,
+    sinks: Query<&SpatialAudioSink>,
+    listener: Query<&GlobalTransform, With<SpatialListener>>,
+    sources: Res<Assets<AudioSource>>,
+    volume: Res<MasterVolume>,
+) {
+    let Ok(listener) = listener.get_single() else {
+        return;
+    };
+
+    for (entity, emitter, gtransform, mut playback) in emitters.iter_mut() {
+        let distance = gtransform.translation().distance(listener.translation());
+        let in_range = distance < emitter.range;
+
+        match playback.sound {
+            // Despawning drops the decoded stream along with the sink
+            Some(sound) if !in_range => {
+                commands.entity(sound).despawn_recursive();
+                playback.sound = None;
+                playback.played = false;
+            }
+            Some(sound) => {
+                if let Ok(sink) = sinks.get(sound) {
+                    sink.set_volume(volume.0 * (1. - distance / emitter.range));
+                }
+            }
+            None if in_range
+                && !(playback.played && !emitter.looping)
+                && is_loaded(&sources, &playback.clip) =>
+            {
+                let mode = if emitter.looping {
+                    PlaybackMode::Loop
+                } else {
+                    PlaybackMode::Despawn
+                };
+                let sound = commands
+                    .spawn((
+                        AudioBundle {
+                            source: playback.clip.clone(),
+                            settings: PlaybackSettings {
+                                mode,
+                                volume: Volume::new_relative(
+                                    volume.0 * (1. - distance / emitter.range),
+                                ),
+                                spatial: true,
+                                ..default()
+                            },
+                        },
+                        TransformBundle::default(),
+                    ))
+                    .id();
+                commands.entity(entity).add_child(sound);
+                playback.sound = Some(sound);
+                playback.played = true;
+            }
+            None => {}
+        }
+    }
+}
+
+// Music
+
+/// Background music, cross-faded on `PlayMusic` and `StopMusic`.
+#[derive(Resource)]
+pub struct MusicPlayer {
+    /// Seconds for a track to fade in or out
+    pub fade_duration: f32,
+    pub menu_track: Option<String>,
+    pub gameplay_track: Option<String>,
+    current: Option<(String, Entity)>,
+    /// Requested, and switched to once it has loaded
+    pending: Option<(String, Handle<AudioSource>)>,
+}
+
+impl Default for MusicPlayer {
+    fn default() -> Self {
+        Self {
+            fade_duration: 1.5,
+            menu_track: Some("music/menu.ogg".to_string()),
+            gameplay_track: Some("music/level.ogg".to_string()),
+            current: None,
+            pending: None,
+        }
+    }
+}
+
+/// Cross-fade to the track, if it isn't the one already playing.
+#[derive(Event, Clone, Debug)]
+pub struct PlayMusic {
+    pub track: String,
+}
+
+/// Fade out the current track.
+#[derive(Event, Clone, Debug, Default)]
+pub struct StopMusic;
+
+#[derive(Component)]
+struct MusicTrack {
+    /// Fade level, from 0 to 1
+    level: f32,
+    fading_out: bool,
+}
+
+fn play_menu_music(player: Res<MusicPlayer>, mut play: EventWriter<PlayMusic>) {
+    if let Some(track) = &player.menu_track {
+        play.send(PlayMusic {
+            track: track.clone(),
+        });
+    }
+}
+
+// Also on resuming from the pause menu, which leaves the track playing
+fn play_gameplay_music(player: Res<MusicPlayer>, mut play: EventWriter<PlayMusic>) {
+    if let Some(track) = &player.gameplay_track {
+        play.send(PlayMusic {
+            track: track.clone(),
+        });
+    }
+}
+
+enum MusicRequest {
+    Play(String),
+    Stop,
+}
+
+fn switch_music(
+    mut commands: Commands,
+    mut play: EventReader<PlayMusic>,
+    mut stop: EventReader<StopMusic>,
+    mut player: ResMut<MusicPlayer>,
+    mut tracks: Query<(Entity, &mut MusicTrack)>,
+    assets: Res<AssetServer>,
+    sources: Res<Assets<AudioSource>>,
+) {
+    // Only the last request of the frame matters, stopping wins over playing
+    let request = if stop.read().count() > 0 {
+        play.clear();
+        Some(MusicRequest::Stop)
+    } else {
+        play.read()
+            .last()
+            .map(|ev| MusicRequest::Play(ev.track.clone()))
+    };
+    match request {
+        Some(MusicRequest::Stop) => {
+            player.pending = None;
+            fade_out_tracks(&mut commands, &mut tracks);
+            player.current = None;
+            return;
+        }
+        Some(MusicRequest::Play(track)) => {
+            let current = player.current.as_ref().map(|(current, _)| current);
+            player.pending = if current == Some(&track) {
+                None
+            } else {
+                let source = assets.load(&track);
+                Some((track, source))
+            };
+        }
+        None => {}
+    }
+
+    // The current track keeps playing until the next one is ready
+    let Some((track, source)) = player.pending.take() else {
+        return;
+    };
+    if assets.load_state(source.id()) == LoadState::Failed {
+        warn!("Failed to load music {track}");
+        return;
+    }
+    if !is_loaded(&sources, &source) {
+        player.pending = Some((track, source));
+        return;
+    }
+
+    fade_out_tracks(&mut commands, &mut tracks);
+    let entity = commands
+        .spawn((
+            Name::new("Music"),
+            MusicTrack {
+                level: 0.,
+                fading_out: false,
+            },
+            AudioBundle {
+                source,
+                settings: PlaybackSettings::LOOP.with_volume(Volume::new_relative(0.)),
+            },
+        ))
+        .id();
+    player.current = Some((track, entity));
+}
+
+// At most one track fades out at a time, so quick switches don't pile up sinks
+fn fade_out_tracks(commands: &mut Commands, tracks: &mut Query<(Entity, &mut MusicTrack)>) {
+    for (entity, mut music) in tracks.iter_mut() {
+        if music.fading_out {
+            commands.entity(entity).despawn();
+        } else {
+            music.fading_out = true;
+        }
+    }
+}
+
+fn fade_music(
+    mut commands: Commands,
+    mut tracks: Query<(Entity, &mut MusicTrack, Option<&AudioSink>)>,
+    player: Res<MusicPlayer>,
+    volume: Res<MasterVolume>,
+    time: Res<Time>,
+) {
+    let step = if player.fade_duration > 0. {
+        time.delta_seconds() / player.fade_duration
+    } else {
+        1.
+    };
+
+    for (entity, mut music, sink) in tracks.iter_mut() {
+        // The sink shows up once the clip has loaded
+        let Some(sink) = sink else {
+            if music.fading_out {
+                commands.entity(entity).despawn();
+            }
+            continue;
+        };
+
+        if music.fading_out {
+            music.level = (music.level - step).max(0.);
+            if music.level == 0. {
+                commands.entity(entity).despawn();
+                continue;
+            }
+        } else {
+            music.level = (music.level + step).min(1.);
+        }
+        sink.set_volume(volume.0 * music.level);
+    }
+}
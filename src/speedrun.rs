@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::GameState;
+use crate::player::Player;
+use crate::terrain::LevelUnloadedEvent;
+use crate::zone::{CheckpointReachedEvent, LevelCompleteEvent};
+
+pub struct SpeedrunPlugin;
+
+impl Plugin for SpeedrunPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpeedrunTimer>()
+            .insert_resource(load_best_times().unwrap_or_default())
+            .add_systems(
+                Update,
+                (
+                    reset_timer,
+                    start_timer,
+                    tick_timer.run_if(in_state(GameState::Playing)),
+                    record_split,
+                    finish_timer,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Where the best time of each level is persisted between sessions.
+const BEST_TIMES_PATH: &str = "best_times.ron";
+
+/// How far the player goes from where they spawned before the clock starts.
+const LEAVE_SPAWN_DISTANCE: f32 = 0.5;
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpeedrunState {
+    /// Standing at the spawn
+    #[default]
+    Waiting,
+    Running,
+    Finished,
+}
+
+/// Time of the current run through the level, paused along with the game.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct SpeedrunTimer {
+    pub state: SpeedrunState,
+    /// Seconds since the player left the spawn
+    pub elapsed: f32,
+    /// `Checkpoint::order` and the time it was first reached at
+    pub splits: Vec<(u32, f32)>,
+    /// The finished run beat the level's best time
+    pub new_record: bool,
+}
+
+impl SpeedrunTimer {
+    /// Stops the clock, e.g. on reaching the goal.
+    pub fn finish(&mut self) {
+        if self.state == SpeedrunState::Running {
+            self.state = SpeedrunState::Finished;
+        }
+    }
+}
+
+/// Fastest finish of each level by its path, in seconds.
+#[derive(Resource, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct BestTimes(pub BTreeMap<String, f32>);
+
+/// `mm:ss.mmm`, for the HUD.
+pub fn format_time(seconds: f32) -> String {
+    let millis = (seconds * 1000.).round() as u64;
+    format!(
+        "{:02}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+// A restarted or newly loaded level is a new run
+fn reset_timer(mut unloaded: EventReader<LevelUnloadedEvent>, mut timer: ResMut<SpeedrunTimer>) {
+    if unloaded.read().count() > 0 {
+        *timer = SpeedrunTimer::default();
+    }
+}
+
+fn start_timer(
+    mut timer: ResMut<SpeedrunTimer>,
+    player: Query<(Entity, &Position), With<Player>>,
+    // Where each player was first seen, as they spawn
+    mut spawns: Local<HashMap<Entity, Vec3>>,
+) {
+    if timer.state != SpeedrunState::Waiting {
+        spawns.clear();
+        return;
+    }
+    // Split-screen starts with whoever moves first
+    for (entity, position) in player.iter() {
+        let spawn = *spawns.entry(entity).or_insert(position.0);
+        if spawn.distance(position.0) > LEAVE_SPAWN_DISTANCE {
+            timer.state = SpeedrunState::Running;
+        }
+    }
+}
+
+fn tick_timer(mut timer: ResMut<SpeedrunTimer>, time: Res<Time>) {
+    if timer.state == SpeedrunState::Running {
+        timer.elapsed += time.delta_seconds();
+    }
+}
+
+fn record_split(
+    mut reached: EventReader<CheckpointReachedEvent>,
+    mut timer: ResMut<SpeedrunTimer>,
+) {
+    for ev in reached.read() {
+        // Backtracking activates a checkpoint again, the split is the first time only
+        if timer.state != SpeedrunState::Running
+            || timer.splits.iter().any(|(order, _)| *order == ev.order)
+        {
+            continue;
+        }
+        let elapsed = timer.elapsed;
+        timer.splits.push((ev.order, elapsed));
+    }
+}
+
+fn finish_timer(
+    mut complete: EventReader<LevelCompleteEvent>,
+    mut timer: ResMut<SpeedrunTimer>,
+    mut best: ResMut<BestTimes>,
+) {
+    let Some(ev) = complete.read().last() else {
+        return;
+    };
+    if timer.state != SpeedrunState::Running {
+        return;
+    }
+    timer.finish();
+
+    let elapsed = timer.elapsed;
+    if best.0.get(&ev.level).map_or(true, |&best| elapsed < best) {
+        timer.new_record = true;
+        best.0.insert(ev.level.clone(), elapsed);
+        save_best_times(&best);
+    }
+}
+
+fn load_best_times() -> Option<BestTimes> {
+    let ron = std::fs::read_to_string(BEST_TIMES_PATH).ok()?;
+    ron::from_str(&ron)
+        .map_err(|e| error!("Failed to parse {BEST_TIMES_PATH}: {e}"))
+        .ok()
+}
+
+fn save_best_times(best: &BestTimes) {
+    let result = ron::ser::to_string_pretty(best, ron::ser::PrettyConfig::default())
+        .map_err(|e| e.to_string())
+        .and_then(|ron| std::fs::write(BEST_TIMES_PATH, ron).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        error!("Failed to save {BEST_TIMES_PATH}: {e}");
+    }
+}
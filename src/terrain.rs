@@ -1,11 +1,20 @@
+use std::collections::HashMap;
+use std::time::UNIX_EPOCH;
+
+use bevy::ecs::system::{EntityCommands, SystemParam};
+use bevy::pbr::CascadeShadowConfigBuilder;
 use bevy::prelude::*;
-use bevy::render::primitives::Aabb;
+use bevy::render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues};
+use bevy::tasks::{futures_lite::future, AsyncComputeTaskPool, Task};
 use bevy::{gltf::Gltf, scene::SceneInstanceReady};
-use bevy_debug_text_overlay::screen_print;
 use bevy_gltf_components::{ComponentsFromGltfPlugin, GltfLoadingTracker};
 use bevy_xpbd_3d::components::Collider;
+use bevy_xpbd_3d::parry::transformation::vhacd::VHACDParameters;
 use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::game::GameState;
+use crate::interaction::Interactable;
 use crate::player;
 
 pub struct TerrainPlugin;
@@ -13,15 +22,40 @@ pub struct TerrainPlugin;
 impl Plugin for TerrainPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<MakeCollider>()
+            .register_type::<SurfaceMaterial>()
+            .register_type::<SurfaceKind>()
+            .register_type::<ColliderKind>()
             .register_type::<SpawnPoint>()
             .register_type::<EnableShadow>()
+            .register_type::<FlickerLight>()
             .register_type::<MakeLadder>()
+            .register_type::<MakeMonkeyBars>()
+            .register_type::<MakeRope>()
+            .add_state::<LoadingState>()
+            .init_resource::<PendingColliders>()
+            .init_resource::<ColliderProgress>()
+            .init_resource::<LoadingScene>()
+            .add_event::<CollidersReadyEvent>()
+            .add_event::<LevelReadyEvent>()
+            .init_resource::<PhysicsActivation>()
+            .init_resource::<CurrentLevel>()
+            .init_resource::<RequestedSpawn>()
+            .add_event::<LoadLevelEvent>()
+            .add_event::<LevelUnloadedEvent>()
             .add_plugins(ComponentsFromGltfPlugin)
+            .add_systems(Startup, load_collider_cache)
             .add_systems(Startup, load_scene)
-            .add_systems(Startup, load_scene)
+            // The level is built behind the menu already, only the player waits for `Playing`
             .add_systems(
                 Update,
-                spawn_scene.run_if(resource_changed::<GltfLoadingTracker>()),
+                (
+                    load_level,
+                    spawn_scene.run_if(
+                        resource_changed::<GltfLoadingTracker>()
+                            .or_else(resource_changed::<LevelGltf>()),
+                    ),
+                )
+                    .chain(),
             )
             .add_systems(
                 Update,
@@ -29,95 +63,380 @@ impl Plugin for TerrainPlugin {
                     (
                         apply_enable_shadow::<PointLight>,
                         apply_enable_shadow::<SpotLight>,
+                        apply_enable_shadow::<DirectionalLight>,
                         make_collider,
-                        spawn_point,
+                        spawn_point.run_if(
+                            in_state(GameState::Playing).and_then(in_state(LoadingState::Ready)),
+                        ),
                     ),
+                    // A frame after the scene is in, for the tags to have been processed
+                    finish_loading.run_if(in_state(LoadingState::Loading)),
                     show_scene,
                 )
                     .chain(),
             )
-            .add_systems(Update, make_collider)
-            .add_systems(Update, make_ladder);
+            .add_systems(OnEnter(LoadingState::Loading), spawn_loading_screen)
+            .add_systems(OnExit(LoadingState::Loading), despawn_loading_screen)
+            .add_systems(
+                Update,
+                update_loading_screen.run_if(in_state(LoadingState::Loading)),
+            )
+            .add_systems(
+                Update,
+                (flicker_light::<PointLight>, flicker_light::<SpotLight>),
+            )
+            .add_systems(Update, insert_ready_colliders)
+            .add_systems(Update, park_far_bodies.run_if(in_state(GameState::Playing)))
+            .add_systems(Update, make_ladder)
+            .add_systems(Update, make_monkey_bars)
+            .add_systems(Update, make_rope);
+        #[cfg(feature = "hot_reload")]
+        app.add_systems(Update, reload_level.after(load_level).before(spawn_scene));
+        #[cfg(feature = "inspector")]
+        app.register_type::<Ladder>().register_type::<MonkeyBars>();
+    }
+}
+
+/// Asset path of the level glb being played.
+#[derive(Resource, Debug, Clone)]
+pub struct CurrentLevel(pub String);
+
+impl Default for CurrentLevel {
+    fn default() -> Self {
+        Self("levels/level.glb".to_string())
     }
 }
 
+/// Replace the current level, along with the player, by the glb at `path`.
+#[derive(Event, Clone, Debug)]
+pub struct LoadLevelEvent {
+    pub path: String,
+    /// `SpawnPoint` id to enter at, instead of the primary one
+    pub spawn: Option<u32>,
+}
+
+/// The level scene has been despawned, for a new level or reloading the current one.
+#[derive(Event, Clone, Debug)]
+pub struct LevelUnloadedEvent;
+
 #[derive(Resource)]
-struct LevelGltf(Handle<Gltf>);
+struct LevelGltf {
+    handle: Handle<Gltf>,
+    spawned: bool,
+}
+
+/// Root of the spawned level scene.
+#[derive(Component)]
+struct LevelScene;
+
+/// Spawned from the level outside of its scene, despawned along with it.
+#[derive(Component)]
+struct LevelEntity;
 
-fn load_scene(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn load_scene(mut commands: Commands, asset_server: Res<AssetServer>, level: Res<CurrentLevel>) {
     // Need to store `Handle<Gltf>` rather than `Handle<Scene>` because
     // gltf is dropped after spawning a scene directly.
-    commands.insert_resource(LevelGltf(asset_server.load("levels/level.glb")));
+    commands.insert_resource(LevelGltf {
+        handle: asset_server.load(&level.0),
+        spawned: false,
+    });
+}
+
+fn load_level(
+    mut commands: Commands,
+    mut load: EventReader<LoadLevelEvent>,
+    mut level: ResMut<CurrentLevel>,
+    spawned: Query<Entity, Or<(With<LevelScene>, With<LevelEntity>, With<player::Player>)>>,
+    asset_server: Res<AssetServer>,
+    mut unloaded: EventWriter<LevelUnloadedEvent>,
+    mut loading: ResMut<NextState<LoadingState>>,
+) {
+    let Some(LoadLevelEvent { path, spawn }) = load.read().last() else {
+        return;
+    };
+
+    // Colliders and the rest generated from the scene are below its root
+    for entity in spawned.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<player::PlayerRespawn>();
+    commands.insert_resource(RequestedSpawn(*spawn));
+    unloaded.send(LevelUnloadedEvent);
+    loading.set(LoadingState::Loading);
+
+    level.0 = path.clone();
+    commands.insert_resource(LevelGltf {
+        handle: asset_server.load(path),
+        spawned: false,
+    });
+}
+
+// Hot reload of the glb being edited, with the `hot_reload` feature turning on
+// bevy's `file_watcher`, without which `AssetEvent::Modified` is never sent.
+// The player stays where it is, as the spawn point doesn't spawn a second one.
+#[cfg(feature = "hot_reload")]
+fn reload_level(
+    mut commands: Commands,
+    mut asset_ev: EventReader<AssetEvent<Gltf>>,
+    level_scene: Option<ResMut<LevelGltf>>,
+    spawned: Query<Entity, Or<(With<LevelScene>, With<LevelEntity>)>>,
+    mut unloaded: EventWriter<LevelUnloadedEvent>,
+    mut loading: ResMut<NextState<LoadingState>>,
+) {
+    let Some(mut level_scene) = level_scene else {
+        return;
+    };
+    let id = level_scene.handle.id();
+    if !asset_ev
+        .read()
+        .any(|ev| matches!(ev, AssetEvent::Modified { id: modified } if *modified == id))
+    {
+        return;
+    }
+
+    info!("Reloading level");
+    for entity in spawned.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    unloaded.send(LevelUnloadedEvent);
+    loading.set(LoadingState::Loading);
+    level_scene.spawned = false;
+}
+
+/// Whether the current level is done building. Its scene stays hidden and the
+/// player unspawned until the glTF is in and every collider has been made,
+/// so the player can't fall through a floor that isn't solid yet.
+#[derive(States, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum LoadingState {
+    #[default]
+    Loading,
+    Ready,
 }
 
-fn show_scene(mut commands: Commands, mut ready_ev: EventReader<SceneInstanceReady>) {
+/// The level is revealed and the player about to spawn.
+#[derive(Event, Clone, Debug)]
+pub struct LevelReadyEvent;
+
+/// Root of the level scene once spawned, held back while loading.
+#[derive(Resource, Default)]
+struct LoadingScene(Option<Entity>);
+
+fn show_scene(mut ready_ev: EventReader<SceneInstanceReady>, mut loading: ResMut<LoadingScene>) {
     for ev in ready_ev.read() {
-        let scene_root = ev.parent;
-        commands.entity(scene_root).insert(Visibility::Visible);
+        loading.0 = Some(ev.parent);
     }
 }
 
-fn spawn_scene(
+fn finish_loading(
     mut commands: Commands,
-    // mut asset_ev: EventReader<AssetEvent<Gltf>>,
-    level_scene: Option<Res<LevelGltf>>,
-    gltf: Res<Assets<Gltf>>,
-    tracker: Res<GltfLoadingTracker>,
-    mut done: Local<bool>,
+    mut loading_scene: ResMut<LoadingScene>,
+    progress: Res<ColliderProgress>,
+    mut loading: ResMut<NextState<LoadingState>>,
+    mut ready: EventWriter<LevelReadyEvent>,
 ) {
-    debug_assert!(tracker.is_changed(), "enforced by run_if");
+    if progress.tasks_remaining > 0 {
+        return;
+    }
+    let Some(scene_root) = loading_scene.0.take() else {
+        return;
+    };
+    // Unloaded again before it was done
+    let Some(mut entity) = commands.get_entity(scene_root) else {
+        return;
+    };
+
+    entity.insert(Visibility::Visible);
+    loading.set(LoadingState::Ready);
+    ready.send(LevelReadyEvent);
+}
+
+#[derive(Component)]
+struct LoadingScreen;
+
+fn spawn_loading_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            Name::new("LoadingScreen"),
+            LoadingScreen,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    align_items: AlignItems::End,
+                    justify_content: JustifyContent::End,
+                    padding: UiRect::all(Val::Px(24.)),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                LoadingScreen,
+                TextBundle::from_section(
+                    "Loading...",
+                    TextStyle {
+                        font_size: 32.,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+            ));
+        });
+}
 
-    if *done {
+fn update_loading_screen(
+    mut text: Query<&mut Text, With<LoadingScreen>>,
+    progress: Res<ColliderProgress>,
+) {
+    if !progress.is_changed() {
         return;
     }
+    let value = if progress.tasks_total == 0 {
+        "Loading...".to_string()
+    } else {
+        let done = progress.tasks_total - progress.tasks_remaining;
+        format!("Loading... {done}/{}", progress.tasks_total)
+    };
+    for mut text in text.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+fn despawn_loading_screen(mut commands: Commands, screen: Query<Entity, With<LoadingScreen>>) {
+    for entity in screen.iter() {
+        if let Some(entity) = commands.get_entity(entity) {
+            entity.despawn_recursive();
+        }
+    }
+}
 
-    let Some(scene_handle) = level_scene else {
+fn spawn_scene(
+    mut commands: Commands,
+    level_scene: Option<ResMut<LevelGltf>>,
+    gltf: Res<Assets<Gltf>>,
+    tracker: Res<GltfLoadingTracker>,
+) {
+    let Some(mut level_scene) = level_scene else {
         return;
     };
 
-    if !tracker.loaded_gltfs.contains(&scene_handle.0) {
+    // Once per `LevelGltf`, which is replaced on loading a level
+    if level_scene.spawned || !tracker.loaded_gltfs.contains(&level_scene.handle) {
         return;
     }
 
-    *done = true;
+    level_scene.spawned = true;
 
-    commands.spawn(SceneBundle {
-        scene: gltf.get(scene_handle.0.clone()).unwrap().scenes[0].clone(),
-        visibility: Visibility::Hidden,
-        ..default()
-    });
+    commands.spawn((
+        Name::new("Level"),
+        LevelScene,
+        SceneBundle {
+            scene: gltf.get(level_scene.handle.clone()).unwrap().scenes[0].clone(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
 }
 
+/// Where the player enters the level. The `primary` one is used unless another
+/// `id` is requested with `LoadLevelEvent`, e.g. for arriving from a door.
 #[derive(Default, Component, Reflect)]
 #[reflect(Component)]
-struct SpawnPoint(bool);
+struct SpawnPoint {
+    primary: bool,
+    id: u32,
+    /// Yaw the player starts with, in degrees, instead of the node's own
+    facing: Option<f32>,
+}
+
+/// Transform the player spawns with at a spawn point, kept on all of them to respawn at.
+#[derive(Component, Debug, Clone)]
+pub struct SpawnAnchor {
+    pub id: u32,
+    pub transform: Transform,
+}
+
+/// Gap between split-screen players spawned at the same point.
+const SPAWN_SPACING: f32 = 1.5;
+
+/// Spawn point id the next level is entered at.
+#[derive(Resource, Default)]
+struct RequestedSpawn(Option<u32>);
 
 fn spawn_point(
     mut commands: Commands,
-    spawn_point: Query<(Entity, &SpawnPoint, &Children), Added<SpawnPoint>>,
+    spawn_point: Query<(Entity, &SpawnPoint, &GlobalTransform, &Children), Added<SpawnPoint>>,
     child: Query<(&Handle<Mesh>, &GlobalTransform)>,
+    players: Query<(), With<player::Player>>,
+    requested: Res<RequestedSpawn>,
     meshes: Res<Assets<Mesh>>,
+    local_players: Res<player::LocalPlayers>,
 ) {
-    for (entity, spawn_point, children) in &spawn_point {
-        if !spawn_point.0 {
-            continue;
-        }
-
-        screen_print!("spawning point at {:?}", entity);
-
-        commands.entity(entity).despawn();
+    let mut anchors = Vec::new();
 
+    for (entity, spawn_point, node_transform, children) in &spawn_point {
         let Ok((mesh, gtransform)) = child.get(children[0]) else {
             continue;
         };
 
         let mesh = meshes.get(mesh).unwrap();
 
+        // Only turned around the vertical, so the player stays upright
+        let yaw = spawn_point.facing.map_or_else(
+            || {
+                let (_, rotation, _) = node_transform.to_scale_rotation_translation();
+                rotation.to_euler(EulerRot::YXZ).0
+            },
+            f32::to_radians,
+        );
+        let transform = Transform::from_translation(
+            gtransform.translation() + Vec3::from(mesh.compute_aabb().unwrap().center),
+        )
+        .with_rotation(Quat::from_rotation_y(yaw));
+
+        // The marker mesh isn't part of the level
+        commands.entity(entity).insert((
+            SpawnAnchor {
+                id: spawn_point.id,
+                transform,
+            },
+            Visibility::Hidden,
+        ));
+        anchors.push((spawn_point.id, spawn_point.primary, transform));
+    }
+
+    // Only one set of players, however many spawn points show up
+    if anchors.is_empty() || !players.is_empty() {
+        return;
+    }
+
+    let chosen = requested
+        .0
+        .and_then(|id| anchors.iter().find(|(anchor_id, _, _)| *anchor_id == id))
+        .or_else(|| anchors.iter().find(|(_, primary, _)| *primary));
+    let Some(&(id, _, transform)) = chosen else {
+        warn!("No primary spawn point in the level");
+        return;
+    };
+
+    debug_print!("spawning at point {id}");
+
+    commands.insert_resource(player::PlayerRespawn { transform });
+    // Split-screen players stand side by side, centered on the spawn point
+    let count = local_players.0.max(1);
+    for slot in 0..count {
+        let offset = (slot as f32 - (count - 1) as f32 / 2.) * SPAWN_SPACING;
         commands
-            .spawn(SpatialBundle::from_transform(Transform::from_translation(
-                gtransform.translation() + Vec3::from(mesh.compute_aabb().unwrap().center),
+            .spawn(SpatialBundle::from_transform(transform.with_translation(
+                transform.translation + transform.right() * offset,
             )))
-            .insert(player::Player);
+            .insert((
+                player::Player,
+                player::PlayerSlot(slot),
+                player::DoubleJump::default(),
+                player::Stamina::default(),
+            ));
     }
 }
 
@@ -127,6 +446,9 @@ struct EnableShadow(bool);
 
 trait ApplyEnableShadow: Component {
     fn enable_shadow(&mut self);
+
+    /// Shadow settings kept in other components of the light.
+    fn configure_shadow(&self, _entity: &mut EntityCommands) {}
 }
 
 impl ApplyEnableShadow for PointLight {
@@ -141,6 +463,33 @@ impl ApplyEnableShadow for SpotLight {
     }
 }
 
+/// Sun shadows reach this far from the camera.
+const SUN_SHADOW_DISTANCE: f32 = 60.;
+/// End of the first, sharpest cascade, around the player.
+const SUN_SHADOW_FIRST_CASCADE: f32 = 8.;
+
+impl ApplyEnableShadow for DirectionalLight {
+    fn enable_shadow(&mut self) {
+        self.shadows_enabled = true;
+        // Biases tuned for the default cascades, kept with the ones below
+        self.shadow_depth_bias = DirectionalLight::DEFAULT_SHADOW_DEPTH_BIAS;
+        self.shadow_normal_bias = DirectionalLight::DEFAULT_SHADOW_NORMAL_BIAS;
+    }
+
+    // The default cascades cover a far larger scene, leaving blurry shadows
+    // at the scale of a level
+    fn configure_shadow(&self, entity: &mut EntityCommands) {
+        entity.insert(
+            CascadeShadowConfigBuilder {
+                maximum_distance: SUN_SHADOW_DISTANCE,
+                first_cascade_far_bound: SUN_SHADOW_FIRST_CASCADE,
+                ..default()
+            }
+            .build(),
+        );
+    }
+}
+
 fn apply_enable_shadow<T: ApplyEnableShadow>(
     mut commands: Commands,
     mut lights: Query<(Entity, &EnableShadow, &mut T)>,
@@ -153,59 +502,536 @@ fn apply_enable_shadow<T: ApplyEnableShadow>(
         }
 
         light.enable_shadow();
+        light.configure_shadow(&mut commands.entity(entity));
     }
 }
 
+/// Varies the intensity of the light on the node like a torch, by up to
+/// `amount` around `base`. `speed` scales how fast it flickers.
 #[derive(Default, Component, Reflect)]
 #[reflect(Component)]
-struct MakeCollider(bool);
+struct FlickerLight {
+    base: f32,
+    amount: f32,
+    speed: f32,
+}
 
-fn make_collider(
-    target: Query<(Entity, &MakeCollider, &Transform, &Children), Added<MakeCollider>>,
-    mesh: Query<&Handle<Mesh>>,
-    meshes: Res<Assets<Mesh>>,
-    mut commands: Commands,
+trait LightIntensity: Component {
+    fn set_intensity(&mut self, intensity: f32);
+}
+
+impl LightIntensity for PointLight {
+    fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+}
+
+impl LightIntensity for SpotLight {
+    fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+}
+
+// Sum of sines, smooth but irregular enough to pass for a flame
+fn flicker_noise(t: f32, seed: f32) -> f32 {
+    ((t * 7. + seed * 7.).sin() + (t * 13. + seed * 13.).sin() * 0.5) / 1.5
+}
+
+fn flicker_light<T: LightIntensity>(
+    mut lights: Query<(Entity, &FlickerLight, &mut T)>,
+    time: Res<Time>,
 ) {
-    for (entity, make_collider, transform, children) in target.iter() {
-        if !make_collider.0 {
+    for (entity, flicker, mut light) in &mut lights {
+        // Seeded by the entity, so torches side by side don't flicker in sync
+        let noise = flicker_noise(
+            time.elapsed_seconds() * flicker.speed,
+            entity.index() as f32,
+        );
+        light.set_intensity((flicker.base + flicker.amount * noise).max(0.));
+    }
+}
+
+/// How the ground feels to walk on, set on a glTF node next to its collider.
+/// Untagged ground is `SurfaceMaterial::default()`.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct SurfaceMaterial {
+    /// Multiplies the walking acceleration and deceleration, low for ice and high for mud
+    pub friction: f32,
+    pub kind: SurfaceKind,
+}
+
+impl Default for SurfaceMaterial {
+    fn default() -> Self {
+        Self {
+            friction: 1.,
+            kind: SurfaceKind::Default,
+        }
+    }
+}
+
+/// Picks the footstep sound.
+#[derive(Default, Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SurfaceKind {
+    #[default]
+    Default,
+    Grass,
+    Stone,
+    Wood,
+    Ice,
+    Mud,
+}
+
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct MakeCollider(pub bool);
+
+/// Shape generated for `MakeCollider`, set next to it on the glTF node.
+#[derive(Default, Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+enum ColliderKind {
+    /// Cheap to collide with, but fills in concave parts
+    #[default]
+    ConvexHull,
+    /// Follows the mesh surface exactly, for concave terrain like caves.
+    /// Only usable with `RigidBody::Static`.
+    Trimesh,
+    /// Several convex hulls, for concave dynamic props. Expensive to generate.
+    Decomposed {
+        /// Voxel resolution, higher is more precise
+        resolution: u32,
+        max_convex_hulls: u32,
+    },
+}
+
+impl ColliderKind {
+    /// Collider over all the mesh parts of a node, already in the node's space.
+    fn collider(self, parts: &[Mesh]) -> Option<Collider> {
+        match self {
+            ColliderKind::ConvexHull => {
+                let mut hulls: Vec<Collider> = parts
+                    .iter()
+                    .filter_map(Collider::convex_hull_from_mesh)
+                    .collect();
+                match hulls.len() {
+                    0 => None,
+                    1 => hulls.pop(),
+                    _ => Some(Collider::compound(
+                        hulls
+                            .into_iter()
+                            .map(|hull| (Position::default(), Rotation::default(), hull))
+                            .collect(),
+                    )),
+                }
+            }
+            // Composite shapes can't be nested in a compound, so the parts are merged instead
+            ColliderKind::Trimesh => Collider::trimesh_from_mesh(&merge_meshes(parts)),
+            ColliderKind::Decomposed {
+                resolution,
+                max_convex_hulls,
+            } => {
+                let mesh = merge_meshes(parts);
+                let parameters = VHACDParameters {
+                    resolution,
+                    max_convex_hulls,
+                    ..default()
+                };
+                Collider::convex_decomposition_from_mesh_with_config(&mesh, &parameters).or_else(
+                    || {
+                        warn!("Convex decomposition failed, falling back to convex hull");
+                        Collider::convex_hull_from_mesh(&mesh)
+                    },
+                )
+            }
+        }
+    }
+}
+
+/// Local transform of `descendant` relative to `ancestor`. Global transforms
+/// aren't propagated yet when components of a fresh scene are added.
+fn relative_transform(
+    ancestor: Entity,
+    descendant: Entity,
+    parents: &Query<&Parent>,
+    transforms: &Query<&Transform>,
+) -> Transform {
+    std::iter::once(descendant)
+        .chain(parents.iter_ancestors(descendant))
+        .take_while(|&entity| entity != ancestor)
+        .filter_map(|entity| transforms.get(entity).ok())
+        .fold(Transform::IDENTITY, |transform, local| *local * transform)
+}
+
+fn transformed_mesh(mesh: &Mesh, transform: Transform) -> Mesh {
+    let mut mesh = mesh.clone();
+    if let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+    {
+        for position in positions {
+            *position = transform.transform_point(Vec3::from(*position)).to_array();
+        }
+    }
+    mesh
+}
+
+/// Positions and triangles of all parts in a single mesh.
+fn merge_meshes(parts: &[Mesh]) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    for part in parts {
+        let Some(VertexAttributeValues::Float32x3(part_positions)) =
+            part.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
             continue;
+        };
+        let offset = positions.len() as u32;
+        match part.indices() {
+            Some(part_indices) => {
+                indices.extend(part_indices.iter().map(|i| offset + i as u32));
+            }
+            None => indices.extend(offset..offset + part_positions.len() as u32),
         }
+        positions.extend_from_slice(part_positions);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
 
-        screen_print!("making collider for entity {:?}", entity);
+/// Colliders being generated on the async compute pool, so a big level doesn't
+/// stall the frame it spawns in. The entity is visible but not colliding until done.
+#[derive(Resource, Default)]
+struct PendingColliders(Vec<PendingCollider>);
 
-        let mesh = meshes.get(mesh.get(children[0]).unwrap()).unwrap();
-        let Some(collider) = Collider::convex_hull_from_mesh(mesh) else {
-            error!("Failed to create collider for entity {:?}", entity);
+impl PendingColliders {
+    fn contains(&self, entity: Entity) -> bool {
+        self.0.iter().any(|pending| pending.entity == entity)
+    }
+}
+
+/// How far the level's colliders are, for a loading screen.
+#[derive(Resource, Default, Debug)]
+pub struct ColliderProgress {
+    pub tasks_remaining: usize,
+    /// Started since the last `CollidersReadyEvent`
+    pub tasks_total: usize,
+}
+
+/// The last pending collider has been inserted.
+#[derive(Event, Clone, Debug)]
+pub struct CollidersReadyEvent;
+
+struct PendingCollider {
+    entity: Entity,
+    scale: Vec3,
+    task: Task<Option<Collider>>,
+    /// Where the result goes in `ColliderCache`, if not from there already
+    cache_key: Option<(String, u64)>,
+}
+
+/// Mesh parts below tagged nodes, placed relative to the node.
+#[derive(SystemParam)]
+struct MeshParts<'w, 's> {
+    children: Query<'w, 's, &'static Children>,
+    parents: Query<'w, 's, &'static Parent>,
+    transforms: Query<'w, 's, &'static Transform>,
+    mesh: Query<'w, 's, &'static Handle<Mesh>>,
+    meshes: Res<'w, Assets<Mesh>>,
+    assets: Res<'w, AssetServer>,
+}
+
+impl MeshParts<'_, '_> {
+    /// Every mesh below `entity`, as glTF exports multi-material models as several primitives.
+    /// Also the asset paths of the meshes, if they all have one.
+    fn get(&self, entity: Entity) -> (Vec<Mesh>, Option<Vec<String>>) {
+        let mut paths = Some(Vec::new());
+        let parts = self
+            .children
+            .iter_descendants(entity)
+            .filter_map(|part| {
+                let handle = self.mesh.get(part).ok()?;
+                let mesh = self.meshes.get(handle)?;
+                let path = self.assets.get_path(handle.id());
+                match (&mut paths, path) {
+                    (Some(paths), Some(path)) => paths.push(path.to_string()),
+                    _ => paths = None,
+                }
+                let transform = relative_transform(entity, part, &self.parents, &self.transforms);
+                Some(transformed_mesh(mesh, transform))
+            })
+            .collect();
+        (parts, paths)
+    }
+
+    fn scale(&self, entity: Entity) -> Vec3 {
+        self.transforms.get(entity).map_or(Vec3::ONE, |t| t.scale)
+    }
+}
+
+fn make_collider(
+    target: Query<
+        (Entity, &MakeCollider, Option<&ColliderKind>),
+        (Added<MakeCollider>, Without<Collider>),
+    >,
+    parts: MeshParts,
+    cache: Res<ColliderCache>,
+    mut pending: ResMut<PendingColliders>,
+    mut progress: ResMut<ColliderProgress>,
+) {
+    // All the tagged nodes of a scene come in together, so their tasks run
+    // side by side on the pool
+    for (entity, make_collider, kind) in target.iter() {
+        // Re-adding the tag doesn't generate a second collider
+        if !make_collider.0 || pending.contains(entity) {
             continue;
+        }
+
+        debug_print!("making collider for entity {:?}", entity);
+
+        let (meshes, paths) = parts.get(entity);
+        let kind = kind.copied().unwrap_or_default();
+        let cache_key = paths.and_then(|paths| {
+            let modified = source_modified(paths.first()?)?;
+            Some((format!("{kind:?} {}", paths.join(" ")), modified))
+        });
+
+        let cached = cache_key
+            .as_ref()
+            .and_then(|(key, modified)| cache.get(key, *modified));
+        let (task, cache_key) = match cached {
+            // Still goes through a task, so it's inserted the same way
+            Some(collider) => (
+                AsyncComputeTaskPool::get().spawn(async move { Some(collider) }),
+                None,
+            ),
+            None => (
+                AsyncComputeTaskPool::get().spawn(async move { kind.collider(&meshes) }),
+                cache_key,
+            ),
         };
 
-        commands
-            .entity(entity)
-            .insert((collider, RigidBody::Static))
-            .insert(ColliderTransform {
+        pending.0.push(PendingCollider {
+            entity,
+            scale: parts.scale(entity),
+            task,
+            cache_key,
+        });
+        progress.tasks_remaining += 1;
+        progress.tasks_total += 1;
+    }
+}
+
+// A `RigidBody` set on the glTF node is kept, e.g. dynamic for throwable props
+fn insert_ready_colliders(
+    mut commands: Commands,
+    mut pending: ResMut<PendingColliders>,
+    mut cache: ResMut<ColliderCache>,
+    bodies: Query<(), With<RigidBody>>,
+    mut progress: ResMut<ColliderProgress>,
+    mut ready: EventWriter<CollidersReadyEvent>,
+) {
+    if pending.0.is_empty() {
+        return;
+    }
+
+    pending.0.retain_mut(|pending| {
+        let Some(collider) = future::block_on(future::poll_once(&mut pending.task)) else {
+            return true;
+        };
+        let Some(collider) = collider else {
+            error!("Failed to create collider for entity {:?}", pending.entity);
+            return false;
+        };
+
+        if let Some((key, modified)) = pending.cache_key.take() {
+            cache.insert(key, modified, collider.clone());
+        }
+
+        // The scene may have been despawned in the meantime
+        if let Some(mut entity) = commands.get_entity(pending.entity) {
+            entity.insert(collider).insert(ColliderTransform {
                 // Meshes are not scaled, so we need to scale the collider
-                scale: transform.scale,
+                scale: pending.scale,
                 ..default()
             });
+            if !bodies.contains(pending.entity) {
+                entity.insert(RigidBody::Static);
+            }
+        }
+        false
+    });
+
+    // Inserted as each finishes, in whatever order
+    progress.tasks_remaining = pending.0.len();
+    if pending.0.is_empty() {
+        progress.tasks_total = 0;
+        ready.send(CollidersReadyEvent);
+        // Written once the level is done, not after every collider
+        if cache.dirty {
+            cache.save();
+        }
+    }
+}
+
+// Collider cache
+
+const COLLIDER_CACHE_PATH: &str = "collider_cache.ron";
+
+/// Generated colliders kept across runs, keyed by collider kind and mesh asset paths.
+#[derive(Resource, Default)]
+struct ColliderCache {
+    colliders: HashMap<String, CachedCollider>,
+    dirty: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedCollider {
+    /// Modified time of the source glb, in seconds since the epoch
+    source_modified: u64,
+    collider: Collider,
+}
+
+impl ColliderCache {
+    /// Cached collider, unless the glb has been modified since
+    fn get(&self, key: &str, source_modified: u64) -> Option<Collider> {
+        self.colliders
+            .get(key)
+            .filter(|cached| cached.source_modified == source_modified)
+            .map(|cached| cached.collider.clone())
+    }
+
+    fn insert(&mut self, key: String, source_modified: u64, collider: Collider) {
+        self.colliders.insert(
+            key,
+            CachedCollider {
+                source_modified,
+                collider,
+            },
+        );
+        self.dirty = true;
+    }
+
+    fn save(&mut self) {
+        self.dirty = false;
+        let result = ron::to_string(&self.colliders)
+            .map_err(|e| e.to_string())
+            .and_then(|ron| std::fs::write(COLLIDER_CACHE_PATH, ron).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            error!("Failed to write {COLLIDER_CACHE_PATH}: {e}");
+        }
     }
 }
 
+/// Modified time of the file a mesh asset path like `levels/level.glb#Mesh0/Primitive0` is from.
+fn source_modified(asset_path: &str) -> Option<u64> {
+    let file = asset_path.split('#').next()?;
+    let modified = std::fs::metadata(format!("assets/{file}"))
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+// A missing or broken cache only means generating everything again
+fn load_collider_cache(mut commands: Commands) {
+    let colliders = std::fs::read_to_string(COLLIDER_CACHE_PATH)
+        .ok()
+        .and_then(|ron| match ron::from_str(&ron) {
+            Ok(colliders) => Some(colliders),
+            Err(e) => {
+                warn!("Ignoring broken {COLLIDER_CACHE_PATH}: {e}");
+                None
+            }
+        })
+        .unwrap_or_default();
+    commands.insert_resource(ColliderCache {
+        colliders,
+        dirty: false,
+    });
+}
+
 #[derive(Default, Component, Reflect)]
 #[reflect(Component)]
 struct MakeLadder(bool);
 
-#[derive(Component)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct Ladder {
     pub face_normal: Vec3,
+    /// Direction of climbing, tilted for inclined ladders
+    pub up: Vec3,
+    /// Half of the ladder length along `up`
+    pub half_height: f32,
+    /// Half of the ladder width across its face
+    pub half_width: f32,
+    /// Half of the ladder thickness along `face_normal`
+    pub half_depth: f32,
+}
+
+/// Bounding box of all meshes below a tagged glTF node, in world space,
+/// oriented like the first of them.
+pub struct MeshBox {
+    pub mesh_entity: Entity,
+    pub position: Position,
+    pub rotation: Quat,
+    pub half_extents: Vec3,
 }
 
-// bevy_gizmos::aabb_transform
-fn aabb_transform(aabb: Aabb, transform: GlobalTransform) -> GlobalTransform {
-    transform
-        * GlobalTransform::from(
-            Transform::from_translation(aabb.center.into())
-                .with_scale((aabb.half_extents * 2.).into()),
+impl MeshBox {
+    pub fn find(
+        entity: Entity,
+        children: &Query<&Children>,
+        has_mesh: &Query<(Entity, &Handle<Mesh>, &GlobalTransform)>,
+        meshes: &Assets<Mesh>,
+    ) -> Option<Self> {
+        let parts: Vec<_> = children
+            .iter_descendants(entity)
+            .filter_map(|e| has_mesh.get(e).ok())
+            .collect();
+        let &(mesh_entity, _, first) = parts.first()?;
+        let (_, rotation, _) = first.to_scale_rotation_translation();
+
+        // Corners of every part's AABB, in the frame of the first part
+        let (min, max) = parts
+            .iter()
+            .flat_map(|(_, mesh, gtransform)| {
+                let aabb = meshes
+                    .get(*mesh)
+                    .unwrap()
+                    .compute_aabb()
+                    .expect("Failed to compute AABB for mesh");
+                let (center, half) = (Vec3::from(aabb.center), Vec3::from(aabb.half_extents));
+                (0..8).map(move |i| {
+                    let corner = Vec3::new(
+                        if i & 1 == 0 { -1. } else { 1. },
+                        if i & 2 == 0 { -1. } else { 1. },
+                        if i & 4 == 0 { -1. } else { 1. },
+                    );
+                    gtransform.transform_point(center + half * corner)
+                })
+            })
+            .map(|corner| rotation.inverse() * corner)
+            .fold((Vec3::MAX, Vec3::MIN), |(min, max), corner| {
+                (min.min(corner), max.max(corner))
+            });
+
+        Some(Self {
+            mesh_entity,
+            position: Position(rotation * (min + max) / 2.),
+            rotation,
+            half_extents: (max - min) / 2.,
+        })
+    }
+
+    pub fn collider(&self) -> Collider {
+        Collider::cuboid(
+            self.half_extents.x * 2.0,
+            self.half_extents.y * 2.0,
+            self.half_extents.z * 2.0,
         )
+    }
 }
 
 fn make_ladder(
@@ -213,27 +1039,27 @@ fn make_ladder(
     query: Query<Entity, Added<MakeLadder>>,
     children: Query<&Children>,
     has_mesh: Query<(Entity, &Handle<Mesh>, &GlobalTransform)>,
+    ladders: Query<(), With<Ladder>>,
     meshes: Res<Assets<Mesh>>,
 ) {
     for ladder_entity in query.iter() {
-        if let Some((mesh_entity, mesh, gtransform)) = children
+        // Re-adding the tag doesn't spawn a second ladder
+        if children
             .iter_descendants(ladder_entity)
-            .find_map(|e| has_mesh.get(e).ok())
+            .any(|e| ladders.contains(e))
         {
-            let aabb = meshes
-                .get(mesh)
-                .unwrap()
-                .compute_aabb()
-                .expect("Failed to compute AABB for ladder mesh");
-            let half_extents = Vec3::from(aabb.half_extents) * gtransform.compute_transform().scale;
-
-            let face_normal = Vec3::Z;
-            screen_print!("half_extents: {:?}", half_extents);
+            continue;
+        }
 
-            let position = Position(aabb_transform(aabb, *gtransform).translation());
+        if let Some(mesh_box) = MeshBox::find(ladder_entity, &children, &has_mesh, &meshes) {
+            let rotation = mesh_box.rotation;
+            // The climbable face is local +Z of the ladder mesh
+            let face_normal = (rotation * Vec3::Z).normalize();
+            let up = (rotation * Vec3::Y).normalize();
+            debug_print!("half_extents: {:?}", mesh_box.half_extents);
 
             commands
-                .entity(mesh_entity)
+                .entity(mesh_box.mesh_entity)
                 // // This results in incorrect scaling:
                 // .insert((
                 //     AsyncCollider(ComputedCollider::ConvexHull),
@@ -243,16 +1069,353 @@ fn make_ladder(
                     cmd.spawn((
                         Ladder {
                             face_normal,
+                            up,
+                            half_height: mesh_box.half_extents.y,
+                            half_width: mesh_box.half_extents.x,
+                            half_depth: mesh_box.half_extents.z,
                         },
-                        Collider::cuboid(
-                            half_extents.x * 2.0,
-                            half_extents.y * 2.0,
-                            half_extents.z * 2.0,
-                        ),
+                        Interactable,
+                        mesh_box.collider(),
                         RigidBody::Static,
-                        position,
+                        mesh_box.position,
+                        Rotation(rotation),
                     ));
                 });
         }
     }
 }
+
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+struct MakeMonkeyBars(bool);
+
+/// Overhead bars the player can hang from and traverse.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct MonkeyBars {
+    /// Horizontal direction the bars run along
+    pub axis: Vec3,
+    /// Half of the bars length along `axis`
+    pub half_length: f32,
+    pub half_height: f32,
+}
+
+fn make_monkey_bars(
+    mut commands: Commands,
+    query: Query<(Entity, &MakeMonkeyBars), Added<MakeMonkeyBars>>,
+    children: Query<&Children>,
+    has_mesh: Query<(Entity, &Handle<Mesh>, &GlobalTransform)>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for (entity, make_monkey_bars) in query.iter() {
+        if !make_monkey_bars.0 {
+            continue;
+        }
+
+        let Some(mesh_box) = MeshBox::find(entity, &children, &has_mesh, &meshes) else {
+            continue;
+        };
+
+        // Bars run along the longer horizontal side of the mesh
+        let half_extents = mesh_box.half_extents;
+        let (axis, half_length) = if half_extents.x >= half_extents.z {
+            (mesh_box.rotation * Vec3::X, half_extents.x)
+        } else {
+            (mesh_box.rotation * Vec3::Z, half_extents.z)
+        };
+
+        commands.entity(mesh_box.mesh_entity).with_children(|cmd| {
+            cmd.spawn((
+                MonkeyBars {
+                    axis: axis.normalize(),
+                    half_length,
+                    half_height: half_extents.y,
+                },
+                Interactable,
+                mesh_box.collider(),
+                RigidBody::Static,
+                mesh_box.position,
+                Rotation(mesh_box.rotation),
+            ));
+        });
+    }
+}
+
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+struct MakeRope(bool);
+
+/// One link of a swaying rope, jointed to its neighbours.
+#[derive(Component)]
+pub struct RopeSegment {
+    pub above: Option<Entity>,
+    pub below: Option<Entity>,
+    pub half_length: f32,
+}
+
+const ROPE_SEGMENT_LENGTH: f32 = 0.5;
+
+fn make_rope(
+    mut commands: Commands,
+    query: Query<(Entity, &MakeRope), Added<MakeRope>>,
+    children: Query<&Children>,
+    has_mesh: Query<(Entity, &Handle<Mesh>, &GlobalTransform)>,
+    materials: Query<&Handle<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (entity, make_rope) in query.iter() {
+        if !make_rope.0 {
+            continue;
+        }
+
+        let Some(mesh_box) = MeshBox::find(entity, &children, &has_mesh, &meshes) else {
+            continue;
+        };
+
+        // The rope hangs straight down from the top of its mesh
+        let half_extents = mesh_box.half_extents;
+        let length = half_extents.y * 2.;
+        let top = mesh_box.position.0 + mesh_box.rotation * Vec3::Y * half_extents.y;
+        let radius = half_extents.x.min(half_extents.z);
+        let count = (length / ROPE_SEGMENT_LENGTH).ceil().max(1.) as usize;
+        let segment_length = length / count as f32;
+
+        // The static mesh is replaced by the simulated segments
+        let material = materials
+            .get(mesh_box.mesh_entity)
+            .cloned()
+            .unwrap_or_default();
+        commands
+            .entity(mesh_box.mesh_entity)
+            .insert(Visibility::Hidden);
+        let segment_mesh = meshes.add(Mesh::from(shape::Cylinder {
+            radius,
+            height: segment_length,
+            resolution: 8,
+            segments: 1,
+        }));
+
+        let anchor = commands
+            .spawn((
+                Name::new("RopeAnchor"),
+                LevelEntity,
+                RigidBody::Static,
+                Position(top),
+            ))
+            .id();
+        let segments: Vec<Entity> = (0..count)
+            .map(|_| commands.spawn(LevelEntity).id())
+            .collect();
+
+        for (i, &segment) in segments.iter().enumerate() {
+            let center = top - Vec3::Y * segment_length * (i as f32 + 0.5);
+            let collider = Collider::cylinder(segment_length, radius);
+
+            commands.entity(segment).insert((
+                Name::new("RopeSegment"),
+                RopeSegment {
+                    above: i.checked_sub(1).map(|i| segments[i]),
+                    below: segments.get(i + 1).copied(),
+                    half_length: segment_length / 2.,
+                },
+                Interactable,
+                PbrBundle {
+                    mesh: segment_mesh.clone(),
+                    material: material.clone(),
+                    transform: Transform::from_translation(center),
+                    ..default()
+                },
+                RigidBody::Dynamic,
+                // Sensor so the climbing player doesn't push the rope away
+                Sensor,
+                MassPropertiesBundle::new_computed(&collider, 1.),
+                collider,
+                Position(center),
+                ExternalImpulse::default(),
+            ));
+
+            let joint = if i == 0 {
+                SphericalJoint::new(anchor, segment)
+            } else {
+                SphericalJoint::new(segments[i - 1], segment)
+                    .with_local_anchor_1(Vec3::NEG_Y * segment_length / 2.)
+            };
+            commands.spawn((
+                LevelEntity,
+                joint.with_local_anchor_2(Vec3::Y * segment_length / 2.),
+            ));
+        }
+    }
+}
+
+// Physics activation
+
+/// Dynamic bodies further than `radius` from every player are put to sleep,
+/// and woken again on coming back within it. Static geometry keeps its
+/// colliders either way, it costs next to nothing.
+#[derive(Resource, Debug, Clone)]
+pub struct PhysicsActivation {
+    pub radius: f32,
+}
+
+impl Default for PhysicsActivation {
+    fn default() -> Self {
+        Self { radius: 40. }
+    }
+}
+
+/// Extra distance past `radius` before a body is put to sleep, so one right at
+/// the edge isn't woken and put back every frame.
+const PARK_MARGIN: f32 = 2.;
+
+/// Put to sleep by `park_far_bodies` rather than by coming to rest.
+#[derive(Component)]
+struct Parked;
+
+// A body parked mid-fall hangs there until the player comes back, which
+// nobody is around to see
+fn park_far_bodies(
+    mut commands: Commands,
+    bodies: Query<
+        (Entity, &RigidBody, &Position, Has<Sleeping>, Has<Parked>),
+        (Without<player::Player>, Without<SleepingDisabled>),
+    >,
+    player: Query<&Position, With<player::Player>>,
+    activation: Res<PhysicsActivation>,
+) {
+    if player.is_empty() {
+        return;
+    }
+
+    for (entity, body, position, sleeping, parked) in bodies.iter() {
+        if !body.is_dynamic() {
+            continue;
+        }
+        let distance = player
+            .iter()
+            .map(|player| player.0.distance(position.0))
+            .fold(f32::INFINITY, f32::min);
+
+        if distance < activation.radius {
+            if parked {
+                commands.entity(entity).remove::<(Sleeping, Parked)>();
+            }
+        } else if distance > activation.radius + PARK_MARGIN {
+            // Also when something else woke it up while still far away
+            if !sleeping {
+                commands.entity(entity).insert(Sleeping);
+            }
+            if !parked {
+                commands.entity(entity).insert(Parked);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_xpbd_3d::parry::math::{Isometry, Point, Vector};
+    use bevy_xpbd_3d::parry::query::{Ray, RayCast};
+
+    // A trough along z, dipping from y = 1 at the rims to y = 0 in the middle
+    fn v_mesh() -> Mesh {
+        let mut positions: Vec<[f32; 3]> = vec![];
+        for z in [-1., 1.] {
+            positions.extend([[-1., 1., z], [0., 0., z], [1., 1., z]]);
+        }
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.set_indices(Some(Indices::U32(vec![
+            0, 1, 4, 0, 4, 3, // Left slope
+            1, 2, 5, 1, 5, 4, // Right slope
+        ])));
+        mesh
+    }
+
+    fn time_of_impact_down(collider: &Collider) -> Option<f32> {
+        let ray = Ray::new(Point::new(0., 2., 0.), Vector::new(0., -1., 0.));
+        collider
+            .shape()
+            .cast_ray(&Isometry::identity(), &ray, 10., true)
+    }
+
+    #[test]
+    fn trimesh_follows_the_dip() {
+        let parts = [v_mesh()];
+
+        let trimesh = ColliderKind::Trimesh.collider(&parts).unwrap();
+        let toi = time_of_impact_down(&trimesh).unwrap();
+        assert!((toi - 2.).abs() < 1e-4, "hit at {toi}, not the floor");
+
+        // The hull closes the dip over at the rims
+        let hull = ColliderKind::ConvexHull.collider(&parts).unwrap();
+        let toi = time_of_impact_down(&hull).unwrap();
+        assert!((toi - 1.).abs() < 1e-4, "hit at {toi}, not the rim");
+    }
+
+    #[test]
+    fn re_added_tag_makes_one_collider() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Mesh>()
+            .init_resource::<ColliderCache>()
+            .init_resource::<PendingColliders>()
+            .init_resource::<ColliderProgress>()
+            .add_event::<CollidersReadyEvent>()
+            .add_systems(Update, (make_collider, insert_ready_colliders).chain());
+
+        let mesh = app
+            .world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Mesh::from(shape::Cube::default()));
+        let part = app.world.spawn((mesh, Transform::default())).id();
+        let entity = app
+            .world
+            .spawn((MakeCollider(true), Transform::default()))
+            .add_child(part)
+            .id();
+
+        app.update();
+        // Like a glTF scene being processed again while the collider is generated
+        app.world
+            .entity_mut(entity)
+            .remove::<MakeCollider>()
+            .insert(MakeCollider(true));
+        app.update();
+        assert_eq!(app.world.resource::<ColliderProgress>().tasks_total, 1);
+
+        // The task finishes on the compute pool in its own time
+        for _ in 0..1000 {
+            if app.world.resource::<PendingColliders>().0.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            app.update();
+        }
+        assert!(app.world.resource::<PendingColliders>().0.is_empty());
+
+        // And again once the collider is in
+        app.world
+            .entity_mut(entity)
+            .remove::<MakeCollider>()
+            .insert(MakeCollider(true));
+        app.update();
+
+        let colliders = app
+            .world
+            .query::<(Entity, &Collider)>()
+            .iter(&app.world)
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        assert_eq!(colliders, [entity]);
+        let bodies = app
+            .world
+            .query::<(Entity, &RigidBody)>()
+            .iter(&app.world)
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        assert_eq!(bodies, [entity]);
+        assert!(app.world.resource::<PendingColliders>().0.is_empty());
+    }
+}
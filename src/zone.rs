@@ -0,0 +1,722 @@
+use std::collections::BTreeSet;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
+use bevy_tnua::controller::TnuaController;
+use bevy_xpbd_3d::prelude::*;
+
+use crate::collectible::LevelCollectibles;
+use crate::mechanism::{find_target, TargetName};
+use crate::player::{GroundPoundEvent, Player, PlayerDiedEvent, PlayerRespawn};
+use crate::terrain::{CurrentLevel, MeshBox};
+
+pub struct ZonePlugin;
+
+impl Plugin for ZonePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<MakeSensor>()
+            .register_type::<ForceZone>()
+            .register_type::<ForceMode>()
+            .register_type::<WindZone>()
+            .register_type::<KillZone>()
+            .register_type::<Checkpoint>()
+            .register_type::<LevelGoal>()
+            .register_type::<Teleporter>()
+            .register_type::<WaterVolume>()
+            .register_type::<CameraZone>()
+            .init_resource::<ActivatedCheckpoints>()
+            .add_event::<SensorOverlap>()
+            .add_event::<CheckpointReachedEvent>()
+            .add_event::<LevelCompleteEvent>()
+            .add_event::<GoalLockedEvent>()
+            .add_event::<TeleportEvent>()
+            .add_systems(
+                Update,
+                (
+                    make_sensor,
+                    make_zone_sensor::<ForceZone>,
+                    make_zone_sensor::<WindZone>,
+                    make_zone_sensor::<KillZone>,
+                    make_zone_sensor::<Checkpoint>,
+                    make_zone_sensor::<LevelGoal>,
+                    make_zone_sensor::<Teleporter>,
+                    make_zone_sensor::<WaterVolume>,
+                    make_zone_sensor::<CameraZone>,
+                ),
+            )
+            // Collision events come from the physics step in `PostUpdate`
+            .add_systems(PostUpdate, emit_sensor_overlap.after(PhysicsSet::Sync))
+            .add_systems(Update, (reach_checkpoint, backtrack_checkpoint, reach_goal))
+            .add_systems(
+                Update,
+                (
+                    apply_force_zone,
+                    apply_impulse_zone,
+                    ground_pound_impulse_zone,
+                    kill_zone,
+                ),
+            )
+            .add_systems(Update, (init_wind_exposure, apply_wind).chain())
+            .add_systems(
+                Update,
+                (init_teleport_exclusion, teleport, end_teleport_exclusion),
+            );
+    }
+}
+
+/// Sensor volume built from the mesh of the glTF node carrying the zone component.
+#[derive(Component)]
+pub struct ZoneSensor {
+    pub zone: Entity,
+    pub half_extents: Vec3,
+}
+
+pub fn make_zone_sensor<T: Component>(
+    mut commands: Commands,
+    zones: Query<Entity, Added<T>>,
+    children: Query<&Children>,
+    has_mesh: Query<(Entity, &Handle<Mesh>, &GlobalTransform)>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for zone in zones.iter() {
+        let Some(mesh_box) = MeshBox::find(zone, &children, &has_mesh, &meshes) else {
+            error!("Zone {zone:?} has no mesh to make a sensor from");
+            continue;
+        };
+
+        let collider = mesh_box.collider();
+        spawn_sensor(
+            &mut commands,
+            zone,
+            &mesh_box,
+            collider,
+            mesh_box.position.0,
+        );
+    }
+}
+
+fn spawn_sensor(
+    commands: &mut Commands,
+    zone: Entity,
+    mesh_box: &MeshBox,
+    collider: Collider,
+    position: Vec3,
+) {
+    commands.entity(mesh_box.mesh_entity).with_children(|cmd| {
+        cmd.spawn((
+            ZoneSensor {
+                zone,
+                half_extents: mesh_box.half_extents,
+            },
+            collider,
+            Sensor,
+            RigidBody::Static,
+            Position(position),
+            Rotation(mesh_box.rotation),
+            CollidingEntities::default(),
+        ));
+    });
+}
+
+/// Plain trigger volume, for reacting to `SensorOverlap` with this node as the zone.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct MakeSensor {
+    /// Fit the mesh by its convex hull rather than its bounding box
+    pub convex_hull: bool,
+}
+
+fn make_sensor(
+    mut commands: Commands,
+    zones: Query<(Entity, &MakeSensor), Added<MakeSensor>>,
+    children: Query<&Children>,
+    has_mesh: Query<(Entity, &Handle<Mesh>, &GlobalTransform)>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for (zone, make_sensor) in zones.iter() {
+        let Some(mesh_box) = MeshBox::find(zone, &children, &has_mesh, &meshes) else {
+            error!("Sensor {zone:?} has no mesh to make a sensor from");
+            continue;
+        };
+
+        if !make_sensor.convex_hull {
+            let collider = mesh_box.collider();
+            spawn_sensor(
+                &mut commands,
+                zone,
+                &mesh_box,
+                collider,
+                mesh_box.position.0,
+            );
+            continue;
+        }
+
+        // The hull is around the mesh origin rather than the box center
+        let Ok((_, mesh, gtransform)) = has_mesh.get(mesh_box.mesh_entity) else {
+            continue;
+        };
+        let Some(VertexAttributeValues::Float32x3(positions)) = meshes
+            .get(mesh)
+            .and_then(|mesh| mesh.attribute(Mesh::ATTRIBUTE_POSITION))
+        else {
+            continue;
+        };
+        let (scale, _, translation) = gtransform.to_scale_rotation_translation();
+        let points = positions.iter().map(|&p| Vec3::from(p) * scale).collect();
+        let Some(collider) = Collider::convex_hull(points) else {
+            error!("Failed to make a convex hull sensor for {zone:?}");
+            continue;
+        };
+        spawn_sensor(&mut commands, zone, &mesh_box, collider, translation);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Overlap {
+    Entered,
+    Exited,
+}
+
+/// Something entering or leaving a zone sensor. Zone components build on this
+/// rather than on the collision events.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SensorOverlap {
+    /// The glTF node carrying the zone component
+    pub zone: Entity,
+    pub sensor: Entity,
+    pub other: Entity,
+    pub overlap: Overlap,
+}
+
+impl SensorOverlap {
+    pub fn entered(&self) -> bool {
+        self.overlap == Overlap::Entered
+    }
+
+    pub fn exited(&self) -> bool {
+        self.overlap == Overlap::Exited
+    }
+}
+
+fn emit_sensor_overlap(
+    mut collision_started: EventReader<CollisionStarted>,
+    mut collision_ended: EventReader<CollisionEnded>,
+    sensors: Query<&ZoneSensor>,
+    mut overlaps: EventWriter<SensorOverlap>,
+) {
+    let started = collision_started
+        .read()
+        .map(|CollisionStarted(entity1, entity2)| (*entity1, *entity2, Overlap::Entered));
+    let ended = collision_ended
+        .read()
+        .map(|CollisionEnded(entity1, entity2)| (*entity1, *entity2, Overlap::Exited));
+
+    for (entity1, entity2, overlap) in started.chain(ended) {
+        let Some((sensor, zone_sensor, other)) = sensor_contact(&sensors, entity1, entity2) else {
+            continue;
+        };
+        overlaps.send(SensorOverlap {
+            zone: zone_sensor.zone,
+            sensor,
+            other,
+            overlap,
+        });
+    }
+}
+
+/// Splits a collision into the zone sensor entity and the entity that touched it.
+fn sensor_contact<'a>(
+    sensors: &'a Query<&ZoneSensor>,
+    entity1: Entity,
+    entity2: Entity,
+) -> Option<(Entity, &'a ZoneSensor, Entity)> {
+    if let Ok(sensor) = sensors.get(entity1) {
+        Some((entity1, sensor, entity2))
+    } else {
+        sensors
+            .get(entity2)
+            .ok()
+            .map(|sensor| (entity2, sensor, entity1))
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Reflect)]
+pub enum ForceMode {
+    /// `force` is an acceleration applied while inside, like wind
+    #[default]
+    Continuous,
+    /// `force` is a velocity change applied once on entering, like a boost pad
+    Impulse,
+}
+
+/// Pushes the player around while they're inside the zone, on top of their own movement.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct ForceZone {
+    pub force: Vec3,
+    pub mode: ForceMode,
+}
+
+fn apply_force_zone(
+    sensors: Query<(&ZoneSensor, &CollidingEntities)>,
+    zones: Query<&ForceZone>,
+    // Player under manual translation (ladders and such) is left alone
+    mut player: Query<&mut LinearVelocity, (With<Player>, With<TnuaController>)>,
+    time: Res<Time>,
+) {
+    for (sensor, colliding) in sensors.iter() {
+        let Ok(zone) = zones.get(sensor.zone) else {
+            continue;
+        };
+        if zone.mode != ForceMode::Continuous {
+            continue;
+        }
+
+        for &entity in colliding.iter() {
+            if let Ok(mut velocity) = player.get_mut(entity) {
+                velocity.0 += zone.force * time.delta_seconds();
+            }
+        }
+    }
+}
+
+fn apply_impulse_zone(
+    mut overlaps: EventReader<SensorOverlap>,
+    zones: Query<&ForceZone>,
+    mut player: Query<&mut LinearVelocity, (With<Player>, With<TnuaController>)>,
+) {
+    for overlap in overlaps.read().filter(|overlap| overlap.entered()) {
+        let Ok(zone) = zones.get(overlap.zone) else {
+            continue;
+        };
+        if zone.mode != ForceMode::Impulse {
+            continue;
+        }
+
+        if let Ok(mut velocity) = player.get_mut(overlap.other) {
+            apply_impulse(&mut velocity, zone.force);
+        }
+    }
+}
+
+// Entering during the slam does nothing, it keeps overriding the velocity,
+// so the pad goes off on the impact instead
+fn ground_pound_impulse_zone(
+    mut ground_pound: EventReader<GroundPoundEvent>,
+    sensors: Query<(&ZoneSensor, &CollidingEntities)>,
+    zones: Query<&ForceZone>,
+    mut player: Query<&mut LinearVelocity, (With<Player>, With<TnuaController>)>,
+) {
+    for ev in ground_pound.read() {
+        let Ok(mut velocity) = player.get_mut(ev.entity) else {
+            continue;
+        };
+        for (sensor, colliding) in sensors.iter() {
+            if !colliding.contains(&ev.entity) {
+                continue;
+            }
+            if let Ok(zone) = zones.get(sensor.zone) {
+                if zone.mode == ForceMode::Impulse {
+                    apply_impulse(&mut velocity, zone.force);
+                }
+            }
+        }
+    }
+}
+
+/// Velocity change of a boost pad, for anything else the player bounces off too.
+pub fn apply_impulse(velocity: &mut LinearVelocity, impulse: Vec3) {
+    velocity.0 += impulse;
+}
+
+/// Horizontal push on the player while inside, on top of their own movement.
+/// With `gust`, the strength rises and falls over time instead of staying steady.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct WindZone {
+    pub direction: Vec3,
+    /// Acceleration at full strength
+    pub strength: f32,
+    pub gust: bool,
+}
+
+/// Wind acceleration on the player this frame, for the sprite to lean into.
+#[derive(Component, Default, Debug)]
+pub struct WindExposure {
+    pub force: Vec3,
+}
+
+// Sum of sines, smooth but irregular enough to pass for gusts
+fn gust_noise(t: f32, seed: f32) -> f32 {
+    ((t * 1.3 + seed * 7.).sin() + (t * 2.9 + seed * 13.).sin() * 0.5) / 1.5
+}
+
+fn init_wind_exposure(mut commands: Commands, player: Query<Entity, Added<Player>>) {
+    for entity in player.iter() {
+        commands.entity(entity).insert(WindExposure::default());
+    }
+}
+
+fn apply_wind(
+    sensors: Query<(&ZoneSensor, &CollidingEntities)>,
+    zones: Query<&WindZone>,
+    mut player: Query<
+        (
+            Entity,
+            &mut WindExposure,
+            &mut LinearVelocity,
+            Has<TnuaController>,
+        ),
+        With<Player>,
+    >,
+    time: Res<Time>,
+) {
+    for (entity, mut exposure, mut velocity, walking) in player.iter_mut() {
+        // Overlapping winds add up
+        let force = sensors
+            .iter()
+            .filter(|(_, colliding)| colliding.contains(&entity))
+            .filter_map(|(sensor, _)| Some((sensor.zone, zones.get(sensor.zone).ok()?)))
+            .map(|(zone_entity, zone)| {
+                let direction = (zone.direction * Vec3::new(1., 0., 1.)).normalize_or_zero();
+                // Zones gust out of step with each other, from calm up to full strength
+                let gust = if zone.gust {
+                    0.5 + 0.5 * gust_noise(time.elapsed_seconds(), zone_entity.index() as f32)
+                } else {
+                    1.
+                };
+                direction * zone.strength * gust
+            })
+            .sum::<Vec3>();
+
+        exposure.force = force;
+        // Player under manual translation (ladders and such) only leans into it
+        if walking {
+            velocity.0 += force * time.delta_seconds();
+        }
+    }
+}
+
+/// Body of water the player swims in. The top of the volume is the surface.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct WaterVolume(pub bool);
+
+/// Camera framing for a room, taking over the follow camera while the player is
+/// inside. Where zones overlap, the highest `priority` wins.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct CameraZone {
+    /// Added to the camera target
+    pub offset: Vec3,
+    pub distance: f32,
+    /// Elevation of the eye, in radians
+    pub pitch: f32,
+    pub priority: i32,
+}
+
+/// Kills the player on touch, e.g. below the level to catch falls into the void.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct KillZone(bool);
+
+fn kill_zone(
+    mut overlaps: EventReader<SensorOverlap>,
+    zones: Query<&KillZone>,
+    player: Query<(), With<Player>>,
+    mut died: EventWriter<PlayerDiedEvent>,
+) {
+    for overlap in overlaps.read().filter(|overlap| overlap.entered()) {
+        if !zones.get(overlap.zone).is_ok_and(|zone| zone.0) {
+            continue;
+        }
+
+        if player.contains(overlap.other) {
+            died.send(PlayerDiedEvent {
+                entity: overlap.other,
+            });
+        }
+    }
+}
+
+/// Respawn point along the way. Later checkpoints have a higher `order`.
+///
+/// The zone's forward (-Z) should point onward through the level, so that
+/// leaving it backwards can be told apart from walking through it.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Checkpoint {
+    pub order: u32,
+}
+
+/// Checkpoints the player has passed through, by `Checkpoint::order`.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct ActivatedCheckpoints {
+    pub activated: BTreeSet<u32>,
+    /// The one the player respawns at
+    pub current: Option<u32>,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct CheckpointReachedEvent {
+    pub player: Entity,
+    pub order: u32,
+}
+
+#[derive(SystemParam)]
+struct CheckpointActivation<'w, 's> {
+    commands: Commands<'w, 's>,
+    checkpoints: ResMut<'w, ActivatedCheckpoints>,
+    reached: EventWriter<'w, CheckpointReachedEvent>,
+}
+
+impl CheckpointActivation<'_, '_> {
+    fn current(&self) -> Option<u32> {
+        self.checkpoints.current
+    }
+
+    fn activate(
+        &mut self,
+        order: u32,
+        player: Entity,
+        (position, rotation): (&Position, &Rotation),
+    ) {
+        self.checkpoints.activated.insert(order);
+        self.checkpoints.current = Some(order);
+        self.commands.insert_resource(PlayerRespawn {
+            transform: Transform::from_translation(position.0).with_rotation(rotation.0),
+        });
+        self.reached.send(CheckpointReachedEvent { player, order });
+
+        debug_print!("checkpoint {order} reached");
+    }
+}
+
+fn reach_checkpoint(
+    mut overlaps: EventReader<SensorOverlap>,
+    sensor_transforms: Query<(&Position, &Rotation)>,
+    zones: Query<&Checkpoint>,
+    player: Query<(), With<Player>>,
+    mut activation: CheckpointActivation,
+) {
+    for overlap in overlaps.read().filter(|overlap| overlap.entered()) {
+        let Ok(checkpoint) = zones.get(overlap.zone) else {
+            continue;
+        };
+        if !player.contains(overlap.other) {
+            continue;
+        }
+
+        // Running back into an earlier checkpoint doesn't make it current,
+        // see `backtrack_checkpoint` for actually going back past it
+        if activation
+            .current()
+            .is_some_and(|current| checkpoint.order < current)
+        {
+            continue;
+        }
+
+        if let Ok(transform) = sensor_transforms.get(overlap.sensor) {
+            activation.activate(checkpoint.order, overlap.other, transform);
+        }
+    }
+}
+
+fn backtrack_checkpoint(
+    mut overlaps: EventReader<SensorOverlap>,
+    sensor_transforms: Query<(&Position, &Rotation)>,
+    zones: Query<&Checkpoint>,
+    player: Query<&Position, With<Player>>,
+    mut activation: CheckpointActivation,
+) {
+    for overlap in overlaps.read().filter(|overlap| overlap.exited()) {
+        let Ok(checkpoint) = zones.get(overlap.zone) else {
+            continue;
+        };
+        let (Ok(player_position), Ok(transform)) = (
+            player.get(overlap.other),
+            sensor_transforms.get(overlap.sensor),
+        ) else {
+            continue;
+        };
+
+        if !activation
+            .current()
+            .is_some_and(|current| checkpoint.order < current)
+        {
+            continue;
+        }
+
+        // Left through the back side, against the level's direction
+        let (position, rotation) = transform;
+        let forward = rotation.0 * Vec3::NEG_Z;
+        if (player_position.0 - position.0).dot(forward) < 0. {
+            activation.activate(checkpoint.order, overlap.other, transform);
+        }
+    }
+}
+
+/// Finish line of the level. Entering it completes the level, going on to
+/// `next_level` or to the results if there's none.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct LevelGoal {
+    /// Path of the level to load next
+    pub next_level: String,
+    /// Stays shut until every collectible of the level has been picked up
+    pub requires_all_collectibles: bool,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct LevelCompleteEvent {
+    pub player: Entity,
+    /// Path of the level that was completed
+    pub level: String,
+    pub next_level: Option<String>,
+}
+
+/// The player reached a goal needing collectibles they haven't got yet.
+#[derive(Event, Clone, Debug)]
+pub struct GoalLockedEvent {
+    pub player: Entity,
+    pub remaining: u32,
+}
+
+fn reach_goal(
+    mut overlaps: EventReader<SensorOverlap>,
+    goals: Query<&LevelGoal>,
+    player: Query<(), With<Player>>,
+    collectibles: Res<LevelCollectibles>,
+    level: Res<CurrentLevel>,
+    mut complete: EventWriter<LevelCompleteEvent>,
+    mut locked: EventWriter<GoalLockedEvent>,
+) {
+    for overlap in overlaps.read().filter(|overlap| overlap.entered()) {
+        let Ok(goal) = goals.get(overlap.zone) else {
+            continue;
+        };
+        if !player.contains(overlap.other) {
+            continue;
+        }
+
+        let remaining = collectibles.total.saturating_sub(collectibles.collected);
+        if goal.requires_all_collectibles && remaining > 0 {
+            locked.send(GoalLockedEvent {
+                player: overlap.other,
+                remaining,
+            });
+            continue;
+        }
+
+        complete.send(LevelCompleteEvent {
+            player: overlap.other,
+            level: level.0.clone(),
+            next_level: Some(goal.next_level.clone()).filter(|next| !next.is_empty()),
+        });
+        // Split-screen players arriving together finish it once
+        return;
+    }
+}
+
+/// Pad sending the player to the teleporter named `destination`.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Teleporter {
+    pub destination: TargetName,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct TeleportEvent {
+    pub player: Entity,
+    pub from: Entity,
+    pub to: Entity,
+}
+
+/// Teleporter the player arrived on, ignored until they step off it so
+/// they aren't bounced straight back.
+#[derive(Component, Default)]
+struct TeleportExclusion(Option<Entity>);
+
+fn init_teleport_exclusion(mut commands: Commands, player: Query<Entity, Added<Player>>) {
+    for entity in player.iter() {
+        commands.entity(entity).insert(TeleportExclusion::default());
+    }
+}
+
+fn teleport(
+    mut overlaps: EventReader<SensorOverlap>,
+    sensor_transforms: Query<(&ZoneSensor, &Position, &Rotation), Without<Player>>,
+    teleporters: Query<&Teleporter>,
+    names: Query<(Entity, &TargetName)>,
+    mut player: Query<
+        (
+            &mut TeleportExclusion,
+            &mut Transform,
+            &mut Position,
+            &mut Rotation,
+            &mut LinearVelocity,
+        ),
+        With<Player>,
+    >,
+    mut teleported: EventWriter<TeleportEvent>,
+) {
+    for overlap in overlaps.read().filter(|overlap| overlap.entered()) {
+        let Ok(teleporter) = teleporters.get(overlap.zone) else {
+            continue;
+        };
+        let Ok((mut exclusion, mut transform, mut position, mut rotation, mut velocity)) =
+            player.get_mut(overlap.other)
+        else {
+            continue;
+        };
+        if exclusion.0 == Some(overlap.zone) {
+            continue;
+        }
+
+        let Some(destination) = find_target(&names, &teleporter.destination) else {
+            warn!("No teleporter named {:?}", teleporter.destination.0);
+            continue;
+        };
+        let Some((_, dest_position, dest_rotation)) = sensor_transforms
+            .iter()
+            .find(|(dest_sensor, _, _)| dest_sensor.zone == destination)
+        else {
+            continue;
+        };
+        let Ok((_, from_position, from_rotation)) = sensor_transforms.get(overlap.sensor) else {
+            continue;
+        };
+
+        // Facing and momentum keep their direction relative to the pads
+        let (from_yaw, _, _) = from_rotation.0.to_euler(EulerRot::YXZ);
+        let (dest_yaw, _, _) = dest_rotation.0.to_euler(EulerRot::YXZ);
+        let turn = Quat::from_rotation_y(dest_yaw - from_yaw);
+
+        position.0 = dest_position.0 + turn * (position.0 - from_position.0);
+        rotation.0 = turn * rotation.0;
+        velocity.0 = turn * velocity.0;
+        transform.translation = position.0;
+        transform.rotation = rotation.0;
+        exclusion.0 = Some(destination);
+
+        teleported.send(TeleportEvent {
+            player: overlap.other,
+            from: overlap.zone,
+            to: destination,
+        });
+    }
+}
+
+fn end_teleport_exclusion(
+    mut overlaps: EventReader<SensorOverlap>,
+    mut player: Query<&mut TeleportExclusion, With<Player>>,
+) {
+    for overlap in overlaps.read().filter(|overlap| overlap.exited()) {
+        if let Ok(mut exclusion) = player.get_mut(overlap.other) {
+            if exclusion.0 == Some(overlap.zone) {
+                exclusion.0 = None;
+            }
+        }
+    }
+}